@@ -0,0 +1,133 @@
+use bb8::Pool;
+use bb8_postgres::PostgresConnectionManager;
+use chrono::{Local, Utc};
+use esl_utils::compat;
+use esl_utils::gateway::Heartbeat;
+use esl_utils::history;
+use esl_utils::prelude::*;
+use esl_utils::price_zone;
+use esl_utils::refresh::{RefreshDecision, RefreshTracker};
+use esl_utils::rules::RuleSets;
+use esl_utils::shard::{self, HashRing, DEFAULT_VNODES};
+use esl_utils::store_config::StoreConfigLoader;
+use esl_utils::update_check::CRATE_VERSION;
+use log::{info, warn};
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+use tokio_postgres::NoTls;
+
+/// The delay between two polls of the labels to print
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// The distance (in meters) beyond which a gateway is not trusted to be in its store
+const MAX_DISTANCE: f64 = 500.;
+
+/// Pushes a label to its vendor.
+///
+/// This crate has no vendor client: plug the Hanshow/Pricer/EasyVCO client of the deployment
+/// here and map its failures to [`VendorError`].
+async fn push(esl: &GenericEsl, provenance_link: &str) -> Result<(), VendorError> {
+    info!("Pushing {} ({}) with {provenance_link}", esl.id, esl.nom);
+    Ok(())
+}
+
+/// The reference wiring of a store gateway.
+///
+/// Reads the Parse configuration (PARSE_*), the gateway identity (GATEWAY_*), the provenance
+/// links (PROVENANCE_*), the Postgres url (DATABASE_URL) and the file caching the store
+/// configuration (STORE_CONFIG_CACHE) from the environment, then polls the labels to print,
+/// applies the price zones, checks the mandatory mentions and pushes them during the push
+/// windows of the store, within the daily refresh budget of each label. The labels are shared
+/// with the other gateways of the store.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let client =
+        ParseClient::from_env().with_latency_budget(LatencyBudget::new(Duration::from_secs(2)));
+    ParseClient::initialize(client.clone()).map_err(|_| "a Parse client is already set")?;
+    let capabilities = compat::probe(&client).await;
+    info!("Parse server capabilities: {capabilities:?}");
+
+    let identity = GatewayIdentity::from_env();
+    let store = Store::verify(&identity, MAX_DISTANCE).await?;
+    let mut gateway = Gateway::find_by_id(identity.gateway_id.clone())
+        .await?
+        .ok_or("this gateway is not registered")?;
+    let links = ProvenanceLinks::from_env();
+    let manager = PostgresConnectionManager::new_from_stringlike(env::var("DATABASE_URL")?, NoTls)?;
+    let pool = Pool::builder().build(manager).await?;
+    let cache = env::var("STORE_CONFIG_CACHE").unwrap_or_else(|_| "store-config.json".to_string());
+    let mut config = StoreConfigLoader::new(store.serial.clone(), cache.into());
+    let mut ring = HashRing::new(std::slice::from_ref(&identity.gateway_id), DEFAULT_VNODES);
+    let mut refresh = RefreshTracker::default();
+    let rule_sets = RuleSets::default();
+
+    loop {
+        if config.reload(&client).await? {
+            info!("Store configuration: {:?}", config.current());
+        }
+        let push_allowed = config
+            .current()
+            .is_none_or(|config| config.push_allowed(Local::now().time()));
+        let rules = rule_sets.for_store(config.current())?;
+        let since = Utc::now() - chrono::Duration::from_std(POLL_INTERVAL * 3)?;
+        let mut members = shard::members(&client, &store.serial, since).await?;
+        members.push(identity.gateway_id.clone());
+        if ring.rebalance(&members) {
+            info!("Sharding the labels between {:?}", ring.members());
+        }
+        let zones = PriceZone::find(store.serial.clone()).await?;
+        let esls = GenericEsl::do_find(store.serial.clone(), pool.clone()).await?;
+        let mut queue_depth = 0;
+        for esl in price_zone::apply(esls, &zones) {
+            if !ring.owns(&identity.gateway_id, &esl.id) {
+                continue;
+            }
+            store.check_esl(&esl)?;
+            let violations = rules.validate(&esl);
+            if !violations.is_empty() {
+                warn!("Not pushing {}: {violations:?}", esl.id);
+                queue_depth += 1;
+                continue;
+            }
+            if !push_allowed {
+                queue_depth += 1;
+                continue;
+            }
+            match refresh.decide(&esl, Utc::now())? {
+                RefreshDecision::Push => {}
+                RefreshDecision::Defer => {
+                    queue_depth += 1;
+                    continue;
+                }
+                RefreshDecision::Unchanged => {
+                    GenericEsl::set_printed(esl, pool.clone()).await?;
+                    continue;
+                }
+            }
+            match push(&esl, &links.esl_link(&esl)).await {
+                Ok(()) => {
+                    refresh.pushed(&esl, Utc::now())?;
+                    history::record(&client, &esl, Utc::now(), None).await?;
+                    GenericEsl::set_printed(esl, pool.clone()).await?;
+                }
+                Err(error) => {
+                    warn!(
+                        "Push of {} failed, retrying in {:?}: {error}",
+                        esl.id,
+                        error.retry_after()
+                    );
+                    queue_depth += 1;
+                }
+            }
+        }
+        let heartbeat = Heartbeat {
+            version: CRATE_VERSION.to_string(),
+            queue_depth,
+            last_sync: Some(Utc::now()),
+            over_budget: refresh.over_budget(Utc::now()).len() as u32,
+        };
+        gateway.heartbeat(heartbeat, Utc::now()).await?;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,231 @@
+use esl_utils::prelude::*;
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::env;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// The configuration of a load test, read from the LOADTEST_* variables
+struct Config {
+    /// The number of synthetic stores
+    stores: usize,
+    /// The number of labels of each store
+    labels: usize,
+    /// The number of requests in flight
+    concurrency: usize,
+    /// The workloads to run, among `save`, `fetch` and `push`
+    workloads: Vec<String>,
+    /// The time the mock vendor takes to push a label
+    push_latency: Duration,
+    /// The Parse class receiving the synthetic labels, it is emptied at the end of the test
+    class: String,
+}
+
+fn var<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let workloads: String = var("LOADTEST_WORKLOADS", "save,fetch,push".to_string());
+        Self {
+            stores: var("LOADTEST_STORES", 200),
+            labels: var("LOADTEST_LABELS", 50),
+            concurrency: var("LOADTEST_CONCURRENCY", 16),
+            workloads: workloads.split(',').map(|w| w.trim().to_string()).collect(),
+            push_latency: Duration::from_millis(var("LOADTEST_PUSH_LATENCY_MS", 50)),
+            class: var("LOADTEST_CLASS", "classes/LoadTestEsl".to_string()),
+        }
+    }
+}
+
+/// The latencies of the requests of a workload
+struct Report {
+    name: &'static str,
+    elapsed: Duration,
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Report {
+    fn percentile(&self, percent: usize) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let index = (self.latencies.len() * percent / 100).min(self.latencies.len() - 1);
+        self.latencies[index]
+    }
+
+    fn print(&self) {
+        let total = self.latencies.len() + self.errors;
+        let throughput = total as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+        println!(
+            "{:<6} {total:>7} requests {:>5} errors {throughput:>9.1}/s  p50 {:>5}ms  p90 {:>5}ms  p99 {:>5}ms  max {:>5}ms",
+            self.name,
+            self.errors,
+            self.percentile(50).as_millis(),
+            self.percentile(90).as_millis(),
+            self.percentile(99).as_millis(),
+            self.percentile(100).as_millis()
+        );
+    }
+}
+
+/// Runs the requests of a workload `concurrency` at a time and measures each of them
+async fn run<F, E>(name: &'static str, concurrency: usize, requests: Vec<F>) -> Report
+where
+    F: std::future::Future<Output = Result<(), E>>,
+    E: std::fmt::Display,
+{
+    info!("Running {} {name} requests", requests.len());
+    let start = Instant::now();
+    let results: Vec<Result<Duration, E>> = stream::iter(requests)
+        .map(|request| async move {
+            let sent = Instant::now();
+            request.await.map(|()| sent.elapsed())
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    let mut report = Report {
+        name,
+        elapsed: start.elapsed(),
+        latencies: vec![],
+        errors: 0,
+    };
+    for result in results {
+        match result {
+            Ok(latency) => report.latencies.push(latency),
+            Err(error) => {
+                warn!("{name} failed: {error}");
+                report.errors += 1;
+            }
+        }
+    }
+    report.latencies.sort();
+    report
+}
+
+/// A synthetic label of a store
+fn esl(serial: &str, index: usize) -> GenericEsl {
+    GenericEsl {
+        r#type: EslType::Pricer,
+        serial: serial.to_string(),
+        printed: false,
+        object_id: None,
+        item_id: Some(format!("item-{index}")),
+        id: Uuid::new_v4().to_string(),
+        nom: format!("Article {index}"),
+        nom_scientifique: "Gadus morhua".to_string(),
+        prix: format!("{},{:02}", 5 + index % 40, index % 100),
+        infos_prix: "€/kg".to_string(),
+        engin: Some("Chaluts".to_string()),
+        zone: Some("Atlantique Nord-Est".to_string()),
+        zone_code: Some("27".to_string()),
+        sous_zone: None,
+        sous_zone_code: None,
+        plu: format!("{index:04}"),
+        taille: None,
+        congel_infos: None,
+        origine: None,
+        allergenes: None,
+        label: None,
+        production: Some("Pêché".to_string()),
+        tva: None,
+        categorie: None,
+        achats: None,
+        stock: None,
+        arrivage: None,
+        geo_point: None,
+    }
+}
+
+/// A vendor answering after a fixed delay
+async fn push(_esl: &GenericEsl, latency: Duration) -> Result<(), VendorError> {
+    tokio::time::sleep(latency).await;
+    Ok(())
+}
+
+/// Drives synthetic save/fetch/push workloads against a Parse server.
+///
+/// Reads the Parse configuration (PARSE_*) and the test configuration (LOADTEST_*) from the
+/// environment, then prints the throughput and latency percentiles of each workload. Point it to
+/// a staging server: the synthetic labels are written to LOADTEST_CLASS.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let config = Config::from_env();
+    let client = ParseClient::from_env();
+    let serials: Vec<String> = (0..config.stores)
+        .map(|_| format!("loadtest-{}", Uuid::new_v4()))
+        .collect();
+    let esls: Vec<GenericEsl> = serials
+        .iter()
+        .flat_map(|serial| (0..config.labels).map(move |index| esl(serial, index)))
+        .collect();
+    let mut reports = vec![];
+
+    for workload in &config.workloads {
+        let report = match workload.as_str() {
+            "save" => {
+                let requests = esls.iter().map(|esl| {
+                    let (client, class) = (&client, config.class.clone());
+                    async move { client.save(class, esl).await.map(|_| ()) }
+                });
+                run("save", config.concurrency, requests.collect()).await
+            }
+            "fetch" => {
+                let requests = serials.iter().map(|serial| {
+                    let (client, class) = (&client, config.class.clone());
+                    async move {
+                        let options = FetchOptions::default().limit(config.labels);
+                        client
+                            .fetch_with::<Value, _>(class, json!({ "serial": serial }), &options)
+                            .await
+                            .map(|_| ())
+                    }
+                });
+                run("fetch", config.concurrency, requests.collect()).await
+            }
+            "push" => {
+                let requests = esls.iter().map(|esl| push(esl, config.push_latency));
+                run("push", config.concurrency, requests.collect()).await
+            }
+            other => {
+                warn!("Unknown workload {other}");
+                continue;
+            }
+        };
+        reports.push(report);
+    }
+
+    if config.workloads.iter().any(|workload| workload == "save") {
+        for serial in &serials {
+            let confirmation = client
+                .delete_confirmation(config.class.clone(), json!({ "serial": serial }))
+                .await?;
+            let options = DeleteOptions {
+                confirm: confirmation.token,
+                max_count: config.labels,
+            };
+            client
+                .delete_where(config.class.clone(), json!({ "serial": serial }), options)
+                .await?;
+        }
+    }
+
+    println!(
+        "{} stores, {} labels each, {} requests in flight",
+        config.stores, config.labels, config.concurrency
+    );
+    for report in &reports {
+        report.print();
+    }
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The key of the public permissions in a Parse ACL
+const PUBLIC: &str = "*";
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// The permissions of a user, a role or the public
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Permission {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub read: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub write: bool,
+}
+
+impl Permission {
+    pub fn new(read: bool, write: bool) -> Self {
+        Self { read, write }
+    }
+}
+
+/// The access control list of a Parse object.
+///
+/// An object without an ACL is readable and writable by anyone holding the REST key. Once an
+/// ACL is set, only the public, users (by objectId) and roles it names have access, the master
+/// key always has.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Acl(BTreeMap<String, Permission>);
+
+impl Acl {
+    /// An ACL giving access to nobody but the master key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn set(mut self, key: String, permission: Permission) -> Self {
+        if permission == Permission::default() {
+            self.0.remove(&key);
+        } else {
+            self.0.insert(key, permission);
+        }
+        self
+    }
+
+    pub fn public(self, read: bool, write: bool) -> Self {
+        self.set(PUBLIC.to_string(), Permission::new(read, write))
+    }
+
+    /// Sets the permissions of a user, by its objectId
+    pub fn user(self, user_id: &str, read: bool, write: bool) -> Self {
+        self.set(user_id.to_string(), Permission::new(read, write))
+    }
+
+    /// Sets the permissions of the users of a role, by its name
+    pub fn role(self, role: &str, read: bool, write: bool) -> Self {
+        self.set(format!("role:{role}"), Permission::new(read, write))
+    }
+
+    pub fn public_permission(&self) -> Permission {
+        self.0.get(PUBLIC).copied().unwrap_or_default()
+    }
+
+    pub fn user_permission(&self, user_id: &str) -> Permission {
+        self.0.get(user_id).copied().unwrap_or_default()
+    }
+
+    pub fn role_permission(&self, role: &str) -> Permission {
+        self.0
+            .get(&format!("role:{role}"))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// An object saved with an ACL, such as `client.save(path, WithAcl::new(&esl, acl))`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WithAcl<T> {
+    #[serde(flatten)]
+    pub object: T,
+    #[serde(rename = "ACL")]
+    pub acl: Acl,
+}
+
+impl<T> WithAcl<T> {
+    pub fn new(object: T, acl: Acl) -> Self {
+        Self { object, acl }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::json;
+
+    #[test]
+    fn serialize() {
+        let acl = Acl::new()
+            .public(true, false)
+            .user("u1", true, true)
+            .role("store-s1", true, true)
+            .role("fishmonger", true, false)
+            .role("fishmonger", false, false);
+        assert_eq!(
+            serde_json::to_value(&acl).unwrap(),
+            json!({
+                "*": { "read": true },
+                "u1": { "read": true, "write": true },
+                "role:store-s1": { "read": true, "write": true },
+            })
+        );
+        let parsed: Acl = serde_json::from_value(serde_json::to_value(&acl).unwrap()).unwrap();
+        assert_eq!(parsed, acl);
+        assert_eq!(parsed.public_permission(), Permission::new(true, false));
+        assert_eq!(parsed.role_permission("fishmonger"), Permission::default());
+        assert!(parsed.user_permission("u1").write);
+    }
+
+    #[test]
+    fn attach() {
+        let acl = Acl::new().role("store-serial", true, true);
+        let value = serde_json::to_value(WithAcl::new(mock::esl(), acl.clone())).unwrap();
+        assert_eq!(value["serial"], "serial");
+        assert_eq!(value["ACL"]["role:store-serial"]["write"], true);
+        let parsed: WithAcl<crate::generic_esl::GenericEsl> =
+            serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.acl, acl);
+        assert_eq!(parsed.object, mock::esl());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::parse::{parse_field_name, ParseClient, ParseError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Returns the aggregate path of a class path, `aggregate/Esl` for `classes/Esl`
+fn aggregate_path(path: &str) -> Result<String, ParseError> {
+    let class_name = path
+        .trim_start_matches('/')
+        .strip_prefix("classes/")
+        .ok_or_else(|| ParseError::Query {
+            cause: format!("{path} is not a class path, cannot aggregate it"),
+        })?;
+    Ok(format!("aggregate/{class_name}"))
+}
+
+/// Queries computed by the Parse server.
+///
+/// Parse only accepts them with the master key, they are sent with it when the client has one
+/// (see [`ParseClient::with_master_key`]). The server must be 2.7.0 or later, see
+/// [`Capabilities::aggregate`](crate::compat::Capabilities::aggregate).
+impl ParseClient {
+    /// Returns the distinct values of a column among the objects matching a query.
+    ///
+    /// The column is given as a Rust field name, `zone_code` for `zoneCode`.
+    pub async fn distinct<T: for<'de> Deserialize<'de>, U: Serialize>(
+        &self,
+        path: String,
+        field: &str,
+        query: U,
+    ) -> Result<Vec<T>, ParseError> {
+        let params = [("distinct", parse_field_name(field))];
+        self.as_master()
+            .fetch_params(aggregate_path(&path)?, query, &params)
+            .await
+    }
+
+    /// Runs an aggregation pipeline, such as
+    /// `[{"$group": {"_id": "$categorie", "count": {"$sum": 1}}}]`.
+    ///
+    /// Parse returns the `_id` of groups as their `objectId`.
+    pub async fn aggregate<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: String,
+        pipeline: &serde_json::Value,
+    ) -> Result<Vec<T>, ParseError> {
+        let params = [("pipeline", serde_json::to_string(pipeline)?)];
+        self.as_master()
+            .fetch_params(aggregate_path(&path)?, json!({}), &params)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn distinct() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"{"results":["27","37"]}"#,
+        )]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_master_key("master".to_string());
+        let zones: Vec<String> = client
+            .distinct(
+                "classes/Esl".to_string(),
+                "zone_code",
+                json!({ "serial": "s1" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(zones, vec!["27", "37"]);
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("GET /aggregate/Esl?where="));
+        assert!(request.contains("&distinct=zoneCode"));
+        assert!(request.contains("x-parse-master-key: master"));
+    }
+
+    #[tokio::test]
+    async fn aggregate() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"{"results":[{"objectId":"Poisson","count":12}]}"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let pipeline = json!([{ "$group": { "_id": "$categorie", "count": { "$sum": 1 } } }]);
+        let groups: Vec<Value> = client
+            .aggregate("classes/Esl".to_string(), &pipeline)
+            .await
+            .unwrap();
+        assert_eq!(groups[0]["count"], 12);
+        assert!(server.join().unwrap()[0].contains("&pipeline=%5B%7B%22%24group"));
+
+        assert!(matches!(
+            client
+                .aggregate::<Value>("users".to_string(), &pipeline)
+                .await,
+            Err(ParseError::Query { .. })
+        ));
+    }
+}
@@ -0,0 +1,495 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// The maximum number of operations of a Parse batch request
+pub const BATCH_SIZE: usize = 50;
+
+/// The default maximum size of the body of a batch request, the default `maxUploadSize` of Parse
+/// Server, see [`ParseClient::with_batch_max_bytes`]
+pub const MAX_BATCH_BYTES: usize = 20 * 1024 * 1024;
+
+/// The size of the `{"requests":[]}` around the operations of a batch request
+const ENVELOPE_BYTES: usize = 15;
+
+/// An operation of a Parse batch request, `path` is relative to the mount point like
+/// `classes/Esl` or `classes/Esl/<objectId>`
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchOperation {
+    Create { path: String, body: Value },
+    Update { path: String, body: Value },
+    Delete { path: String },
+}
+
+impl BatchOperation {
+    pub fn create<T: Serialize>(path: String, data: &T) -> Result<Self, ParseError> {
+        Ok(BatchOperation::Create {
+            path,
+            body: serde_json::to_value(data)?,
+        })
+    }
+
+    pub fn update<T: Serialize>(path: String, data: &T) -> Result<Self, ParseError> {
+        Ok(BatchOperation::Update {
+            path,
+            body: serde_json::to_value(data)?,
+        })
+    }
+
+    pub fn delete(path: String) -> Self {
+        BatchOperation::Delete { path }
+    }
+
+    fn body(&self) -> Option<&Value> {
+        match self {
+            BatchOperation::Create { body, .. } | BatchOperation::Update { body, .. } => Some(body),
+            BatchOperation::Delete { .. } => None,
+        }
+    }
+
+    /// The path of the object modified, for the updates and deletes
+    fn target(&self) -> Option<&str> {
+        match self {
+            BatchOperation::Update { path, .. } | BatchOperation::Delete { path } => Some(path),
+            BatchOperation::Create { .. } => None,
+        }
+    }
+
+    fn to_request(&self, client: &ParseClient) -> Value {
+        let endpoint = client.endpoint();
+        match self {
+            BatchOperation::Create { path, body } => {
+                json!({ "method": "POST", "path": endpoint.mounted_path(path), "body": body })
+            }
+            BatchOperation::Update { path, body } => {
+                json!({ "method": "PUT", "path": endpoint.mounted_path(path), "body": body })
+            }
+            BatchOperation::Delete { path } => {
+                json!({ "method": "DELETE", "path": endpoint.mounted_path(path) })
+            }
+        }
+    }
+}
+
+/// The error of a single operation of a batch request
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BatchError {
+    /// The Parse error code
+    pub code: i32,
+    pub error: String,
+}
+
+/// The result of an operation: the `success` object of Parse, such as `{"objectId": ...}` for a
+/// creation, or its error
+pub type BatchResult = Result<Value, BatchError>;
+
+/// A batch whose requests did not all go through, see [`ParseClient::batch`]
+#[derive(Debug)]
+pub struct BatchFailure {
+    /// The result of each operation, `None` for the operations whose request failed or was not
+    /// sent
+    pub results: Vec<Option<BatchResult>>,
+    /// Why the request failed
+    pub error: ParseError,
+}
+
+impl BatchFailure {
+    /// The number of operations applied before the failure
+    pub fn applied(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| matches!(result, Some(Ok(_))))
+            .count()
+    }
+}
+
+impl From<BatchFailure> for ParseError {
+    fn from(failure: BatchFailure) -> Self {
+        failure.error
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    success: Option<Value>,
+    error: Option<BatchError>,
+}
+
+/// The bounds of [`BatchTuner`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchLimits {
+    pub min_size: usize,
+    /// At most [`BATCH_SIZE`]
+    pub max_size: usize,
+    /// The maximum number of batch requests in flight
+    pub max_concurrency: usize,
+    /// The latency above which batches are shrunk
+    pub target_latency: Duration,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            min_size: 5,
+            max_size: BATCH_SIZE,
+            max_concurrency: 4,
+            target_latency: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Tunes the size and concurrency of batch requests from their observed latency and errors.
+///
+/// Batches grow by a few operations while requests are fast, then more requests are sent in
+/// parallel. A slow or failed request halves the batch size and removes a parallel request, so a
+/// store on a 3G link converges to small sequential batches while one on fiber sends full
+/// batches in parallel. Keep one tuner per store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchTuner {
+    limits: BatchLimits,
+    size: usize,
+    concurrency: usize,
+}
+
+impl BatchTuner {
+    /// Starts from the smallest batches, sent one at a time
+    pub fn new(limits: BatchLimits) -> Self {
+        let max_size = limits.max_size.clamp(1, BATCH_SIZE);
+        let limits = BatchLimits {
+            min_size: limits.min_size.clamp(1, max_size),
+            max_size,
+            max_concurrency: limits.max_concurrency.max(1),
+            ..limits
+        };
+        Self {
+            limits,
+            size: limits.min_size,
+            concurrency: 1,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Records the outcome of a batch request
+    pub fn observe(&mut self, latency: Duration, success: bool) {
+        let limits = &self.limits;
+        if !success || latency > limits.target_latency {
+            self.size = (self.size / 2).max(limits.min_size);
+            self.concurrency = (self.concurrency - 1).max(1);
+        } else if latency < limits.target_latency / 2 {
+            if self.size < limits.max_size {
+                self.size = (self.size + limits.min_size).min(limits.max_size);
+            } else {
+                self.concurrency = (self.concurrency + 1).min(limits.max_concurrency);
+            }
+        }
+    }
+}
+
+impl ParseClient {
+    /// Splits operations into chunks of at most `size` operations whose request body fits in
+    /// [`ParseClient::with_batch_max_bytes`].
+    ///
+    /// An operation larger than the limit on its own is sent alone, and refused by the server.
+    fn chunks<'a>(
+        &self,
+        operations: &'a [BatchOperation],
+        size: usize,
+    ) -> Result<Vec<&'a [BatchOperation]>, ParseError> {
+        let max_bytes = self.batch_max_bytes();
+        let mut chunks = vec![];
+        let (mut start, mut bytes) = (0, ENVELOPE_BYTES);
+        for (index, operation) in operations.iter().enumerate() {
+            // The operation and the comma separating it from the next one
+            let len = serde_json::to_vec(&operation.to_request(self))?.len() + 1;
+            if index > start && (index - start == size || bytes + len > max_bytes) {
+                chunks.push(&operations[start..index]);
+                (start, bytes) = (index, ENVELOPE_BYTES);
+            }
+            bytes += len;
+        }
+        if start < operations.len() {
+            chunks.push(&operations[start..]);
+        }
+        Ok(chunks)
+    }
+
+    /// Sends a single batch request
+    async fn send_batch(&self, chunk: &[BatchOperation]) -> Result<Vec<BatchResult>, ParseError> {
+        let requests: Vec<Value> = chunk.iter().map(|op| op.to_request(self)).collect();
+        let request = self.protocol().batch(&Value::Array(requests))?;
+        let (response, _) = self.send(request).await?;
+        let responses: Vec<BatchResponse> = protocol::interpret(&response, StatusCode::OK)?;
+        if responses.len() != chunk.len() {
+            return Err(ParseError::Platform {
+                code: response.status(),
+                cause: format!(
+                    "{} results returned for {} batch operations",
+                    responses.len(),
+                    chunk.len()
+                ),
+            });
+        }
+        Ok(responses
+            .into_iter()
+            .map(|response| match (response.success, response.error) {
+                (_, Some(error)) => Err(error),
+                (success, None) => Ok(success.unwrap_or(Value::Null)),
+            })
+            .collect())
+    }
+
+    /// Sends operations through the Parse batch API.
+    ///
+    /// Operations are sent [`BATCH_SIZE`] at a time, fewer when their body would exceed
+    /// [`ParseClient::with_batch_max_bytes`]. Each one succeeds or fails on its own, the results
+    /// are returned in the order of the operations. A request refused as a whole
+    /// returns a [`BatchFailure`] with the results of the chunks sent before it, the following
+    /// chunks are not sent.
+    pub async fn batch(
+        &self,
+        operations: &[BatchOperation],
+    ) -> Result<Vec<BatchResult>, BatchFailure> {
+        let fail = |results: Vec<BatchResult>, error| {
+            let mut results: Vec<Option<BatchResult>> = results.into_iter().map(Some).collect();
+            results.resize(operations.len(), None);
+            BatchFailure { results, error }
+        };
+        if let Err(error) = self
+            .check_writes(
+                operations.iter().filter_map(BatchOperation::body),
+                operations.iter().filter_map(BatchOperation::target),
+            )
+            .await
+        {
+            return Err(fail(vec![], error));
+        }
+        let chunks = self
+            .chunks(operations, BATCH_SIZE)
+            .map_err(|error| fail(vec![], error))?;
+        let mut results = Vec::with_capacity(operations.len());
+        for chunk in chunks {
+            match self.send_batch(chunk).await {
+                Ok(chunk_results) => results.extend(chunk_results),
+                Err(error) => return Err(fail(results, error)),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Sends operations through the Parse batch API, with the batch size and concurrency of a
+    /// tuner, which learns from every request.
+    ///
+    /// Batches are also split by [`ParseClient::with_batch_max_bytes`], as with
+    /// [`ParseClient::batch`].
+    ///
+    /// Results are returned in the order of the operations. A request refused as a whole
+    /// returns a [`BatchFailure`] with the results of the requests that went through, the ones
+    /// sent along with it included.
+    pub async fn batch_adaptive(
+        &self,
+        operations: &[BatchOperation],
+        tuner: &mut BatchTuner,
+    ) -> Result<Vec<BatchResult>, BatchFailure> {
+        let mut results: Vec<Option<BatchResult>> = vec![None; operations.len()];
+        if let Err(error) = self
+            .check_writes(
+                operations.iter().filter_map(BatchOperation::body),
+                operations.iter().filter_map(BatchOperation::target),
+            )
+            .await
+        {
+            return Err(BatchFailure { results, error });
+        }
+        let mut sent = 0;
+        while sent < operations.len() {
+            let size = tuner.size();
+            let wave_len = (size * tuner.concurrency()).min(operations.len() - sent);
+            let mut chunks = match self.chunks(&operations[sent..sent + wave_len], size) {
+                Ok(chunks) => chunks,
+                Err(error) => return Err(BatchFailure { results, error }),
+            };
+            chunks.truncate(tuner.concurrency());
+            let requests = chunks.into_iter().map(|chunk| {
+                let offset = sent;
+                sent += chunk.len();
+                async move {
+                    let started = Instant::now();
+                    let result = self.send_batch(chunk).await;
+                    (offset, result, started.elapsed())
+                }
+            });
+            let mut error = None;
+            for (offset, result, latency) in futures::future::join_all(requests).await {
+                tuner.observe(latency, result.is_ok());
+                match result {
+                    Ok(chunk_results) => {
+                        for (index, result) in chunk_results.into_iter().enumerate() {
+                            results[offset + index] = Some(result);
+                        }
+                    }
+                    Err(e) => error = error.or(Some(e)),
+                }
+            }
+            if let Some(error) = error {
+                return Err(BatchFailure { results, error });
+            }
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn batch() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"[{"success":{"objectId":"a","createdAt":"now"}},{"error":{"code":101,"error":"Object not found."}}]"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, format!("{url}/parse"));
+        let operations = [
+            BatchOperation::create("classes/Esl".to_string(), &mock::esl()).unwrap(),
+            BatchOperation::delete("classes/Esl/missing".to_string()),
+        ];
+        let results = client.batch(&operations).await.unwrap();
+        assert_eq!(results[0].as_ref().unwrap()["objectId"], "a");
+        assert_eq!(results[1].as_ref().unwrap_err().code, 101);
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("POST /parse/batch"));
+        assert!(request.contains(r#""method":"POST","path":"/parse/classes/Esl""#));
+        assert!(request.contains(r#""method":"DELETE","path":"/parse/classes/Esl/missing""#));
+    }
+
+    #[tokio::test]
+    async fn batch_failure() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                &format!("[{}]", [r#"{"success":{}}"#; 50].join(",")),
+            ),
+            mock::response("200 OK", &[], r#"[{"success":{}}]"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let operations: Vec<BatchOperation> = (0..52)
+            .map(|i| BatchOperation::delete(format!("classes/Esl/{i}")))
+            .collect();
+        let failure = client.batch(&operations).await.unwrap_err();
+        assert!(matches!(failure.error, ParseError::Platform { .. }));
+        assert_eq!(failure.applied(), 50);
+        assert_eq!(failure.results.len(), 52);
+        assert!(failure.results[50].is_none());
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn split_by_size() {
+        let success = |count: usize| {
+            let results = vec![r#"{"success":{}}"#; count].join(",");
+            mock::response("200 OK", &[], &format!("[{results}]"))
+        };
+        let (url, server) = mock::serve(vec![success(2), success(2), success(1)]);
+        let client = ParseClient::new("app".to_string(), None, url).with_batch_max_bytes(2500);
+        let operations: Vec<BatchOperation> = (0..5)
+            .map(|i| {
+                let esl = json!({ "eslId": format!("e{i}"), "nom": "x".repeat(1000) });
+                BatchOperation::create("classes/Esl".to_string(), &esl).unwrap()
+            })
+            .collect();
+        let results = client.batch(&operations).await.unwrap();
+        assert_eq!(results.len(), 5);
+        let requests = server.join().unwrap();
+        let counts: Vec<usize> = requests
+            .iter()
+            .map(|request| request.matches(r#""method":"POST""#).count())
+            .collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+        assert!(requests[2].contains(r#""eslId":"e4""#));
+    }
+
+    #[test]
+    fn tuner() {
+        let mut tuner = BatchTuner::new(BatchLimits {
+            min_size: 10,
+            max_size: 30,
+            max_concurrency: 2,
+            target_latency: Duration::from_secs(2),
+        });
+        let fast = Duration::from_millis(300);
+        assert_eq!((tuner.size(), tuner.concurrency()), (10, 1));
+        tuner.observe(fast, true);
+        tuner.observe(fast, true);
+        assert_eq!((tuner.size(), tuner.concurrency()), (30, 1));
+        tuner.observe(fast, true);
+        tuner.observe(fast, true);
+        assert_eq!((tuner.size(), tuner.concurrency()), (30, 2));
+        tuner.observe(Duration::from_millis(1500), true);
+        assert_eq!((tuner.size(), tuner.concurrency()), (30, 2));
+        tuner.observe(Duration::from_secs(5), true);
+        assert_eq!((tuner.size(), tuner.concurrency()), (15, 1));
+        tuner.observe(fast, false);
+        assert_eq!((tuner.size(), tuner.concurrency()), (10, 1));
+    }
+
+    #[tokio::test]
+    async fn adaptive() {
+        let success = |count: usize| {
+            let results = vec![r#"{"success":{}}"#; count].join(",");
+            mock::response("200 OK", &[], &format!("[{results}]"))
+        };
+        let (url, server) = mock::serve(vec![success(2), success(4), success(1)]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut tuner = BatchTuner::new(BatchLimits {
+            min_size: 2,
+            max_size: 4,
+            max_concurrency: 1,
+            target_latency: Duration::from_secs(10),
+        });
+        let operations: Vec<BatchOperation> = (0..7)
+            .map(|i| BatchOperation::delete(format!("classes/Esl/{i}")))
+            .collect();
+        let results = client
+            .batch_adaptive(&operations, &mut tuner)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].matches("DELETE").count(), 2);
+        assert_eq!(requests[1].matches("DELETE").count(), 4);
+        assert!(requests[2].contains("classes/Esl/6"));
+
+        let (url, server) = mock::serve(vec![
+            success(2),
+            mock::response("500 Internal Server Error", &[], "{}"),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut tuner = BatchTuner::new(BatchLimits {
+            min_size: 2,
+            max_size: 2,
+            max_concurrency: 1,
+            target_latency: Duration::from_secs(10),
+        });
+        let failure = client
+            .batch_adaptive(&operations, &mut tuner)
+            .await
+            .unwrap_err();
+        assert_eq!(failure.applied(), 2);
+        assert!(failure.results[..2].iter().all(Option::is_some));
+        assert!(failure.results[2..].iter().all(Option::is_none));
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+}
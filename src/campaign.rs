@@ -0,0 +1,266 @@
+#[cfg(feature = "postgres")]
+use crate::generic_esl::GenericEsl;
+#[cfg(feature = "postgres")]
+use crate::mentions::CongelState;
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+#[cfg(feature = "postgres")]
+use crate::price;
+#[cfg(feature = "postgres")]
+use bb8::Pool;
+#[cfg(feature = "postgres")]
+use bb8_postgres::PostgresConnectionManager;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "postgres")]
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+#[cfg(feature = "postgres")]
+use tokio_postgres::NoTls;
+
+/// The Parse class holding the campaigns
+pub const CAMPAIGN_CLASS: &str = "classes/Campaign";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CampaignStatus {
+    /// The campaign has not started yet
+    Scheduled,
+    /// The discounted prices are displayed
+    Active,
+    /// The original prices are displayed again
+    Ended,
+}
+
+/// What a campaign has to do at a given time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CampaignStep {
+    Apply,
+    Revert,
+    /// The campaign ended before it was ever applied
+    Skip,
+}
+
+/// A flash sale: a discount applied to many labels of a store between two dates.
+///
+/// The campaign state is stored in Parse so it can be resumed after a gateway restart, see
+/// [`Campaign::run`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Campaign {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub serial: String,
+    pub name: String,
+    /// The discount in percent
+    pub discount: f64,
+    #[serde(with = "crate::date")]
+    pub start_date: DateTime<Utc>,
+    #[serde(with = "crate::date")]
+    pub end_date: DateTime<Utc>,
+    /// The `eslId`s of the labels taking part in the campaign
+    pub esls: Vec<String>,
+    pub status: CampaignStatus,
+    /// The prices displayed before the campaign started, by `eslId`
+    #[serde(default)]
+    pub original_prices: HashMap<String, String>,
+}
+
+impl Campaign {
+    pub fn new(
+        serial: String,
+        name: String,
+        discount: f64,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+        esls: Vec<String>,
+    ) -> Self {
+        Self {
+            object_id: None,
+            serial,
+            name,
+            discount,
+            start_date,
+            end_date,
+            esls,
+            status: CampaignStatus::Scheduled,
+            original_prices: HashMap::new(),
+        }
+    }
+
+    /// Returns the path of this campaign on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", CAMPAIGN_CLASS, object_id))
+    }
+
+    /// Returns what has to be done for this campaign at `now`, if anything
+    pub fn next_step(&self, now: DateTime<Utc>) -> Option<CampaignStep> {
+        match self.status {
+            CampaignStatus::Scheduled if now >= self.end_date => Some(CampaignStep::Skip),
+            CampaignStatus::Scheduled if now >= self.start_date => Some(CampaignStep::Apply),
+            CampaignStatus::Active if now >= self.end_date => Some(CampaignStep::Revert),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    /// Applies or reverts the campaign depending on `now`.
+    ///
+    /// Original prices are saved to Parse before any label is modified and every step only
+    /// depends on the saved state, so calling `run` again after an interruption finishes the
+    /// work instead of discounting a label twice. Thawed products keep their price, see
+    /// [`CongelState::allows_markdown`].
+    ///
+    /// Returns the step that was executed
+    pub async fn run(
+        &mut self,
+        now: DateTime<Utc>,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Option<CampaignStep>, ParseError> {
+        let step = self.next_step(now);
+        match step {
+            Some(CampaignStep::Apply) => self.apply(pool).await?,
+            Some(CampaignStep::Revert) => self.revert(pool).await?,
+            Some(CampaignStep::Skip) => {
+                self.status = CampaignStatus::Ended;
+                self.update().await?;
+            }
+            None => {}
+        }
+        Ok(step)
+    }
+
+    #[cfg(feature = "postgres")]
+    async fn apply(
+        &mut self,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<(), ParseError> {
+        info!("Applying campaign {} on {}", self.name, self.serial);
+        let (esls, thawed): (Vec<_>, Vec<_>) =
+            GenericEsl::find_by_ids(&self.serial, &self.esls, pool.clone())
+                .await?
+                .into_iter()
+                .partition(|esl| CongelState::of(esl).allows_markdown());
+        for esl in &thawed {
+            warn!(
+                "Campaign {}: {} is thawed, not discounted",
+                self.name, esl.id
+            );
+        }
+        for esl in &esls {
+            self.original_prices
+                .entry(esl.id.clone())
+                .or_insert_with(|| esl.prix.clone());
+        }
+        self.update().await?;
+        for esl in esls {
+            let original = &self.original_prices[&esl.id];
+            match price::discount(original, self.discount) {
+                Some(prix) if prix != esl.prix => {
+                    GenericEsl::set_prix(esl, prix, pool.clone()).await?;
+                }
+                Some(_) => {}
+                None => warn!("Campaign {}: cannot parse price {original}", self.name),
+            }
+        }
+        self.status = CampaignStatus::Active;
+        self.update().await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "postgres")]
+    async fn revert(
+        &mut self,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<(), ParseError> {
+        info!("Reverting campaign {} on {}", self.name, self.serial);
+        let esls = GenericEsl::find_by_ids(&self.serial, &self.esls, pool.clone()).await?;
+        for esl in esls {
+            if let Some(original) = self.original_prices.get(&esl.id) {
+                if *original != esl.prix {
+                    GenericEsl::set_prix(esl, original.clone(), pool.clone()).await?;
+                }
+            }
+        }
+        self.status = CampaignStatus::Ended;
+        self.update().await?;
+        Ok(())
+    }
+}
+
+impl ParseClass for Campaign {
+    const CLASS_NAME: &'static str = "Campaign";
+}
+
+impl ParseObject for Campaign {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(CAMPAIGN_CLASS.to_string(), self).await
+    }
+
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(CAMPAIGN_CLASS.to_string(), json!({ "serial": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn campaign() -> Campaign {
+        Campaign::new(
+            "serial".to_string(),
+            "Fête des mères".to_string(),
+            20.,
+            Utc.with_ymd_and_hms(2023, 5, 26, 8, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2023, 5, 28, 20, 0, 0).unwrap(),
+            vec!["esl".to_string()],
+        )
+    }
+
+    #[test]
+    fn next_step() {
+        let mut campaign = campaign();
+        let before = Utc.with_ymd_and_hms(2023, 5, 25, 8, 0, 0).unwrap();
+        let during = Utc.with_ymd_and_hms(2023, 5, 27, 8, 0, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2023, 5, 29, 8, 0, 0).unwrap();
+        assert_eq!(campaign.next_step(before), None);
+        assert_eq!(campaign.next_step(during), Some(CampaignStep::Apply));
+        assert_eq!(campaign.next_step(after), Some(CampaignStep::Skip));
+        campaign.status = CampaignStatus::Active;
+        assert_eq!(campaign.next_step(during), None);
+        assert_eq!(campaign.next_step(after), Some(CampaignStep::Revert));
+        campaign.status = CampaignStatus::Ended;
+        assert_eq!(campaign.next_step(after), None);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut campaign = campaign();
+        campaign
+            .original_prices
+            .insert("esl".to_string(), "18,90".to_string());
+        let value = serde_json::to_value(&campaign).unwrap();
+        assert_eq!(value["status"], "Scheduled");
+        assert_eq!(value["originalPrices"]["esl"], "18,90");
+        assert_eq!(value["startDate"]["__type"], "Date");
+        let parsed: Campaign = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, campaign);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::generic_esl::GenericEsl;
+use crate::location::Location;
+use crate::parse::ParseError;
+use crate::runtime;
+use futures::future::BoxFuture;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Where the labels of a canary push are sent, such as the client of a vendor.
+pub trait CanaryTarget: Send + Sync {
+    /// Pushes labels to their displays
+    fn push<'a>(&'a self, esls: &'a [GenericEsl]) -> BoxFuture<'a, Result<(), ParseError>>;
+
+    /// Returns the `eslId`s of the labels among `esls` whose display confirmed the last push
+    fn confirmed<'a>(
+        &'a self,
+        esls: &'a [GenericEsl],
+    ) -> BoxFuture<'a, Result<Vec<String>, ParseError>>;
+}
+
+/// How a risky change is tried on a few labels before the whole store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanaryPolicy {
+    /// The labels of each rayon pushed first
+    pub per_rayon: usize,
+    /// The share of the canary labels that must confirm, from 0 to 1
+    pub min_confirmed: f64,
+    /// How long to wait for the confirmations before aborting
+    pub timeout: Duration,
+    /// The delay between two checks of the confirmations
+    pub poll_interval: Duration,
+}
+
+impl Default for CanaryPolicy {
+    /// 5 labels per rayon, all confirmed within 10 minutes, checked every 30 seconds
+    fn default() -> Self {
+        Self {
+            per_rayon: 5,
+            min_confirmed: 1.,
+            timeout: Duration::from_secs(600),
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The result of a [`canary_push`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanaryOutcome {
+    /// The canary labels confirmed, the other ones were pushed
+    Completed { canary: Vec<String>, pushed: usize },
+    /// Not enough canary labels confirmed in time, the other ones were not pushed
+    Aborted {
+        confirmed: Vec<String>,
+        unconfirmed: Vec<String>,
+    },
+}
+
+/// Selects the canary labels: the first `per_rayon` labels of each rayon, as linked by the
+/// locations of the store.
+///
+/// Labels without a location form a rayon of their own.
+pub fn select_canary(esls: &[GenericEsl], locations: &[Location], per_rayon: usize) -> Vec<usize> {
+    let rayons: HashMap<&str, &str> = locations
+        .iter()
+        .flat_map(|location| {
+            location
+                .esls
+                .iter()
+                .map(|esl_id| (esl_id.as_str(), location.rayon.as_str()))
+        })
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    esls.iter()
+        .enumerate()
+        .filter_map(|(index, esl)| {
+            let rayon = rayons.get(esl.id.as_str()).copied().unwrap_or_default();
+            let count = counts.entry(rayon).or_default();
+            *count += 1;
+            (*count <= per_rayon).then_some(index)
+        })
+        .collect()
+}
+
+/// Pushes a change to a canary subset of the labels of a store, waits for their displays to
+/// confirm it, then pushes it to the other labels or aborts.
+///
+/// Use it for a new template or a store-wide price change: a change breaking the displays only
+/// reaches [`CanaryPolicy::per_rayon`] labels of each rayon. The labels are the new versions to
+/// push and `locations` the locations of the store, see [`select_canary`].
+pub async fn canary_push(
+    target: &dyn CanaryTarget,
+    esls: Vec<GenericEsl>,
+    locations: &[Location],
+    policy: &CanaryPolicy,
+) -> Result<CanaryOutcome, ParseError> {
+    let selected: HashSet<usize> = select_canary(&esls, locations, policy.per_rayon)
+        .into_iter()
+        .collect();
+    let (canary, rest): (Vec<_>, Vec<_>) = esls
+        .into_iter()
+        .enumerate()
+        .partition(|(index, _)| selected.contains(index));
+    let canary: Vec<GenericEsl> = canary.into_iter().map(|(_, esl)| esl).collect();
+    let rest: Vec<GenericEsl> = rest.into_iter().map(|(_, esl)| esl).collect();
+    let canary_ids: Vec<String> = canary.iter().map(|esl| esl.id.clone()).collect();
+    info!("Pushing {} canary labels", canary.len());
+    target.push(&canary).await?;
+
+    let required = (policy.min_confirmed * canary.len() as f64).ceil() as usize;
+    let started = Instant::now();
+    loop {
+        let confirmed: HashSet<String> = target.confirmed(&canary).await?.into_iter().collect();
+        let confirmed_count = canary_ids
+            .iter()
+            .filter(|id| confirmed.contains(*id))
+            .count();
+        if confirmed_count >= required {
+            info!(
+                "{confirmed_count} canary labels confirmed, pushing {} labels",
+                rest.len()
+            );
+            target.push(&rest).await?;
+            return Ok(CanaryOutcome::Completed {
+                canary: canary_ids,
+                pushed: rest.len(),
+            });
+        }
+        if started.elapsed() >= policy.timeout {
+            let (confirmed, unconfirmed) = canary_ids
+                .into_iter()
+                .partition(|id| confirmed.contains(id));
+            warn!("Canary push aborted, {confirmed_count} of {required} confirmations");
+            return Ok(CanaryOutcome::Aborted {
+                confirmed,
+                unconfirmed,
+            });
+        }
+        runtime::get()?.sleep(policy.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use std::sync::Mutex;
+
+    /// Confirms the labels pushed, except the broken ones
+    #[derive(Default)]
+    struct Displays {
+        pushed: Mutex<Vec<String>>,
+        broken: Vec<String>,
+    }
+
+    impl CanaryTarget for Displays {
+        fn push<'a>(&'a self, esls: &'a [GenericEsl]) -> BoxFuture<'a, Result<(), ParseError>> {
+            let ids = esls.iter().map(|esl| esl.id.clone());
+            self.pushed.lock().unwrap().extend(ids);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn confirmed<'a>(
+            &'a self,
+            esls: &'a [GenericEsl],
+        ) -> BoxFuture<'a, Result<Vec<String>, ParseError>> {
+            let confirmed = esls
+                .iter()
+                .map(|esl| esl.id.clone())
+                .filter(|id| !self.broken.contains(id))
+                .collect();
+            Box::pin(async { Ok(confirmed) })
+        }
+    }
+
+    fn store() -> (Vec<GenericEsl>, Vec<Location>) {
+        let esls = (0..5)
+            .map(|i| GenericEsl {
+                id: format!("e{i}"),
+                ..mock::esl()
+            })
+            .collect();
+        let mut maree = Location::new("serial".to_string(), "marée".to_string());
+        maree.esls = vec!["e0".to_string(), "e1".to_string(), "e2".to_string()];
+        let mut traiteur = Location::new("serial".to_string(), "traiteur".to_string());
+        traiteur.esls = vec!["e3".to_string()];
+        (esls, vec![maree, traiteur])
+    }
+
+    #[tokio::test]
+    async fn canary() {
+        mock::runtime();
+        let (esls, locations) = store();
+        assert_eq!(select_canary(&esls, &locations, 2), vec![0, 1, 3, 4]);
+        let policy = CanaryPolicy {
+            per_rayon: 1,
+            timeout: Duration::from_millis(20),
+            poll_interval: Duration::from_millis(5),
+            ..CanaryPolicy::default()
+        };
+
+        let displays = Displays::default();
+        let outcome = canary_push(&displays, esls.clone(), &locations, &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            CanaryOutcome::Completed {
+                canary: vec!["e0".to_string(), "e3".to_string(), "e4".to_string()],
+                pushed: 2
+            }
+        );
+        assert_eq!(displays.pushed.lock().unwrap().len(), 5);
+
+        let displays = Displays {
+            broken: vec!["e3".to_string()],
+            ..Displays::default()
+        };
+        let outcome = canary_push(&displays, esls, &locations, &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            CanaryOutcome::Aborted {
+                confirmed: vec!["e0".to_string(), "e4".to_string()],
+                unconfirmed: vec!["e3".to_string()],
+            }
+        );
+        assert_eq!(displays.pushed.lock().unwrap().len(), 3);
+    }
+}
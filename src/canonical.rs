@@ -0,0 +1,84 @@
+use crate::parse::ParseError;
+use serde::Serialize;
+use serde_json::{Number, Value};
+use sha2::{Digest, Sha256};
+
+/// Returns a number written the same way whatever its type: `1.0` and `1` are both written `1`
+fn canonical_number(number: &Number) -> String {
+    if number.is_f64() {
+        let float = number.as_f64().expect("checked by is_f64");
+        // Integral floats within the exact range of f64 are written as integers, -0.0 as 0
+        if float.fract() == 0.0 && float.abs() < 9_007_199_254_740_992.0 {
+            return format!("{}", float as i64);
+        }
+    }
+    number.to_string()
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(object) => {
+            let mut fields: Vec<(&String, &Value)> = object.iter().collect();
+            fields.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in fields.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(values) => {
+            out.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(value, out);
+            }
+            out.push(']');
+        }
+        Value::Number(number) => out.push_str(&canonical_number(number)),
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+/// Serializes a payload to canonical JSON: keys sorted, no whitespace, normalized numbers.
+///
+/// Two payloads with the same content always give the same string, whatever the order of their
+/// map fields. Use it for every hash of a payload.
+pub fn to_canonical_string<T: Serialize>(payload: &T) -> Result<String, ParseError> {
+    let mut out = String::new();
+    write_canonical(&serde_json::to_value(payload)?, &mut out);
+    Ok(out)
+}
+
+/// Returns the SHA-256 hex digest of the canonical JSON of a payload
+pub fn payload_hash<T: Serialize>(payload: &T) -> Result<String, ParseError> {
+    let canonical = to_canonical_string(payload)?;
+    Ok(format!("{:x}", Sha256::digest(canonical.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn canonical() {
+        assert_eq!(
+            to_canonical_string(&json!({ "b": [1.0, -0.0, 2.5], "a": { "d": null, "c": "é\"" } }))
+                .unwrap(),
+            r#"{"a":{"c":"é\"","d":null},"b":[1,0,2.5]}"#
+        );
+        let a: HashMap<&str, i32> = HashMap::from([("x", 1), ("y", 2), ("z", 3)]);
+        let b: HashMap<&str, f64> = HashMap::from([("z", 3.0), ("x", 1.0), ("y", 2.0)]);
+        assert_eq!(payload_hash(&a).unwrap(), payload_hash(&b).unwrap());
+        assert_eq!(payload_hash(&mock::esl()).unwrap().len(), 64);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::update_check::compare_versions;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// The first parse-server release with the `/aggregate` endpoint
+const AGGREGATE_VERSION: &str = "2.7.0";
+/// The first parse-server release with the GraphQL API
+const GRAPHQL_VERSION: &str = "3.5.0";
+
+/// The response of the `serverInfo` endpoint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub parse_server_version: Option<String>,
+    #[serde(default)]
+    pub features: serde_json::Value,
+}
+
+/// The features the gateway may use on a Parse server.
+///
+/// The fields are switches: a feature is used only when it is set, callers fall back to plain
+/// queries otherwise. They can be turned off by configuration even when the server has them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// The parse-server version, unknown when `serverInfo` is not readable
+    pub version: Option<String>,
+    pub aggregate: bool,
+    pub graphql: bool,
+    /// LiveQuery is not reported by `serverInfo`, it is enabled by configuration
+    pub live_query: bool,
+}
+
+fn is_at_least(version: &str, minimum: &str) -> bool {
+    matches!(
+        compare_versions(version, minimum),
+        Some(Ordering::Greater | Ordering::Equal)
+    )
+}
+
+impl Capabilities {
+    /// The capabilities of an unknown server: no optional feature is used
+    pub fn minimal() -> Self {
+        Self {
+            version: None,
+            aggregate: false,
+            graphql: false,
+            live_query: false,
+        }
+    }
+
+    /// Returns the capabilities of a parse-server release
+    pub fn from_version(version: &str) -> Self {
+        Self {
+            version: Some(version.to_string()),
+            aggregate: is_at_least(version, AGGREGATE_VERSION),
+            graphql: is_at_least(version, GRAPHQL_VERSION),
+            live_query: false,
+        }
+    }
+
+    pub fn from_info(info: &ServerInfo) -> Self {
+        match &info.parse_server_version {
+            Some(version) => Capabilities::from_version(version),
+            None => Capabilities::minimal(),
+        }
+    }
+}
+
+impl ParseClient {
+    /// Reads the `serverInfo` endpoint, most servers only answer with the master key
+    pub async fn server_info(&self) -> Result<ServerInfo, ParseError> {
+        self.get_resource("serverInfo".to_string()).await
+    }
+}
+
+/// Probes the capabilities of a Parse server.
+///
+/// A server refusing `serverInfo`, like Back4App without the master key, is considered
+/// [`Capabilities::minimal`] so the same gateway keeps working with plain queries.
+pub async fn probe(client: &ParseClient) -> Capabilities {
+    match client.server_info().await {
+        Ok(info) => Capabilities::from_info(&info),
+        Err(error) => {
+            warn!("Cannot read the Parse server info, using minimal capabilities: {error}");
+            Capabilities::minimal()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn versions() {
+        let old = Capabilities::from_version("2.6.5");
+        assert!(!old.aggregate && !old.graphql && !old.live_query);
+        let v4 = Capabilities::from_version("4.10.4");
+        assert!(v4.aggregate && v4.graphql);
+        assert!(!Capabilities::from_version("3.4.0").graphql);
+        assert!(Capabilities::from_version("3.5.0").graphql);
+        assert_eq!(
+            Capabilities::from_version("nightly"),
+            Capabilities {
+                version: Some("nightly".to_string()),
+                ..Capabilities::minimal()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn probing() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"parseServerVersion":"5.2.1","features":{"schemas":{}}}"#,
+            ),
+            mock::response(
+                "403 Forbidden",
+                &[],
+                r#"{"code":119,"error":"unauthorized"}"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let capabilities = probe(&client).await;
+        assert_eq!(capabilities.version.as_deref(), Some("5.2.1"));
+        assert!(capabilities.aggregate);
+        assert_eq!(probe(&client).await, Capabilities::minimal());
+        assert!(server.join().unwrap()[0].starts_with("GET /serverInfo"));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The path of the Parse Config on the Parse API
+pub const CONFIG_PATH: &str = "config";
+
+/// The runtime parameters of the ESL workers kept in the Parse Config, so they can be changed
+/// without a redeploy. Other parameters are ignored.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EslConfig {
+    /// The TVA of the labels that have none, such as `5.5`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_tva: Option<String>,
+    /// The version of the label templates workers should render with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ConfigResponse<T> {
+    params: T,
+}
+
+#[derive(Deserialize)]
+struct ConfigUpdated {
+    result: bool,
+}
+
+impl ParseClient {
+    /// Reads the parameters of the Parse Config into a type, such as [`EslConfig`].
+    ///
+    /// The parameters restricted to the master key are left out unless the client sends it.
+    pub async fn get_config<T: for<'de> Deserialize<'de>>(&self) -> Result<T, ParseError> {
+        let config: ConfigResponse<T> = self.get_resource(CONFIG_PATH.to_string()).await?;
+        Ok(config.params)
+    }
+
+    /// Sets parameters of the Parse Config, the others are left unchanged.
+    ///
+    /// Parse only accepts it with the master key, see [`ParseClient::with_master_key`].
+    pub async fn update_config<T: Serialize>(&self, params: T) -> Result<(), ParseError> {
+        let master = self.as_master();
+        let body = json!({ "params": params });
+        let (response, _) = master
+            .send(master.protocol().update(CONFIG_PATH, &body)?)
+            .await?;
+        let updated: ConfigUpdated = protocol::interpret(&response, StatusCode::OK)?;
+        if !updated.result {
+            return Err(ParseError::Platform {
+                code: response.status(),
+                cause: "the config was not updated".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn config() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"params":{"defaultTva":"5.5","templateVersion":"v3","welcome":"Bonjour"}}"#,
+            ),
+            mock::response("200 OK", &[], r#"{"result":true}"#),
+        ]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_master_key("master".to_string());
+        let config: EslConfig = client.get_config().await.unwrap();
+        assert_eq!(config.default_tva.as_deref(), Some("5.5"));
+        assert_eq!(config.template_version.as_deref(), Some("v3"));
+        let update = EslConfig {
+            template_version: Some("v4".to_string()),
+            ..EslConfig::default()
+        };
+        client.update_config(&update).await.unwrap();
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /config"));
+        assert!(requests[1].starts_with("PUT /config"));
+        assert!(requests[1].contains("x-parse-master-key: master"));
+        assert!(requests[1].ends_with(r#"{"params":{"templateVersion":"v4"}}"#));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::parse::ParseError;
+use std::collections::HashMap;
+
+/// A `;` or `,` separated file with a header line, as exported by POS and stock systems.
+///
+/// Fields containing the separator are quoted, such as `1234,"18,90"`. A row with more or fewer
+/// fields than the header is an error: an unquoted decimal comma would shift the columns.
+pub(crate) struct Csv {
+    columns: HashMap<String, usize>,
+    /// The line number and the fields of each non-empty row
+    pub(crate) rows: Vec<(usize, Vec<String>)>,
+}
+
+/// Splits a line on a separator outside double quotes, `""` being a quote in a quoted field
+fn split(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+        .into_iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+impl Csv {
+    pub(crate) fn parse(csv: &str) -> Result<Self, ParseError> {
+        let mut lines = csv
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, header) = lines.next().ok_or(ParseError::Import {
+            line: 1,
+            cause: "the file is empty".to_string(),
+        })?;
+        let separator = if header.contains(';') { ';' } else { ',' };
+        let header = split(header, separator);
+        let columns = header
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.to_lowercase(), index))
+            .collect();
+        let rows = lines
+            .map(|(index, line)| {
+                let row = split(line, separator);
+                if row.len() != header.len() {
+                    return Err(ParseError::Import {
+                        line: index + 1,
+                        cause: format!(
+                            "{} fields for {} columns, quote the fields containing {separator:?}",
+                            row.len(),
+                            header.len()
+                        ),
+                    });
+                }
+                Ok((index + 1, row))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { columns, rows })
+    }
+
+    /// Returns the index of an optional column
+    pub(crate) fn optional(&self, name: &str) -> Option<usize> {
+        self.columns.get(name).copied()
+    }
+
+    /// Returns the index of a mandatory column
+    pub(crate) fn column(&self, name: &str) -> Result<usize, ParseError> {
+        self.optional(name).ok_or(ParseError::Import {
+            line: 1,
+            cause: format!("missing column {name}"),
+        })
+    }
+}
+
+/// Returns a field of a row
+pub(crate) fn field(row: &[String], index: usize) -> &str {
+    row.get(index).map_or("", String::as_str)
+}
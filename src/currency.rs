@@ -0,0 +1,164 @@
+use crate::price::{format_cents, parse_cents};
+use serde::{Deserialize, Serialize};
+
+/// The currencies prices can be displayed in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Eur,
+    Chf,
+}
+
+impl Currency {
+    /// The ISO 4217 code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Eur => "EUR",
+            Currency::Chf => "CHF",
+        }
+    }
+
+    /// The symbol printed after the prices: `€` or `CHF`
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::Eur => "€",
+            Currency::Chf => "CHF",
+        }
+    }
+
+    /// The smallest amount a price is rounded to, in cents: 5 centimes in Switzerland
+    pub fn step(&self) -> i64 {
+        match self {
+            Currency::Eur => 1,
+            Currency::Chf => 5,
+        }
+    }
+
+    /// Rounds an amount in cents to the nearest step of this currency
+    pub fn round(&self, cents: f64) -> i64 {
+        let step = self.step() as f64;
+        ((cents / step).round() * step) as i64
+    }
+
+    /// The decimal separator used on labels
+    fn separator(&self) -> &'static str {
+        match self {
+            Currency::Eur => ",",
+            Currency::Chf => ".",
+        }
+    }
+}
+
+/// Provides the conversion rates between currencies, such as a rate fixed by the store or one
+/// refreshed from a bank feed
+pub trait RateSource {
+    /// Returns how much of `to` one unit of `from` is worth, if known
+    fn rate(&self, from: Currency, to: Currency) -> Option<f64>;
+}
+
+/// A rate configured once, it also converts back with its inverse
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FixedRate {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: f64,
+}
+
+impl RateSource for FixedRate {
+    fn rate(&self, from: Currency, to: Currency) -> Option<f64> {
+        if from == to {
+            Some(1.)
+        } else if (from, to) == (self.from, self.to) {
+            Some(self.rate)
+        } else if (from, to) == (self.to, self.from) && self.rate > 0. {
+            Some(1. / self.rate)
+        } else {
+            None
+        }
+    }
+}
+
+/// A price displayed in two currencies
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DualPrice {
+    pub prix: String,
+    pub currency: Currency,
+    pub second_prix: String,
+    pub second_currency: Currency,
+}
+
+/// Converts a displayed price to a second currency, rounded with the rules of that currency.
+///
+/// Returns `None` when the price cannot be parsed or the rate is unknown.
+pub fn dual(
+    prix: &str,
+    currency: Currency,
+    second_currency: Currency,
+    rates: &impl RateSource,
+) -> Option<DualPrice> {
+    let cents = parse_cents(prix)?;
+    let rate = rates.rate(currency, second_currency)?;
+    let converted = second_currency.round(cents as f64 * rate);
+    Some(DualPrice {
+        prix: prix.to_string(),
+        currency,
+        second_prix: format_cents(converted, second_currency.separator()),
+        second_currency,
+    })
+}
+
+impl DualPrice {
+    /// Returns true if the second price is the converted first price, within one rounding step
+    /// of the second currency.
+    ///
+    /// A price edited in one currency only, or an outdated rate, makes the two prices drift.
+    pub fn is_consistent(&self, rates: &impl RateSource) -> bool {
+        let (Some(cents), Some(second), Some(rate)) = (
+            parse_cents(&self.prix),
+            parse_cents(&self.second_prix),
+            rates.rate(self.currency, self.second_currency),
+        ) else {
+            return false;
+        };
+        let expected = cents as f64 * rate;
+        (second as f64 - expected).abs() <= self.second_currency.step() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: FixedRate = FixedRate {
+        from: Currency::Eur,
+        to: Currency::Chf,
+        rate: 0.9712,
+    };
+
+    #[test]
+    fn rounding() {
+        assert_eq!(Currency::Chf.round(1836.), 1835);
+        assert_eq!(Currency::Chf.round(1838.), 1840);
+        assert_eq!(Currency::Eur.round(1836.4), 1836);
+    }
+
+    #[test]
+    fn convert() {
+        let price = dual("18,90", Currency::Eur, Currency::Chf, &RATE).unwrap();
+        assert_eq!(price.second_prix, "18.35");
+        assert!(price.is_consistent(&RATE));
+        let back = dual("18.35", Currency::Chf, Currency::Eur, &RATE).unwrap();
+        assert_eq!(back.second_prix, "18,89");
+        assert_eq!(
+            dual("18,90", Currency::Chf, Currency::Chf, &RATE)
+                .unwrap()
+                .second_prix,
+            "18.90"
+        );
+
+        let stale = DualPrice {
+            second_prix: "19.90".to_string(),
+            ..price
+        };
+        assert!(!stale.is_consistent(&RATE));
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+/// How a date is read from a Parse object: a Date or, for the objects saved before dates were
+/// stored as such, an ISO 8601 string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Date {
+        #[serde(rename = "__type")]
+        _type: DateType,
+        iso: DateTime<Utc>,
+    },
+    Iso(DateTime<Utc>),
+}
+
+#[derive(Deserialize)]
+enum DateType {
+    Date,
+}
+
+impl From<Stored> for DateTime<Utc> {
+    fn from(stored: Stored) -> Self {
+        match stored {
+            Stored::Date { iso, .. } | Stored::Iso(iso) => iso,
+        }
+    }
+}
+
+/// Returns a date as a Parse Date: `{"__type": "Date", "iso": "2023-05-26T08:00:00.000Z"}`.
+///
+/// Parse compares Date columns chronologically, use it for the values of `$lt` or `$gte`
+/// constraints on them. A date sent as a string is stored and compared as text.
+pub fn date(at: &DateTime<Utc>) -> Value {
+    json!({ "__type": "Date", "iso": at.to_rfc3339_opts(SecondsFormat::Millis, true) })
+}
+
+/// Stores a `DateTime<Utc>` field in a Parse Date column, with `#[serde(with = "crate::date")]`
+pub fn serialize<S: Serializer>(at: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    date(at).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    Stored::deserialize(deserializer).map(DateTime::from)
+}
+
+/// Stores an `Option<DateTime<Utc>>` field in a Parse Date column, with
+/// `#[serde(default, with = "crate::date::option")]`
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        at: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        at.as_ref().map(date).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Ok(Option::<Stored>::deserialize(deserializer)?.map(DateTime::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Seen {
+        #[serde(with = "crate::date")]
+        at: DateTime<Utc>,
+        #[serde(default, with = "crate::date::option")]
+        last: Option<DateTime<Utc>>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let at = Utc.with_ymd_and_hms(2023, 5, 26, 8, 0, 0).unwrap();
+        let seen = Seen { at, last: None };
+        let value = serde_json::to_value(&seen).unwrap();
+        assert_eq!(
+            value,
+            json!({ "at": { "__type": "Date", "iso": "2023-05-26T08:00:00.000Z" }, "last": null })
+        );
+        assert_eq!(serde_json::from_value::<Seen>(value).unwrap(), seen);
+
+        let legacy: Seen = serde_json::from_value(json!({ "at": "2023-05-26T08:00:00Z" })).unwrap();
+        assert_eq!(legacy, seen);
+        assert!(serde_json::from_value::<Seen>(json!({ "at": { "iso": 1 } })).is_err());
+    }
+}
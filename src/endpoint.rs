@@ -0,0 +1,112 @@
+use reqwest::Url;
+
+/// The root url of a Parse server and the urls of its REST resources.
+///
+/// Parse servers are mounted on a path that depends on the deployment: `/parse` for a default
+/// parse-server, `/1` for legacy deployments, or the root of the host on Back4App
+/// (`https://parseapi.back4app.com`). The server url given to the client includes that mount
+/// path, with or without a trailing slash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerEndpoint {
+    root: String,
+}
+
+impl ServerEndpoint {
+    pub fn new(server_url: &str) -> Self {
+        Self {
+            root: server_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Returns the url of a path relative to the mount point, such as `classes/Esl`
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.root, path.trim_start_matches('/'))
+    }
+
+    /// Returns the mount path of the server, empty when it is mounted at the root of the host
+    pub fn mount(&self) -> String {
+        Url::parse(&self.root)
+            .map(|url| url.path().trim_end_matches('/').to_string())
+            .unwrap_or_default()
+    }
+
+    /// Returns the absolute path of a resource, as expected by batch requests
+    pub fn mounted_path(&self, path: &str) -> String {
+        format!("{}/{}", self.mount(), path.trim_start_matches('/'))
+    }
+
+    pub fn class(&self, class_name: &str) -> String {
+        self.url(&format!("classes/{class_name}"))
+    }
+
+    pub fn object(&self, class_name: &str, object_id: &str) -> String {
+        self.url(&format!("classes/{class_name}/{object_id}"))
+    }
+
+    pub fn users(&self) -> String {
+        self.url("users")
+    }
+
+    pub fn user(&self, object_id: &str) -> String {
+        self.url(&format!("users/{object_id}"))
+    }
+
+    /// Returns the url of a Cloud Code function
+    pub fn function(&self, name: &str) -> String {
+        self.url(&format!("functions/{name}"))
+    }
+
+    pub fn file(&self, name: &str) -> String {
+        self.url(&format!("files/{name}"))
+    }
+
+    /// Returns the url of a LiveQuery server running along the Parse server: the server url
+    /// with a WebSocket scheme
+    pub fn live_query(&self) -> String {
+        if let Some(rest) = self.root.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = self.root.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            self.root.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let endpoint = ServerEndpoint::new("https://example.com/parse/");
+        assert_eq!(
+            endpoint.class("Esl"),
+            "https://example.com/parse/classes/Esl"
+        );
+        assert_eq!(
+            endpoint.object("Esl", "abc"),
+            "https://example.com/parse/classes/Esl/abc"
+        );
+        assert_eq!(endpoint.url("/users"), "https://example.com/parse/users");
+        assert_eq!(
+            endpoint.function("hello"),
+            "https://example.com/parse/functions/hello"
+        );
+        assert_eq!(endpoint.mounted_path("batch"), "/parse/batch");
+
+        let legacy = ServerEndpoint::new("https://example.com/1");
+        assert_eq!(
+            legacy.file("logo.png"),
+            "https://example.com/1/files/logo.png"
+        );
+        assert_eq!(legacy.mount(), "/1");
+
+        let back4app = ServerEndpoint::new("https://parseapi.back4app.com");
+        assert_eq!(back4app.users(), "https://parseapi.back4app.com/users");
+        assert_eq!(back4app.mount(), "");
+        assert_eq!(back4app.mounted_path("classes/Esl/abc"), "/classes/Esl/abc");
+        assert_eq!(back4app.live_query(), "wss://parseapi.back4app.com");
+        assert_eq!(legacy.live_query(), "wss://example.com/1");
+    }
+}
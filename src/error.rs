@@ -0,0 +1,20 @@
+use crate::parse::ParseError;
+use crate::vendor::VendorError;
+use custom_error::custom_error;
+
+custom_error! {
+    /// Any error of this crate.
+    ///
+    /// Subsystem errors convert into it via a `From` implementation, so pipelines mixing Parse
+    /// and vendor calls can use `?` on both.
+    pub EslError
+        Parse{source: ParseError} = "{source}",
+        Vendor{source: VendorError} = "{source}"
+}
+
+impl EslError {
+    /// Returns true when the failure comes from the ESL vendor rather than from Parse
+    pub fn is_vendor(&self) -> bool {
+        matches!(self, EslError::Vendor { .. })
+    }
+}
@@ -0,0 +1,231 @@
+use crate::fetch::FetchOptions;
+use crate::generic_esl::{EslType, GenericEsl};
+use crate::parse::{ParseClient, ParseCreated, ParseError};
+use serde::Serialize;
+use serde_json::Value;
+
+/// The Parse classes holding the labels of each vendor.
+///
+/// The vendor-specific columns of a label live in the class of its vendor while the
+/// [`GenericEsl`] columns are shared, so labels are saved to the class of their `type` and
+/// queries are sent to every class, their results merged into GenericEsls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EslClasses {
+    classes: Vec<(EslType, String)>,
+}
+
+impl Default for EslClasses {
+    /// `classes/HanshowEsl`, `classes/PricerEsl` and `classes/EasyVCOEsl`
+    fn default() -> Self {
+        Self::new()
+            .with(EslType::Hanshow, "classes/HanshowEsl")
+            .with(EslType::Pricer, "classes/PricerEsl")
+            .with(EslType::EasyVCO, "classes/EasyVCOEsl")
+    }
+}
+
+impl EslClasses {
+    /// A mapping without any class
+    pub fn new() -> Self {
+        Self { classes: vec![] }
+    }
+
+    /// Sets the class path of a vendor, such as `classes/PricerEsl`
+    pub fn with(mut self, esl_type: EslType, path: &str) -> Self {
+        self.classes.retain(|(t, _)| *t != esl_type);
+        self.classes.push((esl_type, path.to_string()));
+        self
+    }
+
+    /// Returns the class path of a vendor
+    pub fn class(&self, esl_type: &EslType) -> Result<&str, ParseError> {
+        self.classes
+            .iter()
+            .find(|(t, _)| t == esl_type)
+            .map(|(_, path)| path.as_str())
+            .ok_or_else(|| ParseError::Query {
+                cause: format!("no Parse class for {esl_type:?} labels"),
+            })
+    }
+
+    /// Saves a label to the class of its vendor.
+    ///
+    /// GenericEsl serializes its objectId, the Postgres one, it is left out of the object. Call
+    /// [`GenericEsl::locate`] first to query the label by area.
+    pub async fn save(
+        &self,
+        client: &ParseClient,
+        esl: &GenericEsl,
+    ) -> Result<ParseCreated, ParseError> {
+        let mut object = serde_json::to_value(esl)?;
+        if let Some(fields) = object.as_object_mut() {
+            fields.remove("objectId");
+        }
+        client
+            .save(self.class(&esl.r#type)?.to_string(), object)
+            .await
+    }
+
+    /// Fetches a label by its objectId from the class of its vendor.
+    ///
+    /// Unlike a query on `eslId` it reads a single object, even when an `eslId` was reused. The
+    /// `type` of the label is the one of the class when the column is not set.
+    pub async fn get(
+        &self,
+        client: &ParseClient,
+        esl_type: &EslType,
+        object_id: &str,
+    ) -> Result<GenericEsl, ParseError> {
+        let mut object: Value = client
+            .get_object(self.class(esl_type)?.to_string(), object_id)
+            .await?;
+        if let Some(fields) = object.as_object_mut() {
+            if !fields.contains_key("type") {
+                fields.insert("type".to_string(), serde_json::to_value(esl_type)?);
+            }
+        }
+        Ok(serde_json::from_value(object)?)
+    }
+
+    /// Fetches the labels matching a query from every class, all the pages of each.
+    ///
+    /// Results are merged class by class, in the order the classes were added: `options.order`
+    /// only orders the labels of each class. The `type` of a label is the one of its class when
+    /// the column is not set.
+    pub async fn fetch<U: Serialize>(
+        &self,
+        client: &ParseClient,
+        query: U,
+        options: FetchOptions,
+    ) -> Result<Vec<GenericEsl>, ParseError> {
+        let query = serde_json::to_value(query)?;
+        let fetches = self.classes.iter().map(|(esl_type, path)| {
+            let (query, options) = (&query, options.clone());
+            async move {
+                let objects: Vec<Value> = client.fetch_all(path.clone(), query, options).await?;
+                objects
+                    .into_iter()
+                    .map(|mut object| {
+                        if let Some(fields) = object.as_object_mut() {
+                            if !fields.contains_key("type") {
+                                fields.insert("type".to_string(), serde_json::to_value(esl_type)?);
+                            }
+                        }
+                        Ok(serde_json::from_value(object)?)
+                    })
+                    .collect::<Result<Vec<GenericEsl>, ParseError>>()
+            }
+        });
+        let mut esls = vec![];
+        for result in futures::future::join_all(fetches).await {
+            esls.extend(result?);
+        }
+        Ok(esls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::store::Store;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn fan_out() {
+        let mut object = serde_json::to_value(mock::esl()).unwrap();
+        object.as_object_mut().unwrap().remove("type");
+        object["hanshowPattern"] = json!("v2");
+        let body = json!({ "results": [object] }).to_string();
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], &body),
+            mock::response("200 OK", &[], &body),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let classes = EslClasses::new()
+            .with(EslType::Hanshow, "classes/HanshowEsl")
+            .with(EslType::Pricer, "classes/PricerEsl");
+        let esls = classes
+            .fetch(
+                &client,
+                json!({ "serial": "serial" }),
+                FetchOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(esls.len(), 2);
+        assert_eq!(esls[0].r#type, EslType::Hanshow);
+        assert_eq!(esls[1].r#type, EslType::Pricer);
+        let requests = server.join().unwrap();
+        assert!(requests
+            .iter()
+            .any(|r| r.starts_with("GET /classes/HanshowEsl")));
+        assert!(requests
+            .iter()
+            .any(|r| r.starts_with("GET /classes/PricerEsl")));
+
+        assert_eq!(
+            EslClasses::default().class(&EslType::EasyVCO).unwrap(),
+            "classes/EasyVCOEsl"
+        );
+        assert!(classes.class(&EslType::EasyVCO).is_err());
+    }
+
+    #[tokio::test]
+    async fn save_located() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "201 Created",
+            &[],
+            r#"{"objectId":"e1","createdAt":"2023-06-01T08:00:00.000Z"}"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut store = Store::new("serial".to_string(), "Tours".to_string());
+        (store.latitude, store.longitude) = (Some(47.39), Some(0.69));
+        let mut esl = GenericEsl {
+            object_id: Some("postgres".to_string()),
+            ..mock::esl()
+        };
+        esl.locate(&store);
+        let created = EslClasses::default().save(&client, &esl).await.unwrap();
+        assert_eq!(created.object_id, "e1");
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("POST /classes/PricerEsl"));
+        assert!(request
+            .contains(r#""geoPoint":{"__type":"GeoPoint","latitude":47.39,"longitude":0.69}"#));
+        assert!(!request.contains("postgres"));
+    }
+
+    #[tokio::test]
+    async fn get() {
+        let mut object = serde_json::to_value(mock::esl()).unwrap();
+        object.as_object_mut().unwrap().remove("type");
+        object["objectId"] = json!("Ed1nuqPvcm");
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], &object.to_string()),
+            mock::response(
+                "404 Not Found",
+                &[],
+                r#"{"code":101,"error":"Object not found."}"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let classes = EslClasses::default();
+        let esl = classes
+            .get(&client, &EslType::Pricer, "Ed1nuqPvcm")
+            .await
+            .unwrap();
+        assert_eq!(esl.r#type, EslType::Pricer);
+        assert_eq!(esl.object_id.as_deref(), Some("Ed1nuqPvcm"));
+        let missing = classes.get(&client, &EslType::Pricer, "unknown").await;
+        assert!(matches!(missing, Err(ParseError::Platform { code, .. }) if code == 404));
+        assert!(matches!(
+            client
+                .get_object::<Value>("classes/PricerEsl".to_string(), "../users")
+                .await,
+            Err(ParseError::Query { .. })
+        ));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /classes/PricerEsl/Ed1nuqPvcm "));
+        assert!(requests[1].starts_with("GET /classes/PricerEsl/unknown "));
+    }
+}
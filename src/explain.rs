@@ -0,0 +1,387 @@
+use crate::fetch::FetchOptions;
+use crate::latency::{Operation, SlowQuery};
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The most distinct queries kept in the sample of an [`IndexAdvisor`]
+pub const ADVISOR_SAMPLE_SIZE: usize = 100;
+
+/// The MongoDB query plan of a Parse query, as returned with `explain=true`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryPlan {
+    pub query_planner: QueryPlanner,
+    /// Only given when the server ran the query to explain it
+    pub execution_stats: Option<ExecutionStats>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryPlanner {
+    /// The collection, such as `parse.GenericEsl`
+    pub namespace: Option<String>,
+    pub winning_plan: PlanStage,
+    #[serde(default)]
+    pub rejected_plans: Vec<PlanStage>,
+}
+
+/// A stage of a plan, such as `IXSCAN` on an index or `COLLSCAN` reading the whole collection
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanStage {
+    /// Missing when MongoDB 5+ nests the plan in `queryPlan`
+    pub stage: Option<String>,
+    pub index_name: Option<String>,
+    pub query_plan: Option<Box<PlanStage>>,
+    pub input_stage: Option<Box<PlanStage>>,
+    #[serde(default)]
+    pub input_stages: Vec<PlanStage>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionStats {
+    pub n_returned: u64,
+    pub execution_time_millis: u64,
+    pub total_keys_examined: u64,
+    pub total_docs_examined: u64,
+}
+
+impl PlanStage {
+    /// Returns this stage and all its input stages
+    pub fn stages(&self) -> Vec<&PlanStage> {
+        let mut stages = vec![self];
+        let children = self.query_plan.iter().chain(self.input_stage.iter());
+        for child in children.map(Box::as_ref).chain(self.input_stages.iter()) {
+            stages.extend(child.stages());
+        }
+        stages
+    }
+}
+
+impl QueryPlan {
+    /// Returns true if the winning plan reads the whole collection, the query needs an index
+    pub fn is_collection_scan(&self) -> bool {
+        self.query_planner
+            .winning_plan
+            .stages()
+            .iter()
+            .any(|stage| stage.stage.as_deref() == Some("COLLSCAN"))
+    }
+
+    /// Returns the indexes used by the winning plan
+    pub fn indexes(&self) -> Vec<&str> {
+        self.query_planner
+            .winning_plan
+            .stages()
+            .iter()
+            .filter_map(|stage| stage.index_name.as_deref())
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct ExplainResponse {
+    results: Value,
+}
+
+impl ParseClient {
+    /// Returns how the database runs a query instead of its results, to find out why it is
+    /// slow.
+    ///
+    /// Parse only explains queries sent with the master key, see
+    /// [`ParseClient::with_master_key`]. The plan is the MongoDB one, Postgres servers are not
+    /// supported.
+    pub async fn fetch_explain<U: Serialize>(
+        &self,
+        path: String,
+        query: U,
+        options: &FetchOptions,
+    ) -> Result<QueryPlan, ParseError> {
+        let mut params = options.params();
+        params.push(("explain", "true".to_string()));
+        let master = self.as_master();
+        let (response, _) = master
+            .send(master.protocol().fetch(&path, &query, &params)?)
+            .await?;
+        let explained: ExplainResponse = protocol::interpret(&response, StatusCode::OK)?;
+        // Older servers return the plan in an array
+        let plan = match explained.results {
+            Value::Array(mut plans) if !plans.is_empty() => plans.swap_remove(0),
+            plan => plan,
+        };
+        Ok(serde_json::from_value(plan)?)
+    }
+}
+
+/// An index missing for a sampled query, see [`IndexAdvisor`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexRecommendation {
+    /// The class to index, such as `GenericEsl`
+    pub class_name: String,
+    /// The name of the index in the MongoDB convention, such as `serial_1_printed_1`
+    pub name: String,
+    /// The indexed fields, the equality constraints before the range ones
+    pub fields: Vec<String>,
+    /// The where clause reading the whole collection without the index
+    pub query: String,
+}
+
+/// Recommends the indexes missing for the slow fetches of a client.
+///
+/// The sample is made of the slow queries of a [`LatencyBudget`], passed to
+/// [`IndexAdvisor::record`]:
+///
+/// ```no_run
+/// # use esl_utils::explain::IndexAdvisor;
+/// # use esl_utils::latency::LatencyBudget;
+/// # use std::time::Duration;
+/// let advisor = IndexAdvisor::new();
+/// let sample = advisor.clone();
+/// let budget = LatencyBudget::new(Duration::from_millis(500)).on_slow(move |slow| sample.record(slow));
+/// ```
+///
+/// [`IndexAdvisor::recommend`] explains the sampled queries and reads the existing indexes
+/// through the schema API, both need the master key. Clones share the same sample.
+///
+/// [`LatencyBudget`]: crate::latency::LatencyBudget
+#[derive(Clone, Default)]
+pub struct IndexAdvisor {
+    /// The path and the where clause of the sampled fetches
+    sample: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+#[derive(Deserialize)]
+struct Schema {
+    #[serde(default)]
+    indexes: HashMap<String, Map<String, Value>>,
+}
+
+impl IndexAdvisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a slow fetch to the sample, the other operations are ignored.
+    ///
+    /// Beyond [`ADVISOR_SAMPLE_SIZE`] distinct queries, the next ones are dropped until
+    /// [`IndexAdvisor::recommend`] empties the sample.
+    pub fn record(&self, slow: &SlowQuery) {
+        let query = match (&slow.query, slow.path.strip_prefix("classes/")) {
+            (Some(query), Some(_)) if slow.operation == Operation::Fetch => query,
+            _ => return,
+        };
+        let mut sample = self.sample.lock().unwrap();
+        let sampled = (slow.path.clone(), query.clone());
+        if sample.len() < ADVISOR_SAMPLE_SIZE && !sample.contains(&sampled) {
+            sample.push(sampled);
+        }
+    }
+
+    /// Returns the indexes missing for the queries sampled since the previous call, then
+    /// empties the sample.
+    ///
+    /// A query needs an index when the database reads the whole collection to run it. No
+    /// index is recommended when the class already has one on the same fields.
+    pub async fn recommend(
+        &self,
+        client: &ParseClient,
+    ) -> Result<Vec<IndexRecommendation>, ParseError> {
+        let sample = std::mem::take(&mut *self.sample.lock().unwrap());
+        let mut schemas: HashMap<String, Schema> = HashMap::new();
+        let mut recommendations: Vec<IndexRecommendation> = vec![];
+        for (path, query) in sample {
+            let class_name = path.trim_start_matches("classes/").to_string();
+            let clause: Value = serde_json::from_str(&query)?;
+            let fields = index_fields(&clause);
+            if fields.is_empty() {
+                continue;
+            }
+            let plan = client
+                .fetch_explain(path, &clause, &FetchOptions::default())
+                .await?;
+            if !plan.is_collection_scan() {
+                continue;
+            }
+            if !schemas.contains_key(&class_name) {
+                let schema = client
+                    .as_master()
+                    .get_resource(format!("schemas/{class_name}"))
+                    .await?;
+                schemas.insert(class_name.clone(), schema);
+            }
+            let indexed = schemas[&class_name].indexes.values().any(|index| {
+                index.len() == fields.len() && fields.iter().all(|f| index.contains_key(f))
+            });
+            let recommended = recommendations
+                .iter()
+                .any(|r| r.class_name == class_name && r.fields == fields);
+            if indexed || recommended {
+                continue;
+            }
+            recommendations.push(IndexRecommendation {
+                name: fields
+                    .iter()
+                    .map(|f| format!("{f}_1"))
+                    .collect::<Vec<_>>()
+                    .join("_"),
+                class_name,
+                fields,
+                query,
+            });
+        }
+        Ok(recommendations)
+    }
+
+    /// Creates a recommended index through the schema API, with the master key
+    pub async fn create(
+        client: &ParseClient,
+        recommendation: &IndexRecommendation,
+    ) -> Result<(), ParseError> {
+        let keys: Map<String, Value> = recommendation
+            .fields
+            .iter()
+            .map(|field| (field.clone(), json!(1)))
+            .collect();
+        let path = format!("schemas/{}", recommendation.class_name);
+        let body = json!({
+            "className": recommendation.class_name,
+            "indexes": { recommendation.name.as_str(): keys },
+        });
+        let master = client.as_master();
+        let (response, _) = master.send(master.protocol().update(&path, &body)?).await?;
+        protocol::interpret_empty(&response, StatusCode::OK)
+    }
+}
+
+/// Returns the fields to index for a where clause: the equality constraints, then the range
+/// ones. `$or`, `$and` and the other top-level operators are not indexed.
+fn index_fields(clause: &Value) -> Vec<String> {
+    let Some(clause) = clause.as_object() else {
+        return vec![];
+    };
+    let is_range = |constraint: &Value| {
+        constraint
+            .as_object()
+            .is_some_and(|c| !c.contains_key("__type") && !c.contains_key("$eq"))
+    };
+    let fields = clause.iter().filter(|(field, _)| !field.starts_with('$'));
+    let (equality, range): (Vec<_>, Vec<_>) = fields.partition(|(_, c)| !is_range(c));
+    equality
+        .into_iter()
+        .chain(range)
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::LatencyBudget;
+    use crate::mock;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn explain() {
+        let plan = json!({
+            "queryPlanner": {
+                "namespace": "parse.GenericEsl",
+                "winningPlan": {
+                    "stage": "FETCH",
+                    "inputStage": { "stage": "IXSCAN", "indexName": "serial_1" }
+                },
+                "rejectedPlans": [{ "stage": "COLLSCAN" }]
+            },
+            "executionStats": {
+                "nReturned": 12,
+                "executionTimeMillis": 3,
+                "totalKeysExamined": 12,
+                "totalDocsExamined": 12
+            }
+        });
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], &json!({ "results": plan }).to_string()),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"queryPlanner":{"winningPlan":{"queryPlan":{"stage":"COLLSCAN"}}}}]}"#,
+            ),
+        ]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_master_key("master".to_string());
+        let options = FetchOptions::default().limit(10);
+        let plan = client
+            .fetch_explain(
+                "classes/GenericEsl".to_string(),
+                json!({ "serial": "s1" }),
+                &options,
+            )
+            .await
+            .unwrap();
+        assert!(!plan.is_collection_scan());
+        assert_eq!(plan.indexes(), vec!["serial_1"]);
+        assert_eq!(plan.execution_stats.unwrap().n_returned, 12);
+        let plan = client
+            .fetch_explain("classes/GenericEsl".to_string(), json!({}), &options)
+            .await
+            .unwrap();
+        assert!(plan.is_collection_scan());
+        let request = &server.join().unwrap()[0];
+        assert!(request.contains("&limit=10&explain=true"));
+        assert!(request.contains("x-parse-master-key: master"));
+    }
+
+    #[test]
+    fn fields() {
+        let clause = json!({ "updatedAt": { "$gte": 1 }, "serial": "s1", "$or": [] });
+        assert_eq!(index_fields(&clause), vec!["serial", "updatedAt"]);
+    }
+
+    #[tokio::test]
+    async fn advisor() {
+        let scan = r#"{"results":{"queryPlanner":{"winningPlan":{"stage":"COLLSCAN"}}}}"#;
+        let schema =
+            r#"{"className":"GenericEsl","indexes":{"_id_":{"_id":1},"serial_1":{"serial":1}}}"#;
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+            mock::response("200 OK", &[], scan),
+            mock::response("200 OK", &[], schema),
+            mock::response("200 OK", &[], scan),
+            mock::response("200 OK", &[], r#"{"className":"GenericEsl"}"#),
+        ]);
+        let advisor = IndexAdvisor::new();
+        let sample = advisor.clone();
+        let budget = LatencyBudget::new(Duration::ZERO).on_slow(move |slow| sample.record(slow));
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_master_key("master".to_string())
+            .with_latency_budget(budget);
+        for query in [
+            json!({ "serial": "s1", "printed": false }),
+            json!({ "serial": "s1" }),
+        ] {
+            let _: Vec<Value> = client
+                .fetch("classes/GenericEsl".to_string(), query)
+                .await
+                .unwrap();
+        }
+        let recommendations = advisor.recommend(&client).await.unwrap();
+        assert_eq!(recommendations.len(), 1);
+        assert_eq!(recommendations[0].name, "printed_1_serial_1");
+        assert_eq!(recommendations[0].fields, vec!["printed", "serial"]);
+        assert!(advisor.recommend(&client).await.unwrap().is_empty());
+        IndexAdvisor::create(&client, &recommendations[0])
+            .await
+            .unwrap();
+        let requests = server.join().unwrap();
+        assert!(requests[3].starts_with("GET /schemas/GenericEsl"));
+        assert!(requests[5].starts_with("PUT /schemas/GenericEsl"));
+        assert!(requests[5].contains(r#""printed_1_serial_1":{"printed":1,"serial":1}"#));
+        assert!(requests[5].contains("x-parse-master-key: master"));
+    }
+}
@@ -0,0 +1,462 @@
+use crate::parse::{parse_field_name, ParseClient, ParseError};
+use crate::protocol;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// The default page size of [`ParseClient::fetch_stream`], it is the default limit of Parse
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// The sort order on a column.
+///
+/// The column is given as a Rust field name and converted with [`parse_field_name`], so
+/// `Order::asc("sous_zone")` sorts on `sousZone`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    Asc(String),
+    Desc(String),
+}
+
+impl Order {
+    pub fn asc(field: &str) -> Self {
+        Order::Asc(parse_field_name(field))
+    }
+
+    pub fn desc(field: &str) -> Self {
+        Order::Desc(parse_field_name(field))
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Order::Asc(column) => write!(f, "{column}"),
+            Order::Desc(column) => write!(f, "-{column}"),
+        }
+    }
+}
+
+/// The query parameters of a fetch besides its where clause
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchOptions {
+    /// The maximum number of results, Parse returns 100 results when it is not set
+    pub limit: Option<usize>,
+    /// The number of results to skip
+    pub skip: Option<usize>,
+    /// The columns to sort by, the first one first
+    pub order: Vec<Order>,
+    /// The only columns to return, every column is returned when empty
+    pub keys: Vec<String>,
+    /// The columns not to return
+    pub exclude_keys: Vec<String>,
+    /// The pointer columns to expand, such as `store` or `product.category`
+    pub include: Vec<String>,
+    /// Whether [`ParseClient::fetch_stream`] pages by objectId instead of skipping results
+    pub cursor: bool,
+}
+
+impl FetchOptions {
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn skip(mut self, skip: usize) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Sorts by another column, after the ones already given
+    pub fn order(mut self, order: Order) -> Self {
+        self.order.push(order);
+        self
+    }
+
+    /// Only returns some columns, given as Rust field names.
+    ///
+    /// `objectId`, `createdAt` and `updatedAt` are always returned. The results must be
+    /// deserialized into a type accepting the missing columns, such as `serde_json::Value`.
+    pub fn keys(mut self, fields: &[&str]) -> Self {
+        self.keys
+            .extend(fields.iter().map(|field| parse_field_name(field)));
+        self
+    }
+
+    /// Does not return some columns, given as Rust field names
+    pub fn exclude_keys(mut self, fields: &[&str]) -> Self {
+        self.exclude_keys
+            .extend(fields.iter().map(|field| parse_field_name(field)));
+        self
+    }
+
+    /// Expands pointer columns, given as Rust field names, with dots for nested pointers.
+    ///
+    /// An expanded pointer is returned as the whole object with its `__type` and `className`,
+    /// so it deserializes into the struct of its class, `Store` for a `store` pointer.
+    pub fn include(mut self, fields: &[&str]) -> Self {
+        self.include.extend(fields.iter().map(|field| {
+            let path: Vec<String> = field.split('.').map(parse_field_name).collect();
+            path.join(".")
+        }));
+        self
+    }
+
+    /// Makes [`ParseClient::fetch_stream`] request the objects after the last objectId of the
+    /// previous page instead of skipping the objects already read.
+    ///
+    /// Objects created or deleted while paging do not shift the next pages, and large skips,
+    /// which are slow on big classes, are avoided. The objects are sorted by objectId, so no
+    /// other `order` nor any `skip` can be given.
+    pub fn cursor(mut self) -> Self {
+        self.cursor = true;
+        self
+    }
+
+    fn is_ordered_by_object_id(&self) -> bool {
+        self.order
+            .iter()
+            .any(|order| matches!(order, Order::Asc(c) | Order::Desc(c) if c == "objectId"))
+    }
+
+    /// Returns the query parameters of these options
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(skip) = self.skip {
+            params.push(("skip", skip.to_string()));
+        }
+        if !self.order.is_empty() {
+            let order: Vec<String> = self.order.iter().map(Order::to_string).collect();
+            params.push(("order", order.join(",")));
+        }
+        if !self.keys.is_empty() {
+            params.push(("keys", self.keys.join(",")));
+        }
+        if !self.exclude_keys.is_empty() {
+            params.push(("excludeKeys", self.exclude_keys.join(",")));
+        }
+        if !self.include.is_empty() {
+            params.push(("include", self.include.join(",")));
+        }
+        params
+    }
+}
+
+/// The position of the next page of [`ParseClient::fetch_stream`]
+enum Page {
+    Skip(usize),
+    /// After an objectId, from the first object when none
+    After(Option<String>),
+}
+
+/// Restricts a where clause to the objects after an objectId
+fn after(query: &Value, object_id: &str) -> Value {
+    let cursor = json!({ "$gt": object_id });
+    match query {
+        Value::Object(fields) if !fields.contains_key("objectId") => {
+            let mut fields = fields.clone();
+            fields.insert("objectId".to_string(), cursor);
+            Value::Object(fields)
+        }
+        _ => json!({ "$and": [query, { "objectId": cursor }] }),
+    }
+}
+
+/// The response of a count query
+#[derive(Deserialize)]
+struct CountResponse {
+    count: u64,
+}
+
+impl ParseClient {
+    /// Counts the objects matching a query without downloading them
+    pub async fn count<U: Serialize>(&self, path: String, query: U) -> Result<u64, ParseError> {
+        let params = [("count", "1".to_string()), ("limit", "0".to_string())];
+        let (response, _) = self
+            .send(self.protocol().fetch(&path, &query, &params)?)
+            .await?;
+        let count: CountResponse = protocol::interpret(&response, StatusCode::OK)?;
+        Ok(count.count)
+    }
+
+    /// Fetches one page of objects, see [`ParseClient::fetch`]
+    pub async fn fetch_with<T: for<'de> Deserialize<'de>, U: Serialize>(
+        &self,
+        path: String,
+        query: U,
+        options: &FetchOptions,
+    ) -> Result<Vec<T>, ParseError> {
+        self.fetch_params(path, query, &options.params()).await
+    }
+
+    /// Fetches every object matching a query, page by page.
+    ///
+    /// `options.limit` is the size of the pages (100 by default) and `options.skip` the number
+    /// of objects to skip before the first page. Pages are sorted by objectId after the given
+    /// `order`, so objects with equal sort keys are neither skipped nor read twice. Objects
+    /// created or deleted while paging still shift the next pages, unless paging with
+    /// [`FetchOptions::cursor`], which refuses a `skip`.
+    pub fn fetch_stream<'a, T: for<'de> Deserialize<'de> + 'a, U: Serialize>(
+        &'a self,
+        path: String,
+        query: U,
+        mut options: FetchOptions,
+    ) -> impl Stream<Item = Result<T, ParseError>> + 'a {
+        let page_size = options.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+        let mut query = serde_json::to_value(query).map_err(ParseError::from);
+        let start = if options.cursor {
+            if !options.order.is_empty() && options.order != [Order::asc("object_id")] {
+                query = Err(ParseError::Query {
+                    cause: "cursor paging is sorted by objectId, it cannot be ordered".to_string(),
+                });
+            } else if options.skip.is_some() {
+                query = Err(ParseError::Query {
+                    cause: "cursor paging starts after an objectId, it cannot skip".to_string(),
+                });
+            }
+            options.order = vec![Order::asc("object_id")];
+            Page::After(None)
+        } else {
+            if !options.is_ordered_by_object_id() {
+                options.order.push(Order::asc("object_id"));
+            }
+            Page::Skip(options.skip.unwrap_or(0))
+        };
+        stream::try_unfold((Some(start), Some(query)), move |(page, query)| {
+            let (path, options) = (path.clone(), options.clone().limit(page_size));
+            async move {
+                let (Some(page), Some(query)) = (page, query) else {
+                    return Ok::<_, ParseError>(None);
+                };
+                let query = query?;
+                let (results, next) = match page {
+                    Page::Skip(skip) => {
+                        let options = options.skip(skip);
+                        let results: Vec<T> = self.fetch_with(path, &query, &options).await?;
+                        let next =
+                            (results.len() == page_size).then_some(Page::Skip(skip + page_size));
+                        (results, next)
+                    }
+                    Page::After(last) => {
+                        let page_query = match &last {
+                            Some(object_id) => after(&query, object_id),
+                            None => query.clone(),
+                        };
+                        let objects: Vec<Value> =
+                            self.fetch_with(path, &page_query, &options).await?;
+                        let last = objects
+                            .last()
+                            .and_then(|object| object.get("objectId"))
+                            .and_then(Value::as_str)
+                            .map(str::to_string);
+                        let next = match last {
+                            Some(object_id) if objects.len() == page_size => {
+                                Some(Page::After(Some(object_id)))
+                            }
+                            _ => None,
+                        };
+                        let results = objects
+                            .into_iter()
+                            .map(serde_json::from_value)
+                            .collect::<Result<Vec<T>, _>>()?;
+                        (results, next)
+                    }
+                };
+                Ok(Some((results, (next, Some(Ok(query))))))
+            }
+        })
+        .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// Fetches every object matching a query, see [`ParseClient::fetch_stream`]
+    pub async fn fetch_all<T: for<'de> Deserialize<'de>, U: Serialize>(
+        &self,
+        path: String,
+        query: U,
+        options: FetchOptions,
+    ) -> Result<Vec<T>, ParseError> {
+        self.fetch_stream(path, query, options)
+            .collect::<Vec<Result<T, ParseError>>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::parse::ParseCreated;
+    use crate::store::Store;
+    use serde_json::json;
+
+    #[test]
+    fn params() {
+        let options = FetchOptions::default()
+            .limit(10)
+            .skip(20)
+            .order(Order::desc("createdAt"))
+            .order(Order::asc("nom_scientifique"));
+        assert_eq!(
+            options.params(),
+            vec![
+                ("limit", "10".to_string()),
+                ("skip", "20".to_string()),
+                ("order", "-createdAt,nomScientifique".to_string())
+            ]
+        );
+        assert!(FetchOptions::default().params().is_empty());
+        let options = FetchOptions::default()
+            .keys(&["serial", "nom", "prix", "printed"])
+            .exclude_keys(&["infos_prix", "nom_scientifique"]);
+        assert_eq!(
+            options.params(),
+            vec![
+                ("keys", "serial,nom,prix,printed".to_string()),
+                ("excludeKeys", "infosPrix,nomScientifique".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn count() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"{"results":[],"count":42}"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let count = client
+            .count("classes/Esl".to_string(), json!({ "printed": false }))
+            .await
+            .unwrap();
+        assert_eq!(count, 42);
+        assert!(server.join().unwrap()[0].contains("count=1&limit=0"));
+    }
+
+    #[tokio::test]
+    async fn include() {
+        #[derive(Deserialize)]
+        struct EslWithStore {
+            serial: String,
+            store: Store,
+        }
+
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"{"results":[{"objectId":"e1","serial":"s1","store":{"__type":"Object","className":"Store","objectId":"st1","serial":"s1","name":"Rungis"}}]}"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let options = FetchOptions::default().include(&["store", "store.price_zone"]);
+        let esls: Vec<EslWithStore> = client
+            .fetch_with("classes/Esl".to_string(), json!({}), &options)
+            .await
+            .unwrap();
+        assert_eq!(esls[0].serial, "s1");
+        assert_eq!(esls[0].store.object_id.as_deref(), Some("st1"));
+        assert_eq!(esls[0].store.name, "Rungis");
+        assert!(server.join().unwrap()[0].contains("include=store%2Cstore.priceZone"));
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let object = |id: &str| format!(r#"{{"createdAt":"now","objectId":"{id}"}}"#);
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                &format!(r#"{{"results":[{},{}]}}"#, object("a"), object("b")),
+            ),
+            mock::response(
+                "200 OK",
+                &[],
+                &format!(r#"{{"results":[{}]}}"#, object("c")),
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let objects: Vec<ParseCreated> = client
+            .fetch_all(
+                "classes/Esl".to_string(),
+                json!({}),
+                FetchOptions::default()
+                    .limit(2)
+                    .order(Order::asc("objectId")),
+            )
+            .await
+            .unwrap();
+        let ids: Vec<&str> = objects.iter().map(|o| o.object_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("limit=2&skip=0"));
+        assert!(requests[1].contains("limit=2&skip=2"));
+        assert!(requests[1].contains("order=objectId"));
+    }
+
+    #[tokio::test]
+    async fn stream_order() {
+        let (url, server) = mock::serve(vec![mock::response("200 OK", &[], r#"{"results":[]}"#)]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let options = FetchOptions::default().order(Order::desc("created_at"));
+        let objects: Vec<ParseCreated> = client
+            .fetch_all("classes/Esl".to_string(), json!({}), options)
+            .await
+            .unwrap();
+        assert!(objects.is_empty());
+        assert!(server.join().unwrap()[0].contains("order=-createdAt%2CobjectId"));
+    }
+
+    #[tokio::test]
+    async fn stream_cursor() {
+        let object = |id: &str| format!(r#"{{"createdAt":"now","objectId":"{id}"}}"#);
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                &format!(r#"{{"results":[{},{}]}}"#, object("a"), object("b")),
+            ),
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let options = FetchOptions::default().limit(2).cursor();
+        let objects: Vec<ParseCreated> = client
+            .fetch_all(
+                "classes/Esl".to_string(),
+                json!({ "serial": "s1" }),
+                options.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(objects.len(), 2);
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("limit=2&order=objectId"));
+        assert!(!requests[0].contains("skip"));
+        assert!(requests[1].contains("%22objectId%22%3A%7B%22%24gt%22%3A%22b%22%7D"));
+        assert!(requests[1].contains("%22serial%22%3A%22s1%22"));
+
+        assert_eq!(
+            after(&json!({ "objectId": { "$ne": "x" } }), "b"),
+            json!({ "$and": [{ "objectId": { "$ne": "x" } }, { "objectId": { "$gt": "b" } }] })
+        );
+        let ordered = options.clone().order(Order::asc("nom"));
+        assert!(matches!(
+            client
+                .fetch_all::<ParseCreated, _>("classes/Esl".to_string(), json!({}), ordered)
+                .await,
+            Err(ParseError::Query { .. })
+        ));
+        let skipped = options.skip(10);
+        assert!(matches!(
+            client
+                .fetch_all::<ParseCreated, _>("classes/Esl".to_string(), json!({}), skipped)
+                .await,
+            Err(ParseError::Query { .. })
+        ));
+    }
+}
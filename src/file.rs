@@ -0,0 +1,113 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// A file stored by Parse, as referenced by a file column.
+///
+/// It is attached to an object by saving it in one of its columns, for instance
+/// `client.update(path, json!({ "photo": file }))` for the photo of a label.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "__type", rename = "File")]
+pub struct ParseFile {
+    /// The name given by Parse, prefixed with a unique id
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl ParseClient {
+    /// Uploads a file, the returned ParseFile can then be saved in a file column
+    pub async fn upload_file(
+        &self,
+        name: &str,
+        bytes: Vec<u8>,
+        mime: &str,
+    ) -> Result<ParseFile, ParseError> {
+        let (response, _) = self
+            .send(self.protocol().upload(name, bytes, mime)?)
+            .await?;
+        #[derive(Deserialize)]
+        struct Uploaded {
+            name: String,
+            url: String,
+        }
+        let uploaded: Uploaded = protocol::interpret(&response, StatusCode::CREATED)?;
+        Ok(ParseFile {
+            name: uploaded.name,
+            url: Some(uploaded.url),
+        })
+    }
+
+    /// Downloads a file by its url.
+    ///
+    /// Files may be served by another host than the Parse server (S3, a CDN...), so the Parse
+    /// credentials are not sent with this request. It shares the connection pool and the
+    /// timeouts of this client.
+    pub async fn download_file(&self, url: &str) -> Result<Vec<u8>, ParseError> {
+        let response = self.get_client().get(url).send().await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+        if status != StatusCode::OK {
+            return Err(ParseError::Platform {
+                code: status,
+                cause: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        Ok(body.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::json;
+
+    #[test]
+    fn serialize() {
+        let file = ParseFile {
+            name: "abc_photo.jpg".to_string(),
+            url: Some("https://files.example.com/abc_photo.jpg".to_string()),
+        };
+        let value = serde_json::to_value(&file).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "__type": "File",
+                "name": "abc_photo.jpg",
+                "url": "https://files.example.com/abc_photo.jpg"
+            })
+        );
+        assert_eq!(serde_json::from_value::<ParseFile>(value).unwrap(), file);
+    }
+
+    #[tokio::test]
+    async fn upload_download() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "201 Created",
+                &[],
+                r#"{"name":"abc_photo.jpg","url":"http://files/abc_photo.jpg"}"#,
+            ),
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 3\r\n\r\njpg".to_string(),
+        ]);
+        let client = ParseClient::new("app".to_string(), Some("key".to_string()), url.clone());
+        let file = client
+            .upload_file("photo.jpg", b"jpg".to_vec(), "image/jpeg")
+            .await
+            .unwrap();
+        assert_eq!(file.name, "abc_photo.jpg");
+        let bytes = client
+            .download_file(&format!("{url}/files/app/abc_photo.jpg"))
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"jpg");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /files/photo.jpg"));
+        assert!(requests[0].contains("content-type: image/jpeg"));
+        assert!(requests[0].ends_with("\r\n\r\njpg"));
+        assert!(!requests[1].contains("x-parse-rest-api-key"));
+    }
+}
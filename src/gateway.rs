@@ -0,0 +1,392 @@
+use crate::date;
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+use crate::store::{Store, STORE_CLASS};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// The Parse class holding the gateways of the fleet
+pub const GATEWAY_CLASS: &str = "classes/Gateway";
+
+/// A gateway pushing labels for a store.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Gateway {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub gateway_id: String,
+    /// The serial of the store this gateway belongs to
+    pub serial: String,
+    /// The SHA-256 of the gateway secret, the secret itself is never stored
+    pub secret_hash: String,
+    #[serde(
+        default,
+        with = "crate::date::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// The version of the software running on the gateway, as of the last heartbeat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The number of operations waiting to be sent, as of the last heartbeat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<u32>,
+    #[serde(
+        default,
+        with = "crate::date::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_sync: Option<DateTime<Utc>>,
+    /// The number of labels refreshed more than their daily budget, as of the last heartbeat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub over_budget: Option<u32>,
+}
+
+/// The state a gateway reports periodically
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heartbeat {
+    pub version: String,
+    pub queue_depth: u32,
+    /// The last time the gateway successfully synchronized its labels
+    pub last_sync: Option<DateTime<Utc>>,
+    /// The number of labels refreshed more than their daily budget, see
+    /// [`RefreshTracker::over_budget`](crate::refresh::RefreshTracker::over_budget)
+    pub over_budget: u32,
+}
+
+/// A store with at least one silent gateway
+#[derive(Clone, Debug, PartialEq)]
+pub struct SilentStore {
+    pub store: Store,
+    pub gateways: Vec<Gateway>,
+}
+
+/// The credentials issued to a gateway when it is registered.
+///
+/// The secret cannot be recovered afterwards, a new one has to be issued with
+/// [`Gateway::rotate_secret`] if it is lost.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewayCredentials {
+    pub gateway_id: String,
+    /// The only serial these credentials are valid for
+    pub serial: String,
+    pub secret: String,
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+impl Gateway {
+    /// Returns the path of this gateway on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", GATEWAY_CLASS, object_id))
+    }
+
+    /// Creates a gateway for a serial along with its credentials
+    fn provision(gateway_id: String, serial: String) -> (Self, GatewayCredentials) {
+        let secret = Uuid::new_v4().to_simple().to_string();
+        let gateway = Self {
+            object_id: None,
+            gateway_id: gateway_id.clone(),
+            serial: serial.clone(),
+            secret_hash: hash_secret(&secret),
+            last_seen: None,
+            version: None,
+            queue_depth: None,
+            last_sync: None,
+            over_budget: None,
+        };
+        let credentials = GatewayCredentials {
+            gateway_id,
+            serial,
+            secret,
+        };
+        (gateway, credentials)
+    }
+
+    /// Registers a new gateway for a store and issues its credentials.
+    ///
+    /// The gateway is saved in the Gateway class and added to the gateways allowed to push for
+    /// the store. A `gatewayId` already registered is refused: issue new credentials to an
+    /// existing gateway with [`Gateway::rotate_secret`].
+    pub async fn register(
+        store: &mut Store,
+        gateway_id: String,
+    ) -> Result<GatewayCredentials, ParseError> {
+        Gateway::register_with(ParseClient::global(), store, gateway_id).await
+    }
+
+    /// Registers a new gateway with a given client, see [`Gateway::register`]
+    pub async fn register_with(
+        client: &ParseClient,
+        store: &mut Store,
+        gateway_id: String,
+    ) -> Result<GatewayCredentials, ParseError> {
+        if Gateway::find_by_id_with(client, gateway_id.clone())
+            .await?
+            .is_some()
+        {
+            return Err(ParseError::Query {
+                cause: format!("the gateway {gateway_id} is already registered"),
+            });
+        }
+        let (mut gateway, credentials) = Gateway::provision(gateway_id, store.serial.clone());
+        let created = client.save(GATEWAY_CLASS.to_string(), &gateway).await?;
+        gateway.object_id = Some(created.object_id);
+        if !store.gateways.contains(&gateway.gateway_id) {
+            store.gateways.push(gateway.gateway_id.clone());
+            client.update(store.path()?, &*store).await?;
+        }
+        Ok(credentials)
+    }
+
+    /// Finds a gateway by its id
+    pub async fn find_by_id(gateway_id: String) -> Result<Option<Self>, ParseError> {
+        Gateway::find_by_id_with(ParseClient::global(), gateway_id).await
+    }
+
+    async fn find_by_id_with(
+        client: &ParseClient,
+        gateway_id: String,
+    ) -> Result<Option<Self>, ParseError> {
+        let gateways: Vec<Self> = client
+            .fetch(
+                GATEWAY_CLASS.to_string(),
+                json!({ "gatewayId": gateway_id }),
+            )
+            .await?;
+        Ok(gateways.into_iter().next())
+    }
+
+    /// Checks credentials presented by a gateway
+    pub fn authenticate(&self, credentials: &GatewayCredentials) -> bool {
+        credentials.gateway_id == self.gateway_id
+            && credentials.serial == self.serial
+            && hash_secret(&credentials.secret) == self.secret_hash
+    }
+
+    /// Issues a new secret for this gateway, the previous one stops working
+    pub async fn rotate_secret(&mut self) -> Result<GatewayCredentials, ParseError> {
+        let (gateway, credentials) =
+            Gateway::provision(self.gateway_id.clone(), self.serial.clone());
+        self.secret_hash = gateway.secret_hash;
+        self.update().await?;
+        Ok(credentials)
+    }
+
+    /// Records that the gateway has been seen at `now`
+    pub async fn seen(&mut self, now: DateTime<Utc>) -> Result<(), ParseError> {
+        self.last_seen = Some(now);
+        self.update().await?;
+        Ok(())
+    }
+
+    /// Records a heartbeat sent by the gateway at `now`.
+    ///
+    /// Gateways should call this periodically, the ones that stop doing so are reported by
+    /// [`Gateway::silent_stores`].
+    pub async fn heartbeat(
+        &mut self,
+        heartbeat: Heartbeat,
+        now: DateTime<Utc>,
+    ) -> Result<(), ParseError> {
+        self.version = Some(heartbeat.version);
+        self.queue_depth = Some(heartbeat.queue_depth);
+        self.over_budget = Some(heartbeat.over_budget);
+        if heartbeat.last_sync.is_some() {
+            self.last_sync = heartbeat.last_sync;
+        }
+        self.seen(now).await
+    }
+
+    /// Returns true if the gateway has not been seen since `since`
+    pub fn is_silent(&self, since: DateTime<Utc>) -> bool {
+        self.last_seen.is_none_or(|last_seen| last_seen < since)
+    }
+
+    /// Finds the stores having gateways that have not been seen since `since`
+    pub async fn silent_stores(since: DateTime<Utc>) -> Result<Vec<SilentStore>, ParseError> {
+        let client = ParseClient::global();
+        let gateways: Vec<Self> = client
+            .fetch(GATEWAY_CLASS.to_string(), silent_where(since))
+            .await?;
+        let mut serials: Vec<&String> = gateways.iter().map(|gateway| &gateway.serial).collect();
+        serials.sort();
+        serials.dedup();
+        let stores: Vec<Store> = client
+            .fetch(
+                STORE_CLASS.to_string(),
+                json!({ "serial": { "$in": serials } }),
+            )
+            .await?;
+        Ok(group_silent(stores, gateways, since))
+    }
+}
+
+/// The gateways not seen since `since`, `lastSeen` is compared as a Date
+fn silent_where(since: DateTime<Utc>) -> serde_json::Value {
+    json!({ "$or": [
+        { "lastSeen": { "$lt": date::date(&since) } },
+        { "lastSeen": { "$exists": false } },
+    ] })
+}
+
+/// Groups the silent gateways by store
+fn group_silent(
+    stores: Vec<Store>,
+    gateways: Vec<Gateway>,
+    since: DateTime<Utc>,
+) -> Vec<SilentStore> {
+    stores
+        .into_iter()
+        .filter_map(|store| {
+            let silent: Vec<Gateway> = gateways
+                .iter()
+                .filter(|gateway| gateway.serial == store.serial && gateway.is_silent(since))
+                .cloned()
+                .collect();
+            (!silent.is_empty()).then_some(SilentStore {
+                store,
+                gateways: silent,
+            })
+        })
+        .collect()
+}
+
+impl ParseClass for Gateway {
+    const CLASS_NAME: &'static str = "Gateway";
+}
+
+impl ParseObject for Gateway {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(GATEWAY_CLASS.to_string(), self).await
+    }
+
+    /// Finds the gateways of a store
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(GATEWAY_CLASS.to_string(), json!({ "serial": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn provision() {
+        let (gateway, credentials) =
+            Gateway::provision("gateway".to_string(), "serial".to_string());
+        assert_eq!(credentials.gateway_id, "gateway");
+        assert_eq!(credentials.serial, "serial");
+        assert_ne!(gateway.secret_hash, credentials.secret);
+        assert!(gateway.authenticate(&credentials));
+
+        let (_, other) = Gateway::provision("gateway".to_string(), "serial".to_string());
+        assert_ne!(credentials.secret, other.secret);
+        assert!(!gateway.authenticate(&other));
+
+        let other_serial = GatewayCredentials {
+            serial: "other".to_string(),
+            ..credentials
+        };
+        assert!(!gateway.authenticate(&other_serial));
+    }
+
+    #[test]
+    fn silent() {
+        use chrono::TimeZone;
+
+        let since = Utc.with_ymd_and_hms(2023, 5, 26, 8, 0, 0).unwrap();
+        let (mut alive, _) = Gateway::provision("alive".to_string(), "serial".to_string());
+        alive.last_seen = Some(Utc.with_ymd_and_hms(2023, 5, 26, 8, 5, 0).unwrap());
+        let (mut silent, _) = Gateway::provision("silent".to_string(), "serial".to_string());
+        silent.last_seen = Some(Utc.with_ymd_and_hms(2023, 5, 25, 8, 0, 0).unwrap());
+        let (never_seen, _) = Gateway::provision("never".to_string(), "other".to_string());
+        assert!(!alive.is_silent(since));
+        assert!(silent.is_silent(since));
+        assert!(never_seen.is_silent(since));
+
+        let stores = vec![
+            Store::new("serial".to_string(), "Lorient".to_string()),
+            Store::new("other".to_string(), "Quimper".to_string()),
+            Store::new("quiet".to_string(), "Brest".to_string()),
+        ];
+        let report = group_silent(
+            stores,
+            vec![alive, silent.clone(), never_seen.clone()],
+            since,
+        );
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].store.serial, "serial");
+        assert_eq!(report[0].gateways, vec![silent]);
+        assert_eq!(report[1].gateways, vec![never_seen]);
+        assert_eq!(
+            silent_where(since)["$or"][0]["lastSeen"]["$lt"],
+            json!({ "__type": "Date", "iso": "2023-05-26T08:00:00.000Z" })
+        );
+        let value = serde_json::to_value(&report[0].gateways[0]).unwrap();
+        assert_eq!(value["lastSeen"]["__type"], "Date");
+        assert_eq!(
+            serde_json::from_value::<Gateway>(value).unwrap(),
+            report[0].gateways[0]
+        );
+    }
+
+    #[test]
+    fn secret_is_not_serialized() {
+        let (gateway, credentials) =
+            Gateway::provision("gateway".to_string(), "serial".to_string());
+        let value = serde_json::to_string(&gateway).unwrap();
+        assert!(!value.contains(&credentials.secret));
+        assert!(value.contains("secretHash"));
+    }
+
+    #[tokio::test]
+    async fn register_once() {
+        let (registered, _) = Gateway::provision("g1".to_string(), "serial".to_string());
+        let found = json!({ "results": [registered] }).to_string();
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], &found),
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+            mock::response("201 Created", &[], r#"{"createdAt":"now","objectId":"g2"}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut store = Store::new("serial".to_string(), "Store".to_string());
+        store.object_id = Some("st".to_string());
+        assert!(matches!(
+            Gateway::register_with(&client, &mut store, "g1".to_string()).await,
+            Err(ParseError::Query { .. })
+        ));
+        let credentials = Gateway::register_with(&client, &mut store, "g2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(credentials.gateway_id, "g2");
+        assert_eq!(store.gateways, vec!["g2".to_string()]);
+        let requests = server.join().unwrap();
+        assert!(requests[2].starts_with("POST /classes/Gateway"));
+        assert!(requests[3].starts_with("PUT /classes/Store/st"));
+    }
+}
@@ -1,26 +1,38 @@
+use crate::geo::GeoPoint;
+#[cfg(feature = "postgres")]
+use crate::location::Location;
+#[cfg(feature = "postgres")]
 use crate::parse::ParseError;
+use crate::query::Query;
+use crate::store::Store;
+#[cfg(feature = "postgres")]
 use bb8::Pool;
+#[cfg(feature = "postgres")]
 use bb8_postgres::PostgresConnectionManager;
+use chrono::NaiveDate;
+#[cfg(feature = "postgres")]
 use postgres_types::{FromSql, ToSql};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
 use tokio_postgres::{NoTls, Row};
+#[cfg(feature = "postgres")]
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "postgres", derive(ToSql, FromSql))]
 pub enum EslType {
     Hanshow,
     Pricer,
-    EasyVCO
+    EasyVCO,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct GenericEsl {
     pub r#type: EslType,
     pub serial: String,
     pub printed: bool,
-    #[serde(rename = "objectId")]
     pub object_id: Option<String>,
-    #[serde(rename = "itemId")]
     /// The item id.
     ///
     /// It only exists for Pricer Esls
@@ -32,22 +44,17 @@ pub struct GenericEsl {
     #[serde(rename = "eslId")]
     pub id: String,
     pub nom: String,
-    #[serde(rename = "nomScientifique")]
     pub nom_scientifique: String,
     pub prix: String,
-    #[serde(rename = "infosPrix")]
     pub infos_prix: String,
     pub engin: Option<String>,
     pub zone: Option<String>,
-    #[serde(rename = "zoneCode")]
     pub zone_code: Option<String>,
-    #[serde(rename = "sousZone")]
     pub sous_zone: Option<String>,
-    #[serde(rename = "sousZoneCode")]
     pub sous_zone_code: Option<String>,
     pub plu: String,
     pub taille: Option<String>,
-    #[serde(rename = "congelInfos", skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub congel_infos: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub origine: Option<String>,
@@ -61,40 +68,93 @@ pub struct GenericEsl {
     pub tva: Option<String>,
     pub categorie: Option<i32>,
     pub achats: Option<f32>,
+    /// The quantity in stock, synced from the POS/stock system
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stock: Option<i32>,
+    /// The date of the next delivery of this product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arrivage: Option<NaiveDate>,
+    /// The coordinates of the store, to query the labels by area, see [`GenericEsl::locate`].
+    ///
+    /// It is a Parse column only, the Postgres table has none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_point: Option<GeoPoint>,
 }
 
-impl From<&Row> for GenericEsl {
-    fn from(row: &Row) -> Self {
-        Self {
-            r#type: row.get("type"),
-            serial: row.get("serial"),
-            printed: row.get("printed"),
-            object_id: row.get("objectId"),
-            item_id: row.get("itemId"),
-            id: row.get("eslId"),
-            nom: row.get("nom"),
-            nom_scientifique: row.get("nomScientifique"),
-            prix: row.get("prix"),
-            infos_prix: row.get("infosPrix"),
-            engin: row.get("engin"),
-            zone: row.get("zone"),
-            zone_code: row.get("zoneCode"),
-            sous_zone: row.get("sousZone"),
-            sous_zone_code: row.get("sousZoneCode"),
-            plu: row.get("plu"),
-            taille: row.get("taille"),
-            congel_infos: row.get("congelInfos"),
-            origine: row.get("origine"),
-            allergenes: row.get("allergenes"),
-            label: row.get("label"),
-            production: row.get("production"),
-            achats: row.get("achats"),
-            categorie: row.get("categorie"),
-            tva: row.get("tva"),
-        }
+impl GenericEsl {
+    /// Attaches the coordinates of its store to the label, before saving it to Parse
+    pub fn locate(&mut self, store: &Store) {
+        self.geo_point = store.location();
+    }
+}
+
+/// The Parse queries of the labels, to fetch them from the Parse classes with
+/// [`EslClasses::fetch`](crate::esl_classes::EslClasses::fetch)
+impl GenericEsl {
+    /// The labels of a store not printed yet, as `do_find` reads them from Postgres
+    pub fn unprinted(serial: &str) -> Query {
+        Query::new().eq("serial", serial).eq("printed", false)
+    }
+
+    /// The labels of a store with the given `eslId`s
+    pub fn with_ids(serial: &str, ids: &[String]) -> Query {
+        Query::new()
+            .eq("serial", serial)
+            .in_("esl_id", ids.iter().map(String::as_str))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl TryFrom<&Row> for GenericEsl {
+    type Error = ParseError;
+
+    /// Reads an Esl from a row of the `esl` table, a column of an unexpected type is an error
+    fn try_from(row: &Row) -> Result<Self, ParseError> {
+        Ok(Self {
+            r#type: row.try_get("type")?,
+            serial: row.try_get("serial")?,
+            printed: row.try_get("printed")?,
+            object_id: row.try_get("objectId")?,
+            item_id: row.try_get("itemId")?,
+            id: row.try_get("eslId")?,
+            nom: row.try_get("nom")?,
+            nom_scientifique: row.try_get("nomScientifique")?,
+            prix: row.try_get("prix")?,
+            infos_prix: row.try_get("infosPrix")?,
+            engin: row.try_get("engin")?,
+            zone: row.try_get("zone")?,
+            zone_code: row.try_get("zoneCode")?,
+            sous_zone: row.try_get("sousZone")?,
+            sous_zone_code: row.try_get("sousZoneCode")?,
+            plu: row.try_get("plu")?,
+            taille: row.try_get("taille")?,
+            congel_infos: row.try_get("congelInfos")?,
+            origine: row.try_get("origine")?,
+            allergenes: row.try_get("allergenes")?,
+            label: row.try_get("label")?,
+            production: row.try_get("production")?,
+            achats: row.try_get("achats")?,
+            categorie: row.try_get("categorie")?,
+            tva: row.try_get("tva")?,
+            // Stock columns are optional, they only exist once stock sync has been set up
+            stock: optional_column(row, "stock")?,
+            arrivage: optional_column(row, "arrivage")?,
+            geo_point: None,
+        })
     }
 }
 
+/// Reads a column that may not exist in the table, `None` when it does not
+#[cfg(feature = "postgres")]
+fn optional_column<'a, T: FromSql<'a>>(row: &'a Row, name: &str) -> Result<Option<T>, ParseError> {
+    if row.columns().iter().any(|column| column.name() == name) {
+        Ok(row.try_get(name)?)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "postgres")]
 impl GenericEsl {
     pub async fn do_save(
         mut esl: GenericEsl,
@@ -148,10 +208,167 @@ impl GenericEsl {
                 &[&serial],
             )
             .await?;
-        let esls: Vec<GenericEsl> = rows.iter().map(GenericEsl::from).collect();
+        let esls = rows
+            .iter()
+            .map(GenericEsl::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(esls)
+    }
+
+    /// Counts the Esls of a serial that are not printed yet
+    pub async fn count_unprinted(
+        serial: &str,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<i64, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        let row = conn
+            .query_one(
+                "SELECT COUNT(*) FROM esl WHERE serial=$1::text AND printed = false",
+                &[&serial],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// Finds every Esl of a serial, printed or not
+    pub async fn find_by_serial(
+        serial: &str,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Vec<Self>, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        let rows = conn
+            .query("SELECT * FROM esl WHERE serial=$1::text", &[&serial])
+            .await?;
+        let esls = rows
+            .iter()
+            .map(GenericEsl::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(esls)
+    }
+
+    /// Finds Esls of a serial by their `eslId`, printed or not
+    pub async fn find_by_ids(
+        serial: &str,
+        ids: &[String],
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Vec<Self>, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        let rows = conn
+            .query(
+                "SELECT * FROM esl WHERE serial=$1::text AND eslId = ANY($2)",
+                &[&serial, &ids],
+            )
+            .await?;
+        let esls = rows
+            .iter()
+            .map(GenericEsl::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(esls)
+    }
+
+    /// Finds Esls of a serial by their PLU, printed or not
+    pub async fn find_by_plus(
+        serial: &str,
+        plus: &[String],
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Vec<Self>, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        let rows = conn
+            .query(
+                "SELECT * FROM esl WHERE serial=$1::text AND plu = ANY($2)",
+                &[&serial, &plus],
+            )
+            .await?;
+        let esls = rows
+            .iter()
+            .map(GenericEsl::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(esls)
     }
 
+    /// Finds every Esl linked to a location, printed or not
+    pub async fn find_by_location(
+        location: &Location,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Vec<Self>, ParseError> {
+        GenericEsl::find_by_ids(&location.serial, &location.esls, pool).await
+    }
+
+    /// Changes the price of an Esl and marks it as not printed so the new price is pushed
+    pub async fn set_prix(
+        mut esl: GenericEsl,
+        prix: String,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Self, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        conn.execute(
+            "UPDATE esl SET prix=$1, printed=false WHERE objectId=$2",
+            &[&prix, &esl.object_id],
+        )
+        .await?;
+        esl.prix = prix;
+        esl.printed = false;
+        Ok(esl)
+    }
+
+    /// Changes the stock of an Esl, it is marked as not printed when `reprint` is set
+    pub async fn set_stock(
+        mut esl: GenericEsl,
+        stock: Option<i32>,
+        arrivage: Option<NaiveDate>,
+        reprint: bool,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<Self, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        conn.execute(
+            "UPDATE esl SET stock=$1, arrivage=$2, printed=(printed AND NOT $3) WHERE objectId=$4",
+            &[&stock, &arrivage, &reprint, &esl.object_id],
+        )
+        .await?;
+        esl.stock = stock;
+        esl.arrivage = arrivage;
+        esl.printed = esl.printed && !reprint;
+        Ok(esl)
+    }
+
+    /// Marks every Esl linked to a location as not printed so they are pushed again.
+    ///
+    /// Returns the number of Esls that will be pushed again
+    pub async fn reprint_location(
+        location: &Location,
+        pool: Pool<PostgresConnectionManager<NoTls>>,
+    ) -> Result<u64, ParseError> {
+        let conn = pool
+            .get()
+            .await
+            .expect("upload: cannot access to the conneciton pool");
+        let updated = conn
+            .execute(
+                "UPDATE esl SET printed=false WHERE serial=$1::text AND eslId = ANY($2)",
+                &[&location.serial, &location.esls],
+            )
+            .await?;
+        Ok(updated)
+    }
+
     /// Specific search methods will aim to find printed and non printed Esls for a specific serial for a specific date
     pub async fn find_by_date(
         serial: String,
@@ -164,7 +381,50 @@ impl GenericEsl {
             .await
             .expect("upload: cannot access to the conneciton pool");
         let rows = conn.query("SELECT * FROM esl WHERE serial=$1 AND createdAt > TO_TIMESTAMP($2,'YYYY-MM-DD HH24:MI:SS:MS') AND createdAt < TO_TIMESTAMP($3,'YYYY-MM-DD HH24:MI:SS:MS')",&[&serial,&start_date, &end_date]).await?;
-        let esls: Vec<GenericEsl> = rows.iter().map(GenericEsl::from).collect();
+        let esls = rows
+            .iter()
+            .map(GenericEsl::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(esls)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::json;
+
+    #[test]
+    fn field_names() {
+        let value = serde_json::to_value(mock::esl()).unwrap();
+        let object = value.as_object().unwrap();
+        for key in [
+            "type",
+            "objectId",
+            "itemId",
+            "eslId",
+            "nomScientifique",
+            "infosPrix",
+            "zoneCode",
+            "sousZone",
+            "sousZoneCode",
+        ] {
+            assert!(object.contains_key(key), "missing {key}");
+        }
+        assert!(!object.contains_key("congelInfos"));
+    }
+
+    #[test]
+    fn queries() {
+        assert_eq!(
+            serde_json::to_value(GenericEsl::unprinted("s1")).unwrap(),
+            json!({ "serial": "s1", "printed": false })
+        );
+        let ids = vec!["e1".to_string(), "e2".to_string()];
+        assert_eq!(
+            serde_json::to_value(GenericEsl::with_ids("s1", &ids)).unwrap(),
+            json!({ "serial": "s1", "eslId": { "$in": ["e1", "e2"] } })
+        );
+    }
+}
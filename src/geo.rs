@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+/// Mean radius of the earth used by Parse to convert distances, in kilometers
+pub(crate) const EARTH_RADIUS_KM: f64 = 6371.;
+
+/// A position stored in a Parse GeoPoint column, such as the location of a store.
+///
+/// Parse allows one GeoPoint column per class. Objects are queried by area with the geo
+/// constraints of [`WhereClause`](crate::query::WhereClause), see [`WhereClause::near`].
+///
+/// [`WhereClause::near`]: crate::query::WhereClause::near
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "__type", rename = "GeoPoint")]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Returns true if the latitude is within ±90° and the longitude within ±180°
+    pub fn is_valid(&self) -> bool {
+        (-90. ..=90.).contains(&self.latitude) && (-180. ..=180.).contains(&self.longitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serialize() {
+        let point = GeoPoint::new(47.39, 0.69);
+        let value = serde_json::to_value(point).unwrap();
+        assert_eq!(
+            value,
+            json!({ "__type": "GeoPoint", "latitude": 47.39, "longitude": 0.69 })
+        );
+        assert_eq!(serde_json::from_value::<GeoPoint>(value).unwrap(), point);
+        assert!(point.is_valid());
+        assert!(!GeoPoint::new(95., 0.).is_valid());
+    }
+}
@@ -0,0 +1,109 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// An error of a GraphQL response
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct GraphQlError {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+/// Reads a GraphQL response, its errors are joined into a [`ParseError::GraphQl`]
+fn interpret<T: for<'de> Deserialize<'de>>(body: &[u8]) -> Result<T, ParseError> {
+    let response: GraphQlResponse<T> = serde_json::from_slice(body)?;
+    match response.data {
+        Some(data) if response.errors.is_empty() => Ok(data),
+        _ => {
+            let messages: Vec<String> = response.errors.into_iter().map(|e| e.message).collect();
+            Err(ParseError::GraphQl {
+                cause: messages.join("; "),
+            })
+        }
+    }
+}
+
+impl ParseClient {
+    /// Runs a GraphQL query or mutation on the `graphql` endpoint of the Parse server.
+    ///
+    /// `T` is the type of the `data` of the response, such as a struct with a `genericEsls`
+    /// field for `query { genericEsls { ... } }`. The server must be 3.5.0 or later, see
+    /// [`Capabilities::graphql`](crate::compat::Capabilities::graphql).
+    pub async fn graphql<T: for<'de> Deserialize<'de>, V: Serialize>(
+        &self,
+        query: &str,
+        variables: V,
+    ) -> Result<T, ParseError> {
+        let body = json!({ "query": query, "variables": variables });
+        let (response, _) = self.send(self.protocol().save("graphql", &body)?).await?;
+        if response.status() != StatusCode::OK
+            && serde_json::from_slice::<GraphQlResponse<serde_json::Value>>(response.body())
+                .is_err()
+        {
+            return Err(protocol::error(&response));
+        }
+        interpret(response.body())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Created {
+        create_generic_esl: CreatedEsl,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct CreatedEsl {
+        generic_esl: CreatedFields,
+    }
+
+    #[derive(Deserialize)]
+    struct CreatedFields {
+        nom: String,
+    }
+
+    const MUTATION: &str = "mutation Create($nom: String!) { createGenericEsl(input: { fields: { nom: $nom } }) { genericEsl { nom } } }";
+
+    #[tokio::test]
+    async fn graphql() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"data":{"createGenericEsl":{"genericEsl":{"nom":"Bar"}}}}"#,
+            ),
+            mock::response(
+                "400 Bad Request",
+                &[],
+                r#"{"errors":[{"message":"Unknown type Strin"}]}"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let created: Created = client
+            .graphql(MUTATION, json!({ "nom": "Bar" }))
+            .await
+            .unwrap();
+        assert_eq!(created.create_generic_esl.generic_esl.nom, "Bar");
+        match client.graphql::<Created, _>(MUTATION, json!({})).await {
+            Err(ParseError::GraphQl { cause }) => assert_eq!(cause, "Unknown type Strin"),
+            _ => panic!("expected a GraphQL error"),
+        }
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("POST /graphql"));
+        assert!(request.contains(r#""variables":{"nom":"Bar"}"#));
+    }
+}
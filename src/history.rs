@@ -0,0 +1,131 @@
+use crate::canonical::payload_hash;
+use crate::date;
+use crate::fetch::{FetchOptions, Order};
+use crate::file::ParseFile;
+use crate::generic_esl::GenericEsl;
+use crate::parse::{ParseClient, ParseCreated, ParseError};
+use crate::pointer::ParseClass;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The Parse class keeping what was displayed on the labels
+pub const DISPLAY_RECORD_CLASS: &str = "classes/DisplayRecord";
+
+/// What a label displayed from a given time on, kept to settle shelf price disputes.
+///
+/// The label data is stored along with its [`payload_hash`], so a record can be checked against
+/// the payload that was actually pushed. A rendered preview can be attached as a Parse file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayRecord {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub esl_id: String,
+    pub serial: String,
+    /// A Parse Date, so records are compared and sorted chronologically
+    #[serde(with = "crate::date")]
+    pub displayed_at: DateTime<Utc>,
+    pub payload_hash: String,
+    pub esl: GenericEsl,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ParseFile>,
+}
+
+impl DisplayRecord {
+    pub fn new(
+        esl: &GenericEsl,
+        displayed_at: DateTime<Utc>,
+        artifact: Option<ParseFile>,
+    ) -> Result<Self, ParseError> {
+        Ok(Self {
+            object_id: None,
+            esl_id: esl.id.clone(),
+            serial: esl.serial.clone(),
+            displayed_at,
+            payload_hash: payload_hash(esl)?,
+            esl: esl.clone(),
+            artifact,
+        })
+    }
+
+    /// Returns true if the recorded label data has not been altered since it was recorded
+    pub fn verify(&self) -> Result<bool, ParseError> {
+        Ok(payload_hash(&self.esl)? == self.payload_hash)
+    }
+}
+
+/// Records what a label displays from `displayed_at` on, call it once the push succeeded
+pub async fn record(
+    client: &ParseClient,
+    esl: &GenericEsl,
+    displayed_at: DateTime<Utc>,
+    artifact: Option<ParseFile>,
+) -> Result<ParseCreated, ParseError> {
+    let record = DisplayRecord::new(esl, displayed_at, artifact)?;
+    client.save(DISPLAY_RECORD_CLASS.to_string(), &record).await
+}
+
+/// Returns what a label displayed at a past date: the last record made before that date
+pub async fn what_was_displayed(
+    client: &ParseClient,
+    esl_id: &str,
+    at: DateTime<Utc>,
+) -> Result<Option<DisplayRecord>, ParseError> {
+    let options = FetchOptions::default()
+        .order(Order::desc("displayed_at"))
+        .limit(1);
+    let records: Vec<DisplayRecord> = client
+        .fetch_with(
+            DISPLAY_RECORD_CLASS.to_string(),
+            json!({ "eslId": esl_id, "displayedAt": { "$lte": date::date(&at) } }),
+            &options,
+        )
+        .await?;
+    Ok(records.into_iter().next())
+}
+
+impl ParseClass for DisplayRecord {
+    const CLASS_NAME: &'static str = "DisplayRecord";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn history() {
+        let at = Utc.with_ymd_and_hms(2023, 6, 1, 8, 0, 0).unwrap();
+        let mut record = DisplayRecord::new(&mock::esl(), at, None).unwrap();
+        assert!(record.verify().unwrap());
+        let mut stored = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            stored["displayedAt"],
+            json!({ "__type": "Date", "iso": "2023-06-01T08:00:00.000Z" })
+        );
+        stored["objectId"] = json!("d1");
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            &json!({ "results": [stored] }).to_string(),
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let disputed = Utc.with_ymd_and_hms(2023, 6, 2, 17, 30, 0).unwrap();
+        let displayed = what_was_displayed(&client, "esl", disputed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(displayed.esl.prix, "18,90");
+        assert_eq!(displayed.displayed_at, at);
+        assert!(displayed.verify().unwrap());
+        let request = &server.join().unwrap()[0];
+        assert!(request.contains("%22%24lte%22%3A%7B%22__type%22%3A%22Date%22"));
+        assert!(request.contains("%222023-06-02T17%3A30%3A00.000Z%22"));
+        assert!(request.contains("&limit=1&order=-displayedAt"));
+
+        record.esl.prix = "12,90".to_string();
+        assert!(!record.verify().unwrap());
+    }
+}
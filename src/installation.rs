@@ -0,0 +1,228 @@
+use crate::parse::{ParseClient, ParseCreated, ParseError};
+use crate::pointer::ParseClass;
+use crate::protocol;
+use crate::push::store_channel;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The path of the installations on the Parse API
+pub const INSTALLATION_PATH: &str = "installations";
+
+/// A device registered to receive push notifications, such as the printing station of a store
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseInstallation {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    /// `android`, `ios`, `web`...
+    pub device_type: String,
+    /// The FCM or APNs token of the device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_token: Option<String>,
+    /// The unique id the app generated for this installation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installation_id: Option<String>,
+    /// The channels the device is subscribed to, see [`Push::to_channels`]
+    ///
+    /// [`Push::to_channels`]: crate::push::Push::to_channels
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+impl ParseInstallation {
+    pub fn new(device_type: &str, device_token: Option<String>) -> Self {
+        Self {
+            object_id: None,
+            device_type: device_type.to_string(),
+            device_token,
+            installation_id: None,
+            channels: vec![],
+        }
+    }
+
+    /// A printing station of a store, subscribed to the channel of the store
+    pub fn printing_station(serial: &str, device_type: &str, device_token: String) -> Self {
+        Self {
+            channels: vec![store_channel(serial)],
+            ..Self::new(device_type, Some(device_token))
+        }
+    }
+
+    /// Returns the path of this installation on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", INSTALLATION_PATH, object_id))
+    }
+
+    /// Registers this installation and sets its objectId.
+    ///
+    /// Parse updates the existing installation instead when one has the same `deviceToken` or
+    /// `installationId`, and answers `200 OK`: the objectId is then the one of the existing
+    /// installation.
+    pub async fn create(&mut self, client: &ParseClient) -> Result<(), ParseError> {
+        let request = client.protocol().save(INSTALLATION_PATH, &*self)?;
+        let (response, _) = client.send(request).await?;
+        if response.status() != StatusCode::OK {
+            let created: ParseCreated = protocol::interpret(&response, StatusCode::CREATED)?;
+            self.object_id = Some(created.object_id);
+            return Ok(());
+        }
+        let updated: Updated = protocol::interpret(&response, StatusCode::OK)?;
+        let object_id = match updated.object_id {
+            Some(object_id) => object_id,
+            None => self
+                .find_existing(client)
+                .await?
+                .ok_or(ParseError::ObectId)?,
+        };
+        self.object_id = Some(object_id);
+        Ok(())
+    }
+
+    /// Finds the installation Parse merged this one into, by `installationId` or `deviceToken`
+    async fn find_existing(&self, client: &ParseClient) -> Result<Option<String>, ParseError> {
+        let query = match (&self.installation_id, &self.device_token) {
+            (Some(installation_id), _) => json!({ "installationId": installation_id }),
+            (None, Some(device_token)) => json!({ "deviceToken": device_token }),
+            (None, None) => return Ok(None),
+        };
+        let installations: Vec<Self> = client.fetch(INSTALLATION_PATH.to_string(), query).await?;
+        Ok(installations
+            .into_iter()
+            .next()
+            .and_then(|installation| installation.object_id))
+    }
+
+    pub async fn update(&self, client: &ParseClient) -> Result<(), ParseError> {
+        client.update(self.path()?, self).await
+    }
+
+    /// Finds an installation by the id the app generated
+    pub async fn find_by_installation_id(
+        client: &ParseClient,
+        installation_id: &str,
+    ) -> Result<Option<Self>, ParseError> {
+        let installations: Vec<Self> = client
+            .fetch(
+                INSTALLATION_PATH.to_string(),
+                json!({ "installationId": installation_id }),
+            )
+            .await?;
+        Ok(installations.into_iter().next())
+    }
+
+    /// Replaces the channels of this installation
+    pub async fn set_channels(
+        &mut self,
+        client: &ParseClient,
+        channels: &[&str],
+    ) -> Result<(), ParseError> {
+        let channels: Vec<String> = channels.iter().map(|c| c.to_string()).collect();
+        client
+            .update(self.path()?, json!({ "channels": channels }))
+            .await?;
+        self.channels = channels;
+        Ok(())
+    }
+
+    /// Adds a channel without overwriting the channels set by another client
+    pub async fn subscribe(
+        &mut self,
+        client: &ParseClient,
+        channel: &str,
+    ) -> Result<(), ParseError> {
+        let body = json!({ "channels": { "__op": "AddUnique", "objects": [channel] } });
+        client.update(self.path()?, body).await?;
+        if !self.channels.iter().any(|c| c == channel) {
+            self.channels.push(channel.to_string());
+        }
+        Ok(())
+    }
+
+    /// Sets the device token, after the push service renewed it
+    pub async fn set_device_token(
+        &mut self,
+        client: &ParseClient,
+        device_token: &str,
+    ) -> Result<(), ParseError> {
+        client
+            .update(self.path()?, json!({ "deviceToken": device_token }))
+            .await?;
+        self.device_token = Some(device_token.to_string());
+        Ok(())
+    }
+
+    pub async fn delete(self, client: &ParseClient) -> Result<(), ParseError> {
+        client.delete(self.path()?).await
+    }
+}
+
+/// The response to a save merged into an existing installation
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Updated {
+    object_id: Option<String>,
+}
+
+impl ParseClass for ParseInstallation {
+    const CLASS_NAME: &'static str = "_Installation";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn installations() {
+        let (url, server) = mock::serve(vec![
+            mock::response("201 Created", &[], r#"{"createdAt":"now","objectId":"i1"}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut station =
+            ParseInstallation::printing_station("serial", "android", "token".to_string());
+        station.create(&client).await.unwrap();
+        assert_eq!(station.object_id.as_deref(), Some("i1"));
+        station.subscribe(&client, "fishmongers").await.unwrap();
+        station.set_device_token(&client, "renewed").await.unwrap();
+        assert_eq!(station.channels, vec!["store-serial", "fishmongers"]);
+        assert_eq!(station.device_token.as_deref(), Some("renewed"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /installations"));
+        assert!(requests[0].contains(r#""channels":["store-serial"]"#));
+        assert!(requests[0].contains(r#""deviceType":"android""#));
+        assert!(requests[1].starts_with("PUT /installations/i1"));
+        assert!(requests[1].contains(r#""__op":"AddUnique""#));
+        assert!(requests[2].contains(r#""deviceToken":"renewed""#));
+    }
+
+    #[tokio::test]
+    async fn deduplicated() {
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], r#"{"objectId":"i1","updatedAt":"now"}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"i2","deviceType":"android","deviceToken":"token"}]}"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let mut station =
+            ParseInstallation::printing_station("serial", "android", "token".to_string());
+        station.create(&client).await.unwrap();
+        assert_eq!(station.object_id.as_deref(), Some("i1"));
+
+        let mut merged =
+            ParseInstallation::printing_station("serial", "android", "token".to_string());
+        merged.create(&client).await.unwrap();
+        assert_eq!(merged.object_id.as_deref(), Some("i2"));
+        let requests = server.join().unwrap();
+        assert!(requests[2].starts_with("GET /installations"));
+        assert!(requests[2].contains("%22deviceToken%22%3A%22token%22"));
+    }
+}
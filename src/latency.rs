@@ -0,0 +1,163 @@
+use log::warn;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The classes of requests sent to Parse
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Save,
+    Fetch,
+    Update,
+    Delete,
+}
+
+/// A request that exceeded its latency threshold.
+///
+/// reqwest does not expose DNS and connection timings: `headers` covers everything from sending
+/// the request to receiving the response headers, `body` is the time spent reading the body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlowQuery {
+    pub operation: Operation,
+    pub path: String,
+    /// The serialized where clause of fetches
+    pub query: Option<String>,
+    pub threshold: Duration,
+    pub headers: Duration,
+    pub body: Duration,
+}
+
+impl SlowQuery {
+    pub fn total(&self) -> Duration {
+        self.headers + self.body
+    }
+}
+
+impl fmt::Display for SlowQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slow {:?} on {}: {}ms (headers {}ms, body {}ms) over {}ms",
+            self.operation,
+            self.path,
+            self.total().as_millis(),
+            self.headers.as_millis(),
+            self.body.as_millis(),
+            self.threshold.as_millis()
+        )?;
+        if let Some(query) = &self.query {
+            write!(f, ", where {query}")?;
+        }
+        Ok(())
+    }
+}
+
+type SlowQueryHandler = Arc<dyn Fn(&SlowQuery) + Send + Sync>;
+
+/// The latency thresholds of a ParseClient.
+///
+/// Requests exceeding the threshold of their operation are logged as warnings on the
+/// `esl_utils::slow_query` target, and passed to the handler set with
+/// [`LatencyBudget::on_slow`].
+#[derive(Clone)]
+pub struct LatencyBudget {
+    default: Duration,
+    thresholds: HashMap<Operation, Duration>,
+    handler: Option<SlowQueryHandler>,
+}
+
+impl LatencyBudget {
+    /// A budget with the same threshold for every operation
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            thresholds: HashMap::new(),
+            handler: None,
+        }
+    }
+
+    /// Sets the threshold of an operation
+    pub fn with(mut self, operation: Operation, threshold: Duration) -> Self {
+        self.thresholds.insert(operation, threshold);
+        self
+    }
+
+    /// Sets a handler receiving every slow query, to forward them to metrics for instance
+    pub fn on_slow(mut self, handler: impl Fn(&SlowQuery) + Send + Sync + 'static) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn threshold(&self, operation: Operation) -> Duration {
+        self.thresholds
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Reports a request if it exceeded its threshold, returns the slow query if so
+    pub(crate) fn check(
+        &self,
+        operation: Operation,
+        path: &str,
+        query: Option<&str>,
+        headers: Duration,
+        body: Duration,
+    ) -> Option<SlowQuery> {
+        let threshold = self.threshold(operation);
+        if headers + body <= threshold {
+            return None;
+        }
+        let slow = SlowQuery {
+            operation,
+            path: path.to_string(),
+            query: query.map(str::to_string),
+            threshold,
+            headers,
+            body,
+        };
+        warn!(target: "esl_utils::slow_query", "{slow}");
+        if let Some(handler) = &self.handler {
+            handler(&slow);
+        }
+        Some(slow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn thresholds() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let events = reported.clone();
+        let budget = LatencyBudget::new(Duration::from_millis(500))
+            .with(Operation::Fetch, Duration::from_secs(2))
+            .on_slow(move |slow| events.lock().unwrap().push(slow.clone()));
+        let ms = Duration::from_millis;
+        assert!(budget
+            .check(Operation::Fetch, "classes/Esl", None, ms(1500), ms(400))
+            .is_none());
+        let slow = budget
+            .check(
+                Operation::Fetch,
+                "classes/Esl",
+                Some("{}"),
+                ms(1500),
+                ms(600),
+            )
+            .unwrap();
+        assert_eq!(slow.total(), ms(2100));
+        assert_eq!(
+            slow.to_string(),
+            "slow Fetch on classes/Esl: 2100ms (headers 1500ms, body 600ms) over 2000ms, where {}"
+        );
+        assert!(budget
+            .check(Operation::Save, "classes/Esl", None, ms(501), ms(0))
+            .is_some());
+        assert_eq!(reported.lock().unwrap().len(), 2);
+    }
+}
@@ -1,3 +1,65 @@
-#![feature(async_fn_in_trait)]
+pub mod acl;
+pub mod aggregate;
+pub mod batch;
+pub mod campaign;
+pub mod canary;
+pub mod canonical;
+pub mod compat;
+pub mod config;
+mod csv;
+pub mod currency;
+pub mod date;
+pub mod endpoint;
+pub mod error;
+pub mod esl_classes;
+pub mod explain;
+pub mod fetch;
+pub mod file;
+pub mod gateway;
 pub mod generic_esl;
+pub mod geo;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod history;
+pub mod installation;
+pub mod latency;
+#[cfg(feature = "live-query")]
+pub mod live_query;
+pub mod location;
+pub mod masking;
+pub mod mentions;
+pub mod migration;
+#[cfg(test)]
+mod mock;
 pub mod parse;
+pub mod permission;
+pub mod pointer;
+pub mod pos;
+pub mod prelude;
+pub mod price;
+pub mod price_zone;
+pub mod print_queue;
+pub mod protocol;
+pub mod provenance;
+pub mod push;
+pub mod query;
+pub mod rate_limit;
+pub mod refresh;
+pub mod retry;
+pub mod role;
+pub mod rules;
+pub mod runtime;
+pub mod shard;
+pub mod stock;
+pub mod store;
+pub mod store_config;
+pub mod supplier;
+pub mod trace;
+pub mod update_check;
+pub mod user;
+#[cfg(feature = "test-utils")]
+pub mod vcr;
+pub mod vendor;
+pub mod watch;
+#[cfg(test)]
+mod wire;
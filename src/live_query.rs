@@ -0,0 +1,230 @@
+use crate::parse::{ParseClient, ParseError};
+use futures::{SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+/// A change of an object matching a subscription
+#[derive(Clone, Debug, PartialEq)]
+pub enum LiveEvent<T> {
+    /// An object was created
+    Create(T),
+    /// An existing object was modified and now matches the query
+    Enter(T),
+    /// An object matching the query was modified
+    Update(T),
+    /// An object was modified and no longer matches the query
+    Leave(T),
+    Delete(T),
+}
+
+/// A message sent by the LiveQuery server
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum ServerMessage {
+    Connected,
+    Subscribed,
+    Create {
+        object: Value,
+    },
+    Enter {
+        object: Value,
+    },
+    Update {
+        object: Value,
+    },
+    Leave {
+        object: Value,
+    },
+    Delete {
+        object: Value,
+    },
+    Error {
+        error: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+fn socket_error(error: tungstenite::Error) -> ParseError {
+    ParseError::LiveQuery {
+        cause: error.to_string(),
+    }
+}
+
+/// Reads a message of the LiveQuery server, returns the event it carries if any
+fn interpret<T: for<'de> Deserialize<'de>>(
+    text: &str,
+) -> Result<(ServerMessage, Option<LiveEvent<T>>), ParseError> {
+    let message: ServerMessage = serde_json::from_str(text)?;
+    let event = match &message {
+        ServerMessage::Create { object } => {
+            LiveEvent::Create(serde_json::from_value(object.clone())?)
+        }
+        ServerMessage::Enter { object } => {
+            LiveEvent::Enter(serde_json::from_value(object.clone())?)
+        }
+        ServerMessage::Update { object } => {
+            LiveEvent::Update(serde_json::from_value(object.clone())?)
+        }
+        ServerMessage::Leave { object } => {
+            LiveEvent::Leave(serde_json::from_value(object.clone())?)
+        }
+        ServerMessage::Delete { object } => {
+            LiveEvent::Delete(serde_json::from_value(object.clone())?)
+        }
+        ServerMessage::Error { error } => {
+            return Err(ParseError::LiveQuery {
+                cause: error.clone(),
+            })
+        }
+        _ => return Ok((message, None)),
+    };
+    Ok((message, Some(event)))
+}
+
+/// Subscriptions to the changes of Parse objects, pushed by a LiveQuery server.
+///
+/// The LiveQuery server has to be enabled for the subscribed classes on the Parse server.
+#[derive(Clone)]
+pub struct LiveQuery<'a> {
+    client: &'a ParseClient,
+    url: String,
+}
+
+impl<'a> LiveQuery<'a> {
+    /// Connects to the LiveQuery server running along the Parse server of a client, see
+    /// [`ServerEndpoint::live_query`](crate::endpoint::ServerEndpoint::live_query)
+    pub fn new(client: &'a ParseClient) -> Self {
+        Self {
+            client,
+            url: client.endpoint().live_query(),
+        }
+    }
+
+    /// Connects to a LiveQuery server running on its own, such as `wss://live.example.com`
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+
+    /// Subscribes to the objects of a class matching a where clause.
+    ///
+    /// The connection authenticates with the keys and session token of the client. The stream
+    /// ends when the server closes the connection, subscribe again to resume: the changes made
+    /// in between are not replayed.
+    pub async fn subscribe<T: for<'de> Deserialize<'de>, U: Serialize>(
+        &self,
+        class_name: &str,
+        query: U,
+    ) -> Result<impl Stream<Item = Result<LiveEvent<T>, ParseError>>, ParseError> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .map_err(socket_error)?;
+        let connect = self.client.protocol().live_query_connect();
+        let subscribe = json!({
+            "op": "subscribe",
+            "requestId": 1,
+            "query": { "className": class_name, "where": query },
+        });
+        for (request, expected) in [(connect, "connected"), (subscribe, "subscribed")] {
+            socket
+                .send(Message::Text(request.to_string()))
+                .await
+                .map_err(socket_error)?;
+            loop {
+                let message = socket.next().await.ok_or_else(|| ParseError::LiveQuery {
+                    cause: format!("connection closed before being {expected}"),
+                })?;
+                if let Message::Text(text) = message.map_err(socket_error)? {
+                    match interpret::<Value>(&text)?.0 {
+                        ServerMessage::Connected | ServerMessage::Subscribed => break,
+                        _ => continue,
+                    }
+                }
+            }
+        }
+        Ok(socket.filter_map(|message| async move {
+            match message {
+                Ok(Message::Text(text)) => interpret(&text).map(|(_, event)| event).transpose(),
+                Ok(_) => None,
+                Err(error) => Some(Err(socket_error(error))),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseCreated;
+    use futures::TryStreamExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn subscribe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut received = vec![];
+            let object = r#"{"objectId":"abc","createdAt":"now"}"#;
+            let replies = [
+                r#"{"op":"connected","clientId":1}"#.to_string(),
+                r#"{"op":"subscribed","clientId":1,"requestId":1}"#.to_string(),
+            ];
+            for reply in replies {
+                received.push(socket.next().await.unwrap().unwrap().into_text().unwrap());
+                socket.send(Message::Text(reply)).await.unwrap();
+            }
+            for op in ["create", "leave"] {
+                let event =
+                    format!(r#"{{"op":"{op}","clientId":1,"requestId":1,"object":{object}}}"#);
+                socket.send(Message::Text(event)).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+            received
+        });
+
+        let client = ParseClient::new(
+            "app".to_string(),
+            Some("key".to_string()),
+            "http://h".to_string(),
+        )
+        .with_session_token("r:abc".to_string());
+        let events: Vec<LiveEvent<ParseCreated>> = LiveQuery::new(&client)
+            .with_url(&url)
+            .subscribe("GenericEsl", json!({ "printed": false }))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(matches!(&events[0], LiveEvent::Create(esl) if esl.object_id == "abc"));
+        assert!(matches!(&events[1], LiveEvent::Leave(_)));
+        assert_eq!(events.len(), 2);
+
+        let received = server.await.unwrap();
+        let connect: Value = serde_json::from_str(&received[0]).unwrap();
+        assert_eq!(
+            connect,
+            json!({ "op": "connect", "applicationId": "app", "restAPIKey": "key", "sessionToken": "r:abc" })
+        );
+        let subscribe: Value = serde_json::from_str(&received[1]).unwrap();
+        assert_eq!(subscribe["query"]["className"], "GenericEsl");
+        assert_eq!(subscribe["query"]["where"]["printed"], false);
+    }
+
+    #[test]
+    fn error() {
+        assert!(matches!(
+            interpret::<Value>(r#"{"op":"error","code":1,"error":"Invalid className","reconnect":false}"#),
+            Err(ParseError::LiveQuery { cause }) if cause == "Invalid className"
+        ));
+        assert!(interpret::<Value>(r#"{"op":"unsubscribed"}"#)
+            .unwrap()
+            .1
+            .is_none());
+    }
+}
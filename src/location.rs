@@ -0,0 +1,137 @@
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The Parse class holding the store locations
+pub const LOCATION_CLASS: &str = "classes/Location";
+
+/// A place inside a store where ESLs are displayed.
+///
+/// A location is identified by its rayon (e.g. "marée"), an optional étagère within the rayon and
+/// an optional position on the étagère. ESLs are linked to a location by their `eslId`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    /// The serial of the store this location belongs to
+    pub serial: String,
+    pub rayon: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etagere: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<i32>,
+    /// The `eslId`s of the labels placed at this location
+    #[serde(default)]
+    pub esls: Vec<String>,
+}
+
+impl Location {
+    pub fn new(serial: String, rayon: String) -> Self {
+        Self {
+            object_id: None,
+            serial,
+            rayon,
+            etagere: None,
+            position: None,
+            esls: vec![],
+        }
+    }
+
+    /// Links an ESL to this location, does nothing if it is already linked
+    pub fn link(&mut self, esl_id: String) {
+        if !self.esls.contains(&esl_id) {
+            self.esls.push(esl_id);
+        }
+    }
+
+    /// Removes the link between an ESL and this location
+    pub fn unlink(&mut self, esl_id: &str) {
+        self.esls.retain(|id| id != esl_id);
+    }
+
+    /// Returns the path of this location on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", LOCATION_CLASS, object_id))
+    }
+
+    /// Finds every location of a rayon for a specific serial
+    pub async fn find_by_rayon(serial: String, rayon: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(
+                LOCATION_CLASS.to_string(),
+                json!({ "serial": serial, "rayon": rayon }),
+            )
+            .await
+    }
+}
+
+impl ParseClass for Location {
+    const CLASS_NAME: &'static str = "Location";
+}
+
+impl ParseObject for Location {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(LOCATION_CLASS.to_string(), self).await
+    }
+
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(LOCATION_CLASS.to_string(), json!({ "serial": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link() {
+        let mut location = Location::new("serial".to_string(), "marée".to_string());
+        location.link("esl1".to_string());
+        location.link("esl2".to_string());
+        location.link("esl1".to_string());
+        assert_eq!(location.esls, vec!["esl1", "esl2"]);
+        location.unlink("esl1");
+        assert_eq!(location.esls, vec!["esl2"]);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut location = Location::new("serial".to_string(), "marée".to_string());
+        location.object_id = Some("abc".to_string());
+        let value = serde_json::to_value(&location).unwrap();
+        assert_eq!(
+            value,
+            json!({ "serial": "serial", "rayon": "marée", "esls": [] })
+        );
+        let parsed: Location = serde_json::from_value(
+            json!({ "objectId": "abc", "serial": "serial", "rayon": "marée" }),
+        )
+        .unwrap();
+        assert_eq!(parsed, location);
+    }
+
+    #[test]
+    fn path_without_object_id() {
+        let location = Location::new("serial".to_string(), "marée".to_string());
+        assert!(matches!(location.path(), Err(ParseError::ObectId)));
+    }
+}
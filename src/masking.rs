@@ -0,0 +1,155 @@
+use crate::generic_esl::GenericEsl;
+use crate::parse::{ParseClient, ParseError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// The fields of a GenericEsl the public provenance service is allowed to see
+const PROVENANCE_FIELDS: &[&str] = &[
+    "serial",
+    "plu",
+    "nom",
+    "nomScientifique",
+    "prix",
+    "infosPrix",
+    "engin",
+    "zone",
+    "zoneCode",
+    "sousZone",
+    "sousZoneCode",
+    "taille",
+    "congelInfos",
+    "origine",
+    "allergenes",
+    "label",
+    "production",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Rule {
+    /// Only these fields are kept
+    Allow(HashSet<String>),
+    /// These fields are removed
+    Deny(HashSet<String>),
+}
+
+/// The restricted view of an object given to a consumer.
+///
+/// Objects are serialized then masked, so a consumer holding a masked value cannot read the
+/// hidden fields whatever the object. Prefer [`MaskingProfile::allow`]: fields added to a
+/// struct later stay hidden until they are explicitly allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaskingProfile {
+    pub name: String,
+    rule: Rule,
+}
+
+impl MaskingProfile {
+    /// A profile keeping only the listed Parse columns
+    pub fn allow(name: &str, fields: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            rule: Rule::Allow(fields.iter().map(|f| f.to_string()).collect()),
+        }
+    }
+
+    /// A profile removing the listed Parse columns
+    pub fn deny(name: &str, fields: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            rule: Rule::Deny(fields.iter().map(|f| f.to_string()).collect()),
+        }
+    }
+
+    /// The profile of the public provenance web service: product information only, no purchase
+    /// price, tax or printing state
+    pub fn provenance() -> Self {
+        MaskingProfile::allow("provenance", PROVENANCE_FIELDS)
+    }
+
+    fn is_visible(&self, field: &str) -> bool {
+        match &self.rule {
+            Rule::Allow(fields) => fields.contains(field),
+            Rule::Deny(fields) => !fields.contains(field),
+        }
+    }
+
+    /// Masks a JSON object, arrays are masked item by item and other values are kept
+    pub fn mask_value(&self, value: Value) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .filter(|(field, _)| self.is_visible(field))
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                Value::Array(values.into_iter().map(|v| self.mask_value(v)).collect())
+            }
+            value => value,
+        }
+    }
+
+    /// Serializes an object then masks it
+    pub fn mask<T: Serialize>(&self, object: &T) -> Result<Value, ParseError> {
+        Ok(self.mask_value(serde_json::to_value(object)?))
+    }
+}
+
+impl GenericEsl {
+    /// Returns the view of this Esl allowed by a masking profile
+    pub fn masked(&self, profile: &MaskingProfile) -> Value {
+        profile
+            .mask(self)
+            .expect("a GenericEsl can always be serialized")
+    }
+}
+
+impl ParseClient {
+    /// Fetches objects and masks them with a profile before returning them
+    pub async fn fetch_masked<U: for<'de> serde::Serialize>(
+        &self,
+        path: String,
+        query: U,
+        profile: &MaskingProfile,
+    ) -> Result<Vec<Value>, ParseError> {
+        let objects: Vec<Value> = self.fetch(path, query).await?;
+        Ok(objects
+            .into_iter()
+            .map(|object| profile.mask_value(object))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::json;
+
+    #[test]
+    fn provenance() {
+        let esl = GenericEsl {
+            achats: Some(9.5),
+            tva: Some("5.5".to_string()),
+            object_id: Some("abc".to_string()),
+            ..mock::esl()
+        };
+        let value = esl.masked(&MaskingProfile::provenance());
+        let object = value.as_object().unwrap();
+        assert_eq!(object["nom"], "Cabillaud");
+        assert_eq!(object["prix"], "18,90");
+        for hidden in ["achats", "tva", "objectId", "printed", "eslId", "itemId"] {
+            assert!(!object.contains_key(hidden), "{hidden} is visible");
+        }
+    }
+
+    #[test]
+    fn deny() {
+        let profile = MaskingProfile::deny("internal", &["achats"]);
+        assert_eq!(
+            profile.mask_value(json!([{ "nom": "Bar", "achats": 3 }, "other"])),
+            json!([{ "nom": "Bar" }, "other"])
+        );
+    }
+}
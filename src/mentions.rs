@@ -0,0 +1,275 @@
+use crate::generic_esl::GenericEsl;
+use crate::rules::RuleSet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The mandatory mentions of a seafood label, each one is rendered as a block of the template.
+///
+/// They come from the consumer information rules on fishery and aquaculture products
+/// (regulation (EU) 1379/2013, article 35) and the national price display rules, see
+/// [`RuleSet`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Mention {
+    /// The commercial designation of the species (`nom`)
+    Denomination,
+    NomScientifique,
+    /// Pêché, pêché en eaux douces or élevé
+    Production,
+    /// The FAO catch area for sea catches
+    Zone,
+    /// The FAO sub-area, required in the North-East Atlantic (27) and the Mediterranean (37)
+    SousZone,
+    /// The country of production for farmed and freshwater products
+    Origine,
+    /// The category of fishing gear
+    Engin,
+    /// The product has been thawed before being sold, see [`DECONGELE_MENTION`]
+    Decongele,
+    /// The price with its unit (`€/kg` for products sold by weight)
+    Prix,
+}
+
+impl fmt::Display for Mention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mention::Denomination => "dénomination",
+            Mention::NomScientifique => "nom scientifique",
+            Mention::Production => "méthode de production",
+            Mention::Zone => "zone de capture",
+            Mention::SousZone => "sous-zone de capture",
+            Mention::Origine => "pays d'origine",
+            Mention::Engin => "engin de pêche",
+            Mention::Decongele => "décongelé",
+            Mention::Prix => "prix",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The production method of a seafood product
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Production {
+    /// Caught at sea
+    Peche,
+    /// Caught in fresh water
+    PecheEauDouce,
+    /// Farmed
+    Elevage,
+}
+
+impl Production {
+    /// Reads the `production` field of an Esl (`peche`, `Pêché en eau douce`, `élevé`...)
+    pub fn parse(production: &str) -> Option<Self> {
+        let normalized: String = production
+            .trim()
+            .to_lowercase()
+            .chars()
+            .map(|c| match c {
+                'é' | 'è' | 'ê' => 'e',
+                c => c,
+            })
+            .collect();
+        if normalized.starts_with("elev") {
+            Some(Production::Elevage)
+        } else if normalized.starts_with("peche") && normalized.contains("douce") {
+            Some(Production::PecheEauDouce)
+        } else if normalized.starts_with("peche") {
+            Some(Production::Peche)
+        } else {
+            None
+        }
+    }
+}
+
+/// The text that must be displayed on thawed products
+pub const DECONGELE_MENTION: &str = "Décongelé - ne pas recongeler";
+
+/// Whether a product has been frozen, derived from the `congel_infos` field of an Esl
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CongelState {
+    /// Never frozen, `congel_infos` is empty
+    Frais,
+    /// Frozen then thawed before being sold
+    Decongele,
+    /// Sold frozen
+    Surgele,
+}
+
+impl CongelState {
+    /// Reads the `congel_infos` field of an Esl.
+    ///
+    /// Unknown non-empty values are considered thawed: displaying the mention by mistake is
+    /// harmless while omitting it is not.
+    pub fn parse(congel_infos: Option<&str>) -> Self {
+        let infos = match congel_infos.map(str::trim) {
+            None | Some("") => return CongelState::Frais,
+            Some(infos) => infos.to_lowercase().replace(['é', 'è', 'ê'], "e"),
+        };
+        if infos.contains("decongel") {
+            CongelState::Decongele
+        } else if infos.contains("surgel") {
+            CongelState::Surgele
+        } else {
+            CongelState::Decongele
+        }
+    }
+
+    pub fn of(esl: &GenericEsl) -> Self {
+        CongelState::parse(esl.congel_infos.as_deref())
+    }
+
+    /// Returns the mention to render for this state, if any
+    pub fn mention(&self) -> Option<&'static str> {
+        match self {
+            CongelState::Decongele => Some(DECONGELE_MENTION),
+            CongelState::Frais | CongelState::Surgele => None,
+        }
+    }
+
+    /// Returns false for thawed products, which are never marked down.
+    ///
+    /// Their use-by date is set when they are thawed and the Esl does not carry it: a discount
+    /// keeping them on sale could not be checked against it.
+    pub fn allows_markdown(&self) -> bool {
+        *self != CongelState::Decongele
+    }
+}
+
+/// A missing or invalid mandatory mention
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub mention: Mention,
+    pub message: String,
+    /// The rule broken, prefixed with its rule set, such as `fr/sous-zone`
+    pub rule: String,
+    /// The text the rule comes from
+    pub source: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.mention, self.message, self.rule)
+    }
+}
+
+/// Returns the mentions that must be rendered on the label of an Esl under the French rules.
+///
+/// The list only depends on the production method and the catch area, an Esl missing some of
+/// these mentions is reported by [`validate`]. See [`RuleSet`] for the rules of other countries.
+pub fn required_mentions(esl: &GenericEsl) -> Vec<Mention> {
+    RuleSet::fr().required_mentions(esl)
+}
+
+/// Checks that an Esl carries every mandatory mention under the French rules
+pub fn validate(esl: &GenericEsl) -> Vec<Violation> {
+    RuleSet::fr().validate(esl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn production() {
+        assert_eq!(Production::parse("peche"), Some(Production::Peche));
+        assert_eq!(Production::parse("Pêché"), Some(Production::Peche));
+        assert_eq!(
+            Production::parse("peche eau douce"),
+            Some(Production::PecheEauDouce)
+        );
+        assert_eq!(Production::parse("Élevé"), Some(Production::Elevage));
+        assert_eq!(Production::parse("eleve"), Some(Production::Elevage));
+        assert_eq!(Production::parse("sauvage"), None);
+    }
+
+    #[test]
+    fn congel_state() {
+        assert_eq!(CongelState::parse(None), CongelState::Frais);
+        assert_eq!(CongelState::parse(Some(" ")), CongelState::Frais);
+        assert_eq!(
+            CongelState::parse(Some("Décongelé")),
+            CongelState::Decongele
+        );
+        assert_eq!(
+            CongelState::parse(Some("decongele, ne pas recongeler")),
+            CongelState::Decongele
+        );
+        assert_eq!(CongelState::parse(Some("Surgelé")), CongelState::Surgele);
+        assert_eq!(
+            CongelState::parse(Some("congelé à bord")),
+            CongelState::Decongele
+        );
+        assert_eq!(CongelState::parse(Some("12/05")), CongelState::Decongele);
+        assert_eq!(CongelState::Decongele.mention(), Some(DECONGELE_MENTION));
+        assert_eq!(CongelState::Surgele.mention(), None);
+        assert!(!CongelState::Decongele.allows_markdown());
+        assert!(CongelState::Surgele.allows_markdown());
+    }
+
+    #[test]
+    fn sea_catch() {
+        let esl = mock::esl();
+        assert_eq!(
+            required_mentions(&esl),
+            vec![
+                Mention::Denomination,
+                Mention::NomScientifique,
+                Mention::Production,
+                Mention::Zone,
+                Mention::SousZone,
+                Mention::Engin,
+                Mention::Prix,
+            ]
+        );
+        assert!(validate(&esl).is_empty());
+
+        let esl = GenericEsl {
+            sous_zone: None,
+            engin: Some(" ".to_string()),
+            ..mock::esl()
+        };
+        let mentions: Vec<Mention> = validate(&esl).into_iter().map(|v| v.mention).collect();
+        assert_eq!(mentions, vec![Mention::SousZone, Mention::Engin]);
+    }
+
+    #[test]
+    fn farmed() {
+        let esl = GenericEsl {
+            nom: "Saumon".to_string(),
+            nom_scientifique: "Salmo salar".to_string(),
+            production: Some("élevé".to_string()),
+            zone: None,
+            zone_code: None,
+            engin: None,
+            congel_infos: Some("décongelé".to_string()),
+            ..mock::esl()
+        };
+        let required = required_mentions(&esl);
+        assert!(required.contains(&Mention::Origine));
+        assert!(required.contains(&Mention::Decongele));
+        assert!(!required.contains(&Mention::Zone));
+        assert!(!required.contains(&Mention::Engin));
+        let frozen = GenericEsl {
+            congel_infos: Some("surgelé".to_string()),
+            ..esl.clone()
+        };
+        assert!(!required_mentions(&frozen).contains(&Mention::Decongele));
+        let violations = validate(&esl);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].mention, Mention::Origine);
+    }
+
+    #[test]
+    fn invalid_price_and_production() {
+        let esl = GenericEsl {
+            prix: "prix libre".to_string(),
+            production: Some("sauvage".to_string()),
+            ..mock::esl()
+        };
+        let mentions: Vec<Mention> = validate(&esl).into_iter().map(|v| v.mention).collect();
+        assert_eq!(mentions, vec![Mention::Production, Mention::Prix]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::fetch::{FetchOptions, Order};
+use crate::parse::{ParseClient, ParseError};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+
+/// The Parse class recording the applied migrations.
+///
+/// Parse reserves class names starting with `_` to its own classes, so this cannot be
+/// `_EslMigrations`.
+pub const MIGRATION_CLASS: &str = "classes/EslMigrations";
+
+/// A data migration, such as backfilling `zoneCode` from `zone`.
+///
+/// Migrations are applied once, in the order they are given to [`migrate`]. Their id is the key
+/// recorded in [`MIGRATION_CLASS`] and must never change once the migration was shipped.
+pub trait Migration: Send + Sync {
+    /// A unique id, such as `2023-06-12-backfill-zone-code`
+    fn id(&self) -> &str;
+
+    /// Applies the migration
+    fn up<'a>(&'a self, client: &'a ParseClient) -> BoxFuture<'a, Result<(), ParseError>>;
+}
+
+/// A migration recorded as applied
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedMigration {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub id: String,
+    #[serde(with = "crate::date")]
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Returns the migrations recorded as applied
+pub async fn applied(client: &ParseClient) -> Result<Vec<AppliedMigration>, ParseError> {
+    let options = FetchOptions::default().order(Order::asc("applied_at"));
+    client
+        .fetch_all(MIGRATION_CLASS.to_string(), json!({}), options)
+        .await
+}
+
+/// Applies the migrations that were not applied yet, in order.
+///
+/// Each migration is recorded right after it succeeded, so a failing migration stops the run and
+/// the next run starts again from it. With `dry_run` nothing is applied nor recorded.
+///
+/// Returns the ids of the migrations applied, or that would be applied with `dry_run`
+pub async fn migrate(
+    client: &ParseClient,
+    migrations: &[Box<dyn Migration>],
+    dry_run: bool,
+) -> Result<Vec<String>, ParseError> {
+    let mut ids = HashSet::new();
+    if let Some(duplicate) = migrations.iter().find(|m| !ids.insert(m.id())) {
+        return Err(ParseError::Migration {
+            id: duplicate.id().to_string(),
+            cause: "this id is used by several migrations".to_string(),
+        });
+    }
+    let done: HashSet<String> = applied(client)
+        .await?
+        .into_iter()
+        .map(|migration| migration.id)
+        .collect();
+    let mut pending = vec![];
+    for migration in migrations.iter().filter(|m| !done.contains(m.id())) {
+        pending.push(migration.id().to_string());
+        if dry_run {
+            info!("Migration {} would be applied", migration.id());
+            continue;
+        }
+        info!("Applying migration {}", migration.id());
+        migration.up(client).await?;
+        let record = AppliedMigration {
+            object_id: None,
+            id: migration.id().to_string(),
+            applied_at: Utc::now(),
+        };
+        client.save(MIGRATION_CLASS.to_string(), &record).await?;
+    }
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use futures::FutureExt;
+
+    struct Backfill(&'static str);
+
+    impl Migration for Backfill {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn up<'a>(&'a self, client: &'a ParseClient) -> BoxFuture<'a, Result<(), ParseError>> {
+            async move {
+                client
+                    .update("classes/Esl/abc".to_string(), json!({ "zoneCode": "27" }))
+                    .await
+            }
+            .boxed()
+        }
+    }
+
+    fn migrations() -> Vec<Box<dyn Migration>> {
+        vec![
+            Box::new(Backfill("2023-06-01-printed-status")),
+            Box::new(Backfill("2023-06-12-backfill-zone-code")),
+        ]
+    }
+
+    const APPLIED: &str = r#"{"results":[{"objectId":"m1","id":"2023-06-01-printed-status","appliedAt":"2023-06-01T08:00:00Z"}]}"#;
+
+    #[tokio::test]
+    async fn migrate() {
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], APPLIED),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+            mock::response("201 Created", &[], r#"{"createdAt":"now","objectId":"m2"}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let applied = super::migrate(&client, &migrations(), false).await.unwrap();
+        assert_eq!(applied, vec!["2023-06-12-backfill-zone-code"]);
+        let requests = server.join().unwrap();
+        assert!(requests[1].starts_with("PUT /classes/Esl/abc"));
+        assert!(requests[2].starts_with("POST /classes/EslMigrations"));
+        assert!(requests[2].contains(r#""id":"2023-06-12-backfill-zone-code""#));
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let (url, server) = mock::serve(vec![mock::response("200 OK", &[], r#"{"results":[]}"#)]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let pending = super::migrate(&client, &migrations(), true).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        let client = ParseClient::new("app".to_string(), None, "http://h".to_string());
+        let mut duplicated = migrations();
+        duplicated.push(Box::new(Backfill("2023-06-01-printed-status")));
+        assert!(matches!(
+            super::migrate(&client, &duplicated, true).await,
+            Err(ParseError::Migration { .. })
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::generic_esl::{EslType, GenericEsl};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+/// Formats an HTTP response with a JSON body
+pub(crate) fn response(status: &str, headers: &[&str], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    for header in headers {
+        response.push_str(header);
+        response.push_str("\r\n");
+    }
+    format!(
+        "{response}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut request = vec![];
+    let mut buffer = [0; 4096];
+    let header_end = loop {
+        let read = stream.read(&mut buffer).unwrap();
+        request.extend_from_slice(&buffer[..read]);
+        if let Some(index) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break index + 4;
+        }
+        assert!(read > 0, "connection closed before the end of the headers");
+    };
+    let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+    let content_length: usize = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .map(|length| length.trim().parse().unwrap())
+        .unwrap_or(0);
+    while request.len() < header_end + content_length {
+        let read = stream.read(&mut buffer).unwrap();
+        assert!(read > 0, "connection closed before the end of the body");
+        request.extend_from_slice(&buffer[..read]);
+    }
+    String::from_utf8(request).unwrap()
+}
+
+/// Answers each connection with the next response.
+///
+/// Returns the url of the server and a handle resolving to the raw requests received.
+pub(crate) fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        responses
+            .into_iter()
+            .map(|response| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = read_request(&mut stream);
+                stream.write_all(response.as_bytes()).unwrap();
+                request
+            })
+            .collect()
+    });
+    (url, handle)
+}
+
+/// A fully described cabillaud Esl
+pub(crate) fn esl() -> GenericEsl {
+    GenericEsl {
+        r#type: EslType::Pricer,
+        serial: "serial".to_string(),
+        printed: false,
+        object_id: None,
+        item_id: Some("item".to_string()),
+        id: "esl".to_string(),
+        nom: "Cabillaud".to_string(),
+        nom_scientifique: "Gadus morhua".to_string(),
+        prix: "18,90".to_string(),
+        infos_prix: "€/kg".to_string(),
+        engin: Some("Chaluts".to_string()),
+        zone: Some("Atlantique Nord-Est".to_string()),
+        zone_code: Some("27".to_string()),
+        sous_zone: Some("Mer du Nord".to_string()),
+        sous_zone_code: Some("IV".to_string()),
+        plu: "1234".to_string(),
+        taille: None,
+        congel_infos: None,
+        origine: None,
+        allergenes: None,
+        label: None,
+        production: Some("Pêché".to_string()),
+        tva: None,
+        categorie: None,
+        achats: None,
+        stock: None,
+        arrivage: None,
+        geo_point: None,
+    }
+}
+
+/// Installs the tokio runtime of the tests, which also run without the `tokio` feature
+pub(crate) fn runtime() {
+    let _ = crate::runtime::set(Box::new(crate::runtime::TokioRuntime));
+}
@@ -1,8 +1,26 @@
+use crate::batch::{BatchOperation, BATCH_SIZE, MAX_BATCH_BYTES};
+use crate::endpoint::ServerEndpoint;
+use crate::fetch::{FetchOptions, DEFAULT_PAGE_SIZE};
+use crate::latency::{LatencyBudget, Operation};
+use crate::permission;
+use crate::pointer::ParseClass;
+use crate::protocol::{self, Protocol};
+use crate::query::WhereClause;
+use crate::rate_limit::{Limiter, RateLimit};
+use crate::retry::{self, RetryPolicy, REQUEST_ID_HEADER};
+use crate::runtime;
+use crate::trace::TraceContext;
+#[cfg(feature = "test-utils")]
+use crate::vcr::{Cassette, RecordedRequest};
 use custom_error::custom_error;
-use http::{HeaderMap, HeaderValue};
-use log::{debug, info};
-use reqwest::{Client, StatusCode, Url};
+use futures::StreamExt;
+use http::HeaderValue;
+use log::{debug, info, warn};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use std::{env, io};
 
 custom_error! {
@@ -17,9 +35,41 @@ custom_error! {
         Io{source: io::Error}= "An I/O error occured: {source}",
         Platform{ code: reqwest::StatusCode, cause: String} =  "An error occured sending log to ParsePlatform. status: {code}, cause: {cause}",
         ObectId = "This ParseObject have no objectId, please create it first",
-        Error{source: tokio_postgres::Error} = "Postgres Error: {source}"
+        Session = "This ParseUser has no session token, please log in first",
+        Query{cause: String} = "Invalid query: {cause}",
+        Import{line: usize, cause: String} = "Invalid import file at line {line}: {cause}",
+        Identity{serial: String, cause: String} = "This gateway is not allowed to push to {serial}: {cause}",
+        Schema{object_id: String, field: String, cause: String} = "Invalid field {field} on objectId {object_id}: {cause}",
+        Migration{id: String, cause: String} = "Invalid migration {id}: {cause}",
+        LiveQuery{cause: String} = "LiveQuery error: {cause}",
+        Cassette{cause: String} = "Cassette error: {cause}",
+        GraphQl{cause: String} = "GraphQL error: {cause}",
+        Forbidden{serial: String, cause: String} = "These credentials cannot modify {serial}: {cause}",
+        Batch{applied: usize, code: i32, cause: String} = "A batch stopped after {applied} operations were applied, Parse error {code}: {cause}",
+        NoRuntime = "No async runtime, enable the tokio feature or call runtime::set",
+        Error{source: PostgresError} = "Postgres Error: {source}"
 }
 
+/// The errors of the Postgres backend of GenericEsl
+#[cfg(feature = "postgres")]
+pub type PostgresError = tokio_postgres::Error;
+
+/// Without the `postgres` feature no Postgres error can occur
+#[cfg(not(feature = "postgres"))]
+#[derive(Debug)]
+pub enum PostgresError {}
+
+#[cfg(not(feature = "postgres"))]
+impl std::fmt::Display for PostgresError {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+impl std::error::Error for PostgresError {}
+
+#[allow(async_fn_in_trait)]
 pub trait ParseObject {
     async fn save(&self) -> Result<ParseCreated, ParseError>;
     async fn find(serial: String) -> Result<Vec<Self>, ParseError>
@@ -28,18 +78,121 @@ pub trait ParseObject {
     async fn update(&mut self) -> Result<Self, ParseError>
     where
         Self: Sized;
+    async fn delete(self) -> Result<(), ParseError>
+    where
+        Self: Sized;
+    /// Fetches an object of this class by its objectId with the global client, see
+    /// [`ParseClient::get_object`]
+    async fn get(object_id: String) -> Result<Self, ParseError>
+    where
+        Self: Sized + ParseClass + for<'de> Deserialize<'de>,
+    {
+        ParseClient::global()
+            .get_object(Self::class_path(), &object_id)
+            .await
+    }
 }
 #[derive(Clone)]
 pub struct ParseClient {
     pub(self) application_id: String,
     pub(self) api_key: Option<String>,
     pub(self) server_url: String,
+    pub(self) etag_cache: Option<Arc<Mutex<EtagCache>>>,
+    pub(self) schema_validation: bool,
+    pub(self) latency_budget: Option<LatencyBudget>,
+    pub(self) master_key: Option<String>,
+    /// Whether the requests are sent with the master key, see [`ParseClient::as_master`]
+    pub(self) use_master_key: bool,
+    /// The session token sent with every request, see [`ParseClient::with_session_token`]
+    pub(self) session_token: Option<String>,
+    /// Whether each credential can modify each serial, see [`ParseClient::with_write_checks`]
+    pub(self) write_checks: Option<Arc<WriteChecks>>,
+    /// How failed requests are retried, see [`ParseClient::with_retry_policy`]
+    pub(self) retry_policy: Option<RetryPolicy>,
+    /// Throttles the requests, see [`ParseClient::with_rate_limit`]
+    pub(self) rate_limiter: Option<Arc<Limiter>>,
+    /// How long establishing a connection may take, see [`ParseClient::with_connect_timeout`]
+    pub(self) connect_timeout: Option<Duration>,
+    /// How long a whole request may take, see [`ParseClient::with_timeout`]
+    pub(self) timeout: Option<Duration>,
+    /// The HTTP client sending the requests, its connection pool is shared by the clones
+    pub(self) http: Client,
+    /// The maximum size of a batch request, see [`ParseClient::with_batch_max_bytes`]
+    pub(self) batch_max_bytes: usize,
+    /// The trace the requests are part of, see [`ParseClient::with_trace_context`]
+    pub(self) trace_context: Option<TraceContext>,
+    /// Records or replays the requests instead of only sending them, see [`crate::vcr`]
+    #[cfg(feature = "test-utils")]
+    pub(self) cassette: Option<Arc<Cassette>>,
+}
+/// The Parse error code of the errors unrelated to Parse, such as a connection error
+const OTHER_CAUSE: i32 = -1;
+/// The credentials a request is sent with, the write checks are made for each of them
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Credential {
+    Session(String),
+    RestKey,
+}
+/// Whether a credential can modify a serial
+/// Whether each credential can modify each serial, and when it was checked
+type WriteChecks = Mutex<HashMap<(Credential, String), (bool, Instant)>>;
+
+/// How long a write check is trusted, an ACL change is seen after it at the latest
+pub const WRITE_CHECK_TTL: Duration = Duration::from_secs(300);
+/// The client used by the `ParseObject` implementations
+static GLOBAL_CLIENT: OnceLock<ParseClient> = OnceLock::new();
+/// The time spent waiting for the headers of a response and reading its body
+pub(crate) struct Timing {
+    headers: Duration,
+    body: Duration,
+}
+/// The most fetch responses kept by the ETag cache, see [`ParseClient::with_etag_cache`]
+pub const ETAG_CACHE_ENTRIES: usize = 256;
+/// The fetch responses kept to answer `304 Not Modified`, by URL
+#[derive(Default)]
+struct EtagCache {
+    responses: HashMap<String, CachedResponse>,
+    /// Incremented on each use, the response with the lowest stamp is the least recently used
+    clock: u64,
+}
+/// A fetch response kept to answer `304 Not Modified`
+struct CachedResponse {
+    etag: String,
+    body: String,
+    /// The clock of the cache when the response was last used
+    used: u64,
+}
+
+impl EtagCache {
+    fn get(&mut self, url: &str) -> Option<&CachedResponse> {
+        self.clock += 1;
+        let cached = self.responses.get_mut(url)?;
+        cached.used = self.clock;
+        Some(cached)
+    }
+
+    /// Keeps a response, evicting the least recently used one when the cache is full
+    fn insert(&mut self, url: String, etag: String, body: String) {
+        if !self.responses.contains_key(&url) && self.responses.len() >= ETAG_CACHE_ENTRIES {
+            let oldest = self
+                .responses
+                .iter()
+                .min_by_key(|(_, cached)| cached.used)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                self.responses.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        let used = self.clock;
+        self.responses
+            .insert(url, CachedResponse { etag, body, used });
+    }
 }
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ParseCreated {
-    #[serde(rename = "createdAt")]
     pub created_at: String,
-    #[serde(rename = "objectId")]
     pub object_id: String,
 }
 /// The response format of Parse query API
@@ -50,9 +203,32 @@ pub struct QueryResponse<T> {
 /// The response format of Parse API errors
 #[derive(Deserialize, Serialize)]
 pub struct ParseErrorResponse {
-    code: i32,
-    error: String,
+    pub(crate) code: i32,
+    pub(crate) error: String,
 }
+/// Returns the Parse column name of a Rust struct field.
+///
+/// Parse classes use camelCase columns (`nomScientifique`, `sousZoneCode`) while our structs use
+/// snake_case fields. Structs mapped to a Parse class should be annotated with
+/// `#[serde(rename_all = "camelCase")]`, this function applies the very same conversion for the
+/// places where a column name is written by hand (queries, keys, ordering...).
+pub fn parse_field_name(field: &str) -> String {
+    let field = field.strip_prefix("r#").unwrap_or(field);
+    let mut name = String::with_capacity(field.len());
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' {
+            upper = !name.is_empty();
+        } else if upper {
+            name.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
 /// A really basic ParsePlatform Rest API client
 impl ParseClient {
     pub fn new(application_id: String, api_key: Option<String>, server_url: String) -> Self {
@@ -60,22 +236,306 @@ impl ParseClient {
             application_id,
             api_key,
             server_url,
+            etag_cache: None,
+            schema_validation: false,
+            latency_budget: None,
+            master_key: None,
+            use_master_key: false,
+            session_token: None,
+            write_checks: None,
+            retry_policy: None,
+            rate_limiter: None,
+            connect_timeout: None,
+            timeout: None,
+            http: http_client(None, None),
+            batch_max_bytes: MAX_BATCH_BYTES,
+            trace_context: None,
+            #[cfg(feature = "test-utils")]
+            cassette: None,
         }
     }
 
-    /// Returns a reqwest client with parse Authentication headers set
-    fn get_client(&self) -> Result<Client, ParseError> {
-        let mut headers = HeaderMap::new();
-        let application_id = HeaderValue::from_str(&self.application_id)
-            .expect("Cannot encode application ID into a request header");
-        if let Some(api_key) = &self.api_key {
-            let key = HeaderValue::from_str(api_key)
-                .expect("Cannot encode application key into a request header");
-            headers.append("X-Parse-REST-API-Key", key);
+    /// Enables the ETag cache of [`ParseClient::fetch`].
+    ///
+    /// Fetch responses carrying an `ETag` header are kept in memory and the next identical
+    /// fetch sends `If-None-Match`. When the server (or a CDN in front of it) answers
+    /// `304 Not Modified`, the results are read from the cache. Clones of this client share
+    /// the same cache.
+    ///
+    /// At most [`ETAG_CACHE_ENTRIES`] responses are kept, the least recently used one is evicted
+    /// beyond it. Each URL is an entry, so the queries changing on every poll (a timestamp, a
+    /// cursor) only churn the cache: enable it for the clients sending repeated queries.
+    pub fn with_etag_cache(mut self) -> Self {
+        self.etag_cache = Some(Arc::new(Mutex::new(EtagCache::default())));
+        self
+    }
+
+    /// Enables the schema validation of [`ParseClient::fetch`].
+    ///
+    /// Each fetched object is deserialized on its own, a type mismatch is reported as a
+    /// [`ParseError::Schema`] naming the objectId and the path of the invalid field instead of
+    /// failing the whole response with a terse serde error. It is slower, use it to track down
+    /// column type changes.
+    pub fn with_schema_validation(mut self) -> Self {
+        self.schema_validation = true;
+        self
+    }
+
+    /// Retries the requests failing with a `5xx` or `429` response, a timeout or a connection
+    /// error, see [`RetryPolicy`].
+    ///
+    /// A save whose response was lost may have been applied: every attempt of a request other
+    /// than a `GET` carries the same `X-Parse-Request-Id`, enable the `idempotencyOptions` of
+    /// the server so it does not apply a retried save or batch twice.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Throttles the requests of this client, see [`RateLimit`].
+    ///
+    /// Requests wait for their turn before being sent, each attempt of a retried request
+    /// included. Clones of this client share the limit.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limiter = Some(Arc::new(Limiter::new(&limit)));
+        self
+    }
+
+    /// Fails the requests not connected to the server within `timeout`.
+    ///
+    /// Without it a connection attempt waits for the operating system to give up, which can take
+    /// minutes.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self.http = http_client(self.connect_timeout, self.timeout);
+        self
+    }
+
+    /// Fails the requests not completed within `timeout`, from connecting to reading the whole
+    /// response.
+    ///
+    /// Without it a hung server blocks the request forever. A timed out request fails with a
+    /// [`ParseError::Reqwest`] and is retried by the retry policy, if any.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self.http = http_client(self.connect_timeout, self.timeout);
+        self
+    }
+
+    /// Splits the batch requests whose body would exceed `max_bytes`, the `maxUploadSize` of the
+    /// server, see [`MAX_BATCH_BYTES`]
+    pub fn with_batch_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.batch_max_bytes = max_bytes;
+        self
+    }
+
+    pub(crate) fn batch_max_bytes(&self) -> usize {
+        self.batch_max_bytes
+    }
+
+    /// Reports the successful requests exceeding a latency budget, see [`LatencyBudget`]
+    pub fn with_latency_budget(mut self, budget: LatencyBudget) -> Self {
+        self.latency_budget = Some(budget);
+        self
+    }
+
+    /// Sets the master key of the application.
+    ///
+    /// The master key bypasses ACLs and class permissions, so it is not sent by default: the
+    /// privileged requests are sent through [`ParseClient::as_master`]. Aggregate queries use it
+    /// on their own.
+    pub fn with_master_key(mut self, master_key: String) -> Self {
+        self.master_key = Some(master_key);
+        self
+    }
+
+    /// Returns a client sending its requests with the master key, such as
+    /// `client.as_master().update(...)`.
+    ///
+    /// Without a master key the requests are sent with the REST API key, and Parse refuses the
+    /// privileged ones.
+    pub fn as_master(&self) -> Self {
+        Self {
+            use_master_key: true,
+            ..self.clone()
         }
-        headers.append("X-Parse-Application-Id", application_id);
-        debug!("Forged request headers Headers {:?}", headers);
-        Ok(Client::builder().default_headers(headers).build()?)
+    }
+
+    /// Sends the requests as the user of a session, see
+    /// [`ParseUser::log_in`](crate::user::ParseUser::log_in)
+    pub fn with_session_token(mut self, session_token: String) -> Self {
+        self.session_token = Some(session_token);
+        self
+    }
+
+    pub fn session_token(&self) -> Option<&str> {
+        self.session_token.as_deref()
+    }
+
+    /// Refuses the saves, updates, deletes and batches of objects with a `serial` the credentials
+    /// of this client cannot modify, before sending them.
+    ///
+    /// The serial of an updated or deleted object is read from Parse first, so a write that does
+    /// not carry it is checked too.
+    ///
+    /// Permissions are checked with [`permission::can_write`] once per serial and credential, a
+    /// batch fails upfront with [`ParseError::Forbidden`] instead of failing midway with 403
+    /// errors. Clones of this client share the checks already made, those of a clone sending
+    /// another session token are its own. A check is made again after [`WRITE_CHECK_TTL`], so an
+    /// ACL change applies within 5 minutes. The writes sent with the master key are not checked.
+    pub fn with_write_checks(mut self) -> Self {
+        self.write_checks = Some(Arc::new(Mutex::new(HashMap::new())));
+        self
+    }
+
+    /// Returns true if the requests are sent with the master key
+    pub fn uses_master_key(&self) -> bool {
+        self.use_master_key && self.master_key.is_some()
+    }
+
+    fn credential(&self) -> Credential {
+        match &self.session_token {
+            Some(session_token) => Credential::Session(session_token.clone()),
+            None => Credential::RestKey,
+        }
+    }
+
+    /// Checks the write permissions on the serials of objects about to be modified, see
+    /// [`ParseClient::with_write_checks`].
+    ///
+    /// The serials are the ones of the bodies sent and the ones of the objects already stored
+    /// at `targets`, the paths of the updated and deleted objects: an update without a `serial`
+    /// is checked against the store of the object it modifies.
+    pub(crate) async fn check_writes<'a>(
+        &self,
+        bodies: impl IntoIterator<Item = &'a serde_json::Value>,
+        targets: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), ParseError> {
+        let Some(checks) = &self.write_checks else {
+            return Ok(());
+        };
+        if self.uses_master_key() {
+            return Ok(());
+        }
+        let mut serials: Vec<String> = bodies
+            .into_iter()
+            .filter_map(|body| Some(body.get("serial")?.as_str()?.to_string()))
+            .collect();
+        serials.extend(self.stored_serials(targets).await?);
+        serials.sort_unstable();
+        serials.dedup();
+        let credential = self.credential();
+        for serial in serials {
+            let key = (credential.clone(), serial.clone());
+            let checked = checks.lock().unwrap().get(&key).copied();
+            let allowed = match checked {
+                Some((allowed, at)) if at.elapsed() < WRITE_CHECK_TTL => allowed,
+                _ => {
+                    let allowed = permission::can_write(self, &serial).await?;
+                    checks
+                        .lock()
+                        .unwrap()
+                        .insert(key, (allowed, Instant::now()));
+                    allowed
+                }
+            };
+            if !allowed {
+                return Err(ParseError::Forbidden {
+                    serial,
+                    cause: "the ACL of the store does not grant write access".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the serials of the objects stored at some paths, such as `classes/Esl/<objectId>`,
+    /// with one query per class and [`BATCH_SIZE`] objects, so the urls stay short
+    async fn stored_serials<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<String>, ParseError> {
+        let mut classes: HashMap<&str, Vec<&str>> = HashMap::new();
+        for path in paths {
+            if let Some((class, object_id)) = path.rsplit_once('/') {
+                classes.entry(class).or_default().push(object_id);
+            }
+        }
+        let mut serials = vec![];
+        for (class, object_ids) in classes {
+            for object_ids in object_ids.chunks(BATCH_SIZE) {
+                let params = [
+                    ("keys", "serial".to_string()),
+                    ("limit", object_ids.len().to_string()),
+                ];
+                let query = serde_json::json!({ "objectId": { "$in": object_ids } });
+                let objects: Vec<SerialOnly> =
+                    self.fetch_params(class.to_string(), query, &params).await?;
+                serials.extend(objects.into_iter().filter_map(|object| object.serial));
+            }
+        }
+        Ok(serials)
+    }
+
+    /// Sends the requests as part of the trace of the caller, such as
+    /// `client.with_trace_context(context.child())` for one operation.
+    ///
+    /// The server spans of the requests are then children of the span of this context.
+    pub fn with_trace_context(mut self, trace_context: TraceContext) -> Self {
+        self.trace_context = Some(trace_context);
+        self
+    }
+
+    /// Records the requests of this client to a cassette, or answers them from a recorded one.
+    ///
+    /// Clones of this client share the cassette.
+    #[cfg(feature = "test-utils")]
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    fn check_latency(&self, operation: Operation, path: &str, query: Option<&str>, timing: Timing) {
+        if let Some(budget) = &self.latency_budget {
+            budget.check(operation, path, query, timing.headers, timing.body);
+        }
+    }
+
+    /// Reads the results of a query response
+    fn parse_results<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        body: &str,
+    ) -> Result<Vec<T>, ParseError> {
+        if !self.schema_validation {
+            let results: QueryResponse<T> = serde_json::from_str(body)?;
+            return Ok(results.results);
+        }
+        let results: QueryResponse<serde_json::Value> = serde_json::from_str(body)?;
+        results
+            .results
+            .into_iter()
+            .map(|object| {
+                let object_id = object
+                    .get("objectId")
+                    .and_then(|id| id.as_str())
+                    .unwrap_or("?")
+                    .to_string();
+                serde_path_to_error::deserialize(object).map_err(|error| ParseError::Schema {
+                    object_id,
+                    field: error.path().to_string(),
+                    cause: error.into_inner().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the reqwest client sending the requests.
+    ///
+    /// It is built once, with the timeouts, and shared by the clones of this client such as
+    /// [`ParseClient::as_master`]: the authentication headers are set on each request by the
+    /// [`Protocol`], so the connections and TLS sessions are reused across credentials.
+    pub(crate) fn get_client(&self) -> &Client {
+        &self.http
     }
 
     /// Returns a new ParseClient by reading properties from the environment.
@@ -83,20 +543,178 @@ impl ParseClient {
     /// * PARSE_APPLICATION_ID
     /// * PARSE_API_KEY
     /// * PARSE_SERVER_URL
+    /// * PARSE_MASTER_KEY, optional
+    /// * PARSE_TIMEOUT_MS, optional, see [`ParseClient::with_timeout`]
+    /// * PARSE_CONNECT_TIMEOUT_MS, optional, see [`ParseClient::with_connect_timeout`]
     pub fn from_env() -> Self {
         let parse_application_id =
             env::var("PARSE_APPLICATION_ID").expect("env.PARSE_APPLICATION_ID is undefined");
         let parse_api_key = env::var("PARSE_API_KEY").ok();
         let parse_server_url =
             env::var("PARSE_SERVER_URL").expect("env.PARSE_SERVER_URL is undefined");
-        ParseClient::new(parse_application_id, parse_api_key, parse_server_url)
+        let mut client = ParseClient::new(parse_application_id, parse_api_key, parse_server_url);
+        client.timeout = env_millis("PARSE_TIMEOUT_MS");
+        client.connect_timeout = env_millis("PARSE_CONNECT_TIMEOUT_MS");
+        client.http = http_client(client.connect_timeout, client.timeout);
+        match env::var("PARSE_MASTER_KEY") {
+            Ok(master_key) => client.with_master_key(master_key),
+            Err(_) => client,
+        }
+    }
+
+    /// Returns the endpoint of the Parse server
+    pub fn endpoint(&self) -> ServerEndpoint {
+        ServerEndpoint::new(&self.server_url)
+    }
+
+    /// Sets the client used by the `ParseObject` implementations.
+    ///
+    /// It can only be set once, before the first call to [`ParseClient::global`]: the client is
+    /// given back when one is already set. Functions taking a `&ParseClient` remain the way to
+    /// use several clients.
+    // The client is given back as is, boxing it would change the signature
+    #[allow(clippy::result_large_err)]
+    pub fn initialize(client: ParseClient) -> Result<(), ParseClient> {
+        GLOBAL_CLIENT.set(client)
+    }
+
+    /// Returns the client set by [`ParseClient::initialize`], if any
+    pub fn get() -> Option<&'static ParseClient> {
+        GLOBAL_CLIENT.get()
+    }
+
+    /// Returns the client used by the `ParseObject` implementations.
+    ///
+    /// The client is read once from the environment (see [`ParseClient::from_env`]) when
+    /// [`ParseClient::initialize`] has not been called.
+    pub fn global() -> &'static ParseClient {
+        GLOBAL_CLIENT.get_or_init(ParseClient::from_env)
+    }
+
+    /// Returns the request builder of this client, see [`Protocol`]
+    pub fn protocol(&self) -> Protocol {
+        let protocol = Protocol::new(
+            self.application_id.clone(),
+            self.api_key.clone(),
+            self.endpoint(),
+        );
+        let protocol = match &self.session_token {
+            Some(session_token) => protocol.with_session_token(session_token.clone()),
+            None => protocol,
+        };
+        let protocol = match &self.trace_context {
+            Some(trace_context) => protocol.with_trace_context(trace_context.clone()),
+            None => protocol,
+        };
+        match &self.master_key {
+            Some(master_key) if self.use_master_key => protocol.with_master_key(master_key.clone()),
+            _ => protocol,
+        }
+    }
+
+    /// Sends a request built by the [`Protocol`] and reads the whole response
+    pub(crate) async fn send(
+        &self,
+        request: http::Request<Vec<u8>>,
+    ) -> Result<(http::Response<Vec<u8>>, Timing), ParseError> {
+        info!("Sending {} {}", request.method(), request.uri());
+        #[cfg(feature = "test-utils")]
+        if let Some(cassette) = &self.cassette {
+            let recorded = RecordedRequest::of(&request);
+            if !cassette.is_recording() {
+                let response = cassette.answer(&recorded)?;
+                let timing = Timing {
+                    headers: Duration::ZERO,
+                    body: Duration::ZERO,
+                };
+                return Ok((response, timing));
+            }
+            let (response, timing) = self.execute(request).await?;
+            cassette.push(recorded, &response);
+            return Ok((response, timing));
+        }
+        self.execute(request).await
+    }
+
+    /// Sends a request to the Parse server, retrying it according to the retry policy
+    async fn execute(
+        &self,
+        mut request: http::Request<Vec<u8>>,
+    ) -> Result<(http::Response<Vec<u8>>, Timing), ParseError> {
+        let Some(policy) = &self.retry_policy else {
+            return self.execute_once(request).await;
+        };
+        if request.method() != http::Method::GET
+            && !request.headers().contains_key(REQUEST_ID_HEADER)
+        {
+            let id = HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("a uuid is a valid header value");
+            request.headers_mut().insert(REQUEST_ID_HEADER, id);
+        }
+        let mut attempt = 1;
+        loop {
+            let mut copy = http::Request::new(request.body().clone());
+            *copy.method_mut() = request.method().clone();
+            *copy.uri_mut() = request.uri().clone();
+            *copy.headers_mut() = request.headers().clone();
+            let result = self.execute_once(copy).await;
+            let retry = match &result {
+                Ok((response, _)) => policy.retries_status(response.status()),
+                Err(error) => policy.retries_error(error),
+            };
+            if !retry || attempt >= policy.max_attempts {
+                return result;
+            }
+            let retry_after = match &result {
+                Ok((response, _)) => retry::retry_after(response.headers(), chrono::Utc::now()),
+                Err(_) => None,
+            };
+            let Some(delay) = policy.delay_after(attempt, retry_after) else {
+                return result;
+            };
+            match &result {
+                Ok((response, _)) => warn!(
+                    "{} {} answered {}, retrying in {delay:?}",
+                    request.method(),
+                    request.uri(),
+                    response.status()
+                ),
+                Err(error) => warn!(
+                    "{} {} failed, retrying in {delay:?}: {error}",
+                    request.method(),
+                    request.uri()
+                ),
+            }
+            runtime::get()?.sleep(delay).await;
+            attempt += 1;
+        }
     }
 
-    /// Merges a parse object path with the server root url
-    fn get_url(&self, path: String) -> String {
-        let formatted = format!("{}/{}", self.server_url, path);
-        info!("Formated url {}", formatted);
-        formatted
+    async fn execute_once(
+        &self,
+        request: http::Request<Vec<u8>>,
+    ) -> Result<(http::Response<Vec<u8>>, Timing), ParseError> {
+        let _permit = match &self.rate_limiter {
+            Some(limiter) => limiter.acquire().await?,
+            None => None,
+        };
+        let client = self.get_client();
+        let sent = Instant::now();
+        let response = client.execute(reqwest::Request::try_from(request)?).await?;
+        let headers = Instant::now();
+        let mut builder = http::Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            builder = builder.header(name, value);
+        }
+        let body = response.bytes().await?.to_vec();
+        let timing = Timing {
+            headers: headers - sent,
+            body: headers.elapsed(),
+        };
+        let response = builder
+            .body(body)
+            .expect("the response was valid for reqwest");
+        Ok((response, timing))
     }
 
     /// Saves a ParseObject by sending a POST request to the Parse API
@@ -105,26 +723,18 @@ impl ParseClient {
         path: String,
         data: T,
     ) -> Result<ParseCreated, ParseError> {
-        let client = self.get_client()?;
         debug!(
             "Attempting to save ParseObject: {:?}",
             serde_json::to_string(&data)
         );
-        let response = client.post(self.get_url(path)).json(&data).send().await?;
-        match response.status() {
-            StatusCode::CREATED => {
-                let created: ParseCreated = response.json().await?;
-                Ok(created)
-            }
-            error_code => {
-                // Extract the error content
-                let err_json: ParseErrorResponse = response.json().await?;
-                Err(ParseError::Platform {
-                    code: error_code,
-                    cause: err_json.error,
-                })
-            }
+        if self.write_checks.is_some() {
+            self.check_writes([&serde_json::to_value(&data)?], [])
+                .await?;
         }
+        let (response, timing) = self.send(self.protocol().save(&path, &data)?).await?;
+        let created = protocol::interpret(&response, StatusCode::CREATED)?;
+        self.check_latency(Operation::Save, &path, None, timing);
+        Ok(created)
     }
     /// Find one or many ParseObject(s) by sending a GET request to the Parse API
     ///
@@ -135,50 +745,297 @@ impl ParseClient {
         path: String,
         query: U,
     ) -> Result<Vec<T>, ParseError> {
-        let client = self.get_client()?;
-        let payload = serde_json::to_string(&query)?;
-        let mut url = Url::parse(&self.get_url(path)).map_err(|_e| ParseError::Url)?;
-        url.query_pairs_mut().append_pair("where", &payload);
-        let response = client.get(url).send().await?;
+        self.fetch_params(path, query, &[]).await
+    }
+
+    /// Fetches with extra query parameters (`limit`, `keys`...) next to `where`
+    pub(crate) async fn fetch_params<
+        T: for<'de> serde::Deserialize<'de>,
+        U: for<'de> serde::Serialize,
+    >(
+        &self,
+        path: String,
+        query: U,
+        params: &[(&str, String)],
+    ) -> Result<Vec<T>, ParseError> {
+        let mut request = self.protocol().fetch(&path, &query, params)?;
+        let url = request.uri().to_string();
+        if let Some(cache) = &self.etag_cache {
+            if let Some(cached) = cache.lock().unwrap().get(&url) {
+                let etag = HeaderValue::from_str(&cached.etag).map_err(|_e| ParseError::Url)?;
+                request
+                    .headers_mut()
+                    .insert(http::header::IF_NONE_MATCH, etag);
+            }
+        }
+        let (response, timing) = self.send(request).await?;
         match response.status() {
             StatusCode::OK => {
-                let results: QueryResponse<T> = response.json().await?;
-                Ok(results.results)
+                let payload = serde_json::to_string(&query)?;
+                self.check_latency(Operation::Fetch, &path, Some(&payload), timing);
+                let etag = response
+                    .headers()
+                    .get(http::header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+                let body = String::from_utf8_lossy(response.body()).into_owned();
+                let results = self.parse_results(&body)?;
+                if let (Some(cache), Some(etag)) = (&self.etag_cache, etag) {
+                    cache.lock().unwrap().insert(url, etag, body);
+                }
+                Ok(results)
             }
-            error_code => {
-                let err_json: ParseErrorResponse = response.json().await?;
-                Err(ParseError::Platform {
-                    code: error_code,
-                    cause: err_json.error,
-                })
+            StatusCode::NOT_MODIFIED if self.etag_cache.is_some() => {
+                debug!("Serving {url} from the ETag cache");
+                let mut cache = self.etag_cache.as_ref().unwrap().lock().unwrap();
+                let cached = cache.get(&url).ok_or(ParseError::Platform {
+                    code: StatusCode::NOT_MODIFIED,
+                    cause: "not modified but missing from the ETag cache".to_string(),
+                })?;
+                self.parse_results(&cached.body)
             }
+            _ => Err(protocol::error(&response)),
         }
     }
 
+    /// Reads a resource of the Parse API by sending a GET request
+    pub(crate) async fn get_resource<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        path: String,
+    ) -> Result<T, ParseError> {
+        let (response, _) = self.send(self.protocol().get(&path)?).await?;
+        protocol::interpret(&response, StatusCode::OK)
+    }
+
+    /// Fetches a single object by its objectId, such as
+    /// `client.get_object("classes/HanshowEsl".to_string(), "Ed1nuqPvcm")`.
+    ///
+    /// An unknown objectId fails with a `404` [`ParseError::Platform`].
+    pub async fn get_object<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        path: String,
+        object_id: &str,
+    ) -> Result<T, ParseError> {
+        if object_id.is_empty()
+            || !object_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ParseError::Query {
+                cause: format!("invalid objectId {object_id:?}"),
+            });
+        }
+        let path = format!("{path}/{object_id}");
+        let (response, timing) = self.send(self.protocol().get(&path)?).await?;
+        let object = protocol::interpret(&response, StatusCode::OK)?;
+        self.check_latency(Operation::Fetch, &path, None, timing);
+        Ok(object)
+    }
+
+    /// Validates a where clause before sending it with [`ParseClient::fetch`]
+    pub async fn fetch_where<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        path: String,
+        query: &WhereClause,
+    ) -> Result<Vec<T>, ParseError> {
+        query.validate()?;
+        self.fetch(path, query).await
+    }
+
     /// Updates a ParseObject by sending a PUT request to the Parse API
     pub async fn update<T: serde::Serialize>(
         &self,
         path: String,
         data: T,
     ) -> Result<(), ParseError> {
-        let client = self.get_client()?;
-        let response = client.put(self.get_url(path)).json(&data).send().await?;
-        match response.status() {
-            StatusCode::OK => Ok(()),
-            error_code => {
-                let err_json: ParseErrorResponse = response.json().await?;
-                Err(ParseError::Platform {
-                    code: error_code,
-                    cause: err_json.error,
+        if self.write_checks.is_some() {
+            self.check_writes([&serde_json::to_value(&data)?], [path.as_str()])
+                .await?;
+        }
+        let (response, timing) = self.send(self.protocol().update(&path, &data)?).await?;
+        protocol::interpret_empty(&response, StatusCode::OK)?;
+        self.check_latency(Operation::Update, &path, None, timing);
+        Ok(())
+    }
+
+    /// Deletes a ParseObject by sending a DELETE request to the Parse API
+    pub async fn delete(&self, path: String) -> Result<(), ParseError> {
+        self.check_writes([], [path.as_str()]).await?;
+        let (response, timing) = self.send(self.protocol().delete(&path)?).await?;
+        protocol::interpret_empty(&response, StatusCode::OK)?;
+        self.check_latency(Operation::Delete, &path, None, timing);
+        Ok(())
+    }
+
+    /// Counts the objects of a class matching a query, before deleting them with
+    /// [`ParseClient::delete_where`].
+    ///
+    /// Check the count, then pass the token as [`DeleteOptions::confirm`].
+    pub async fn delete_confirmation<U: serde::Serialize>(
+        &self,
+        path: String,
+        query: U,
+    ) -> Result<DeleteConfirmation, ParseError> {
+        let query = serde_json::to_value(query)?;
+        let count = self.count(path.clone(), &query).await?;
+        Ok(DeleteConfirmation {
+            count,
+            token: delete_token(&path, &query, count),
+        })
+    }
+
+    /// Deletes every object of a class matching a query.
+    ///
+    /// Nothing is deleted when more than `options.max_count` objects match, or when
+    /// `options.confirm` is not the token of [`ParseClient::delete_confirmation`] for the same
+    /// query and the same count. The objects are read with cursor paging and deleted page by
+    /// page through the batch API, at most the number counted. Returns the number of deleted
+    /// objects, or a [`ParseError::Batch`] with the number of objects deleted before the first
+    /// error and its Parse error code.
+    pub async fn delete_where<U: serde::Serialize>(
+        &self,
+        path: String,
+        query: U,
+        options: DeleteOptions,
+    ) -> Result<usize, ParseError> {
+        let query = serde_json::to_value(query)?;
+        let count = self.count(path.clone(), &query).await?;
+        if count > options.max_count as u64 {
+            return Err(ParseError::Query {
+                cause: format!(
+                    "{count} objects of {path} match, more than {}, nothing was deleted",
+                    options.max_count
+                ),
+            });
+        }
+        if options.confirm != delete_token(&path, &query, count) {
+            return Err(ParseError::Query {
+                cause: format!(
+                    "the deletion of the {count} matching objects of {path} is not confirmed"
+                ),
+            });
+        }
+        let fetch = FetchOptions::default().keys(&["object_id"]).cursor();
+        let mut matches = std::pin::pin!(self
+            .fetch_stream::<ObjectIdOnly, _>(path.clone(), &query, fetch)
+            .take(count as usize));
+        let mut deleted = 0;
+        let mut page = vec![];
+        loop {
+            let next = matches.next().await.transpose()?;
+            if let Some(object) = next {
+                page.push(BatchOperation::delete(format!(
+                    "{path}/{}",
+                    object.object_id
+                )));
+                if page.len() < DEFAULT_PAGE_SIZE {
+                    continue;
+                }
+            }
+            if page.is_empty() {
+                return Ok(deleted);
+            }
+            deleted += self.delete_page(&page, deleted).await?;
+            page.clear();
+        }
+    }
+
+    /// Deletes a page of [`ParseClient::delete_where`], `deleted` objects being already deleted
+    async fn delete_page(
+        &self,
+        operations: &[BatchOperation],
+        deleted: usize,
+    ) -> Result<usize, ParseError> {
+        let results = match self.batch(operations).await {
+            Ok(results) => results,
+            Err(failure) if deleted + failure.applied() == 0 => return Err(failure.error),
+            Err(failure) => {
+                return Err(ParseError::Batch {
+                    applied: deleted + failure.applied(),
+                    code: OTHER_CAUSE,
+                    cause: failure.error.to_string(),
                 })
             }
+        };
+        if let Some(Err(error)) = results.iter().find(|result| result.is_err()) {
+            return Err(ParseError::Batch {
+                applied: deleted + results.iter().filter(|result| result.is_ok()).count(),
+                code: error.code,
+                cause: error.error.clone(),
+            });
         }
+        Ok(results.len())
     }
 }
 
+/// The safety limits of [`ParseClient::delete_where`]
+#[derive(Clone, Debug)]
+pub struct DeleteOptions {
+    /// The token of [`ParseClient::delete_confirmation`]
+    pub confirm: String,
+    /// The deletion is refused when more objects than this match
+    pub max_count: usize,
+}
+
+/// The objects a [`ParseClient::delete_where`] would delete
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeleteConfirmation {
+    /// The number of matching objects
+    pub count: u64,
+    /// Confirms the deletion of these objects only: another query or count is refused
+    pub token: String,
+}
+
+/// Returns the confirmation token of the deletion of the `count` objects matching a query
+fn delete_token(path: &str, query: &serde_json::Value, count: u64) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(format!("{path}\n{query}\n{count}"));
+    let hash: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{count}-{hash}")
+}
+
+/// Builds the HTTP client of a [`ParseClient`]
+fn http_client(connect_timeout: Option<Duration>, timeout: Option<Duration>) -> Client {
+    let mut builder = Client::builder();
+    if let Some(timeout) = connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder
+        .build()
+        .expect("the TLS backend of reqwest cannot be initialized")
+}
+
+/// Reads a duration in milliseconds from the environment
+fn env_millis(name: &str) -> Option<Duration> {
+    let value = env::var(name).ok()?;
+    let millis = value
+        .parse()
+        .unwrap_or_else(|_| panic!("env.{name} is not a number of milliseconds: {value:?}"));
+    Some(Duration::from_millis(millis))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectIdOnly {
+    object_id: String,
+}
+
+#[derive(Deserialize)]
+struct SerialOnly {
+    serial: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock;
+    use serde_json::json;
     use std::env;
 
     fn get_env() -> Vec<&'static str> {
@@ -223,17 +1080,334 @@ mod tests {
         fill_env(vars.clone());
 
         let client = ParseClient::from_env();
-        let formated = client.get_url("status".to_string());
+        let formated = client.endpoint().url("status");
         assert!(formated == *"PARSE_SERVER_URL/status");
     }
 
     #[test]
-    fn get_client() {
-        let vars = get_env();
-        fill_env(vars.clone());
+    fn global() {
+        let client = ParseClient::new("app".to_string(), None, "url".to_string());
+        assert!(ParseClient::initialize(client).is_ok());
+        assert_eq!(ParseClient::get().unwrap().application_id, "app");
+        assert_eq!(ParseClient::global().server_url, "url");
+        let other = ParseClient::new("other".to_string(), None, "url".to_string());
+        assert!(ParseClient::initialize(other).is_err());
+    }
+
+    #[test]
+    fn field_name() {
+        assert_eq!(parse_field_name("nom"), "nom");
+        assert_eq!(parse_field_name("r#type"), "type");
+        assert_eq!(parse_field_name("nom_scientifique"), "nomScientifique");
+        assert_eq!(parse_field_name("sous_zone_code"), "sousZoneCode");
+        assert_eq!(parse_field_name("object_id"), "objectId");
+    }
+
+    #[tokio::test]
+    async fn get_client() {
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], "{}"),
+            mock::response("200 OK", &[], "{}"),
+        ]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_master_key("master".to_string());
+        // The clones send their own credentials through the same HTTP client
+        let master = client.as_master();
+        client.delete("classes/Esl/a".to_string()).await.unwrap();
+        master.delete("classes/Esl/b".to_string()).await.unwrap();
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("x-parse-master-key"));
+        assert!(requests[1].contains("x-parse-master-key: master"));
+        assert!(requests[1].contains("x-parse-application-id: app"));
+    }
+
+    #[test]
+    fn master_key() {
+        let client = ParseClient::new("app".to_string(), None, "http://h".to_string())
+            .with_master_key("master".to_string());
+        assert!(!client
+            .protocol()
+            .headers()
+            .contains_key("X-Parse-Master-Key"));
+        let master = client.as_master();
+        assert_eq!(master.protocol().headers()["X-Parse-Master-Key"], "master");
+        assert!(!format!("{:?}", master.protocol().headers()).contains("\"master\""));
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        // Accepts the connection and never answers
+        std::thread::spawn(move || {
+            let _connection = listener.accept();
+            std::thread::sleep(Duration::from_secs(5));
+        });
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_connect_timeout(Duration::from_secs(1))
+            .with_timeout(Duration::from_millis(100));
+        let started = Instant::now();
+        let result = client.delete("classes/Esl/abc".to_string()).await;
+        assert!(matches!(result, Err(ParseError::Reqwest { source }) if source.is_timeout()));
+        assert!(started.elapsed() < Duration::from_secs(2));
 
-        let parse = ParseClient::from_env();
-        let client = parse.get_client();
-        assert!(client.is_ok());
+        env::set_var("PARSE_TEST_TIMEOUT_MS", "1500");
+        assert_eq!(
+            env_millis("PARSE_TEST_TIMEOUT_MS"),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(env_millis("PARSE_TEST_UNSET_TIMEOUT_MS"), None);
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        mock::runtime();
+        let unavailable = mock::response(
+            "503 Service Unavailable",
+            &[],
+            r#"{"code":1,"error":"down"}"#,
+        );
+        let (url, server) = mock::serve(vec![
+            unavailable.clone(),
+            mock::response(
+                "201 Created",
+                &[],
+                r#"{"createdAt":"now","objectId":"abc"}"#,
+            ),
+            unavailable.clone(),
+            unavailable,
+            mock::response(
+                "404 Not Found",
+                &[],
+                r#"{"code":101,"error":"Object not found."}"#,
+            ),
+        ]);
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let client = ParseClient::new("app".to_string(), None, url).with_retry_policy(policy);
+        let created = client
+            .save("classes/Esl".to_string(), json!({ "eslId": "e1" }))
+            .await
+            .unwrap();
+        assert_eq!(created.object_id, "abc");
+        let update = client
+            .update("classes/Esl/abc".to_string(), json!({}))
+            .await;
+        assert!(
+            matches!(update, Err(ParseError::Platform { code, .. }) if code == StatusCode::SERVICE_UNAVAILABLE)
+        );
+        let delete = client.delete("classes/Esl/abc".to_string()).await;
+        assert!(
+            matches!(delete, Err(ParseError::Platform { code, .. }) if code == StatusCode::NOT_FOUND)
+        );
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /classes/Esl"));
+        assert_eq!(requests[0], requests[1]);
+        assert!(requests[4].starts_with("DELETE /classes/Esl/abc"));
+    }
+
+    #[tokio::test]
+    async fn retry_throttled() {
+        mock::runtime();
+        let (url, server) = mock::serve(vec![
+            mock::response("429 Too Many Requests", &["Retry-After: 0"], "{}"),
+            mock::response(
+                "201 Created",
+                &[],
+                r#"{"createdAt":"now","objectId":"abc"}"#,
+            ),
+            mock::response("429 Too Many Requests", &["Retry-After: 3600"], "{}"),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_retry_policy(RetryPolicy::default());
+        let created = client
+            .save("classes/Esl".to_string(), json!({ "eslId": "e1" }))
+            .await
+            .unwrap();
+        assert_eq!(created.object_id, "abc");
+        let throttled = client
+            .get_object::<serde_json::Value>("classes/Esl".to_string(), "abc")
+            .await;
+        assert!(
+            matches!(throttled, Err(ParseError::Platform { code, .. }) if code == StatusCode::TOO_MANY_REQUESTS)
+        );
+        let requests = server.join().unwrap();
+        let request_id = |request: &str| {
+            request
+                .lines()
+                .find_map(|line| line.strip_prefix("x-parse-request-id: "))
+                .map(str::to_string)
+        };
+        assert!(request_id(&requests[0]).is_some());
+        assert_eq!(request_id(&requests[0]), request_id(&requests[1]));
+        assert_eq!(request_id(&requests[2]), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_etag() {
+        let body = r#"{"results":[{"createdAt":"2023-05-26T08:00:00.000Z","objectId":"abc"}]}"#;
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &["ETag: \"v1\""], body),
+            mock::response("304 Not Modified", &[], ""),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url).with_etag_cache();
+        for _ in 0..2 {
+            let results: Vec<ParseCreated> = client
+                .fetch("classes/Esl".to_string(), json!({ "serial": "abc" }))
+                .await
+                .unwrap();
+            assert_eq!(results[0].object_id, "abc");
+        }
+        let requests = server.join().unwrap();
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1].to_lowercase().contains("if-none-match: \"v1\""));
+    }
+
+    #[test]
+    fn etag_cache_eviction() {
+        let mut cache = EtagCache::default();
+        for i in 0..ETAG_CACHE_ENTRIES {
+            cache.insert(format!("url{i}"), "v1".to_string(), String::new());
+        }
+        assert!(cache.get("url0").is_some());
+        cache.insert("new".to_string(), "v1".to_string(), String::new());
+        assert_eq!(cache.responses.len(), ETAG_CACHE_ENTRIES);
+        assert!(cache.get("url0").is_some());
+        assert!(cache.get("url1").is_none());
+        assert!(cache.get("new").is_some());
+    }
+
+    #[tokio::test]
+    async fn schema_validation() {
+        let body =
+            r#"{"results":[{"createdAt":"now","objectId":"a"},{"createdAt":3,"objectId":"b"}]}"#;
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], body),
+            mock::response("200 OK", &[], body),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let result: Result<Vec<ParseCreated>, _> =
+            client.fetch("classes/Esl".to_string(), json!({})).await;
+        assert!(matches!(result, Err(ParseError::SerdeJson { .. })));
+        let result: Result<Vec<ParseCreated>, _> = client
+            .with_schema_validation()
+            .fetch("classes/Esl".to_string(), json!({}))
+            .await;
+        match result {
+            Err(ParseError::Schema {
+                object_id,
+                field,
+                cause,
+            }) => {
+                assert_eq!(object_id, "b");
+                assert_eq!(field, "createdAt");
+                assert!(cause.contains("expected a string"), "{cause}");
+            }
+            _ => panic!("expected a schema error"),
+        }
+        server.join().unwrap();
+    }
+
+    fn count(count: u64) -> String {
+        mock::response(
+            "200 OK",
+            &[],
+            &format!(r#"{{"results":[],"count":{count}}}"#),
+        )
+    }
+
+    #[tokio::test]
+    async fn delete_where() {
+        let (url, server) = mock::serve(vec![
+            count(2),
+            count(2),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"a"},{"objectId":"b"}]}"#,
+            ),
+            mock::response("200 OK", &[], r#"[{"success":{}},{"success":{}}]"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, format!("{url}/parse"));
+        let query = json!({ "serial": "test" });
+        let confirmation = client
+            .delete_confirmation("classes/Esl".to_string(), &query)
+            .await
+            .unwrap();
+        assert_eq!(confirmation.count, 2);
+        let options = DeleteOptions {
+            confirm: confirmation.token,
+            max_count: 2,
+        };
+        let deleted = client
+            .delete_where("classes/Esl".to_string(), &query, options)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("count=1"));
+        assert!(requests[2].contains("keys=objectId"));
+        assert!(requests[2].contains("order=objectId"));
+        assert!(requests[3].starts_with("POST /parse/batch"));
+        assert!(requests[3].contains(r#""path":"/parse/classes/Esl/b""#));
+    }
+
+    #[tokio::test]
+    async fn delete_where_partial() {
+        let (url, server) = mock::serve(vec![
+            count(2),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"a"},{"objectId":"b"}]}"#,
+            ),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"[{"success":{}},{"error":{"code":119,"error":"Permission denied"}}]"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let options = DeleteOptions {
+            confirm: delete_token("classes/Esl", &json!({}), 2),
+            max_count: 2,
+        };
+        let result = client
+            .delete_where("classes/Esl".to_string(), json!({}), options)
+            .await;
+        match result {
+            Err(ParseError::Batch { applied, code, .. }) => assert_eq!((applied, code), (1, 119)),
+            _ => panic!("expected a batch error"),
+        }
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_where_limits() {
+        let (url, server) = mock::serve(vec![count(2), count(2), count(3)]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let options = DeleteOptions {
+            confirm: delete_token("classes/Esl", &json!({}), 2),
+            max_count: 1,
+        };
+        let result = client
+            .delete_where("classes/Esl".to_string(), json!({}), options.clone())
+            .await;
+        assert!(matches!(result, Err(ParseError::Query { .. })));
+        let options = DeleteOptions {
+            max_count: 10,
+            ..options
+        };
+        // Another class, then more objects than confirmed
+        for path in ["classes/Store", "classes/Esl"] {
+            let result = client
+                .delete_where(path.to_string(), json!({}), options.clone())
+                .await;
+            assert!(matches!(result, Err(ParseError::Query { .. })));
+        }
+        assert_eq!(server.join().unwrap().len(), 3);
     }
 }
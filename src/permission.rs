@@ -0,0 +1,245 @@
+use crate::acl::Acl;
+use crate::parse::{ParseClient, ParseError};
+use crate::pointer::Pointer;
+use crate::role::{ParseRole, ROLE_PATH};
+use crate::store::STORE_CLASS;
+use crate::user::ParseUser;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+
+/// How deep role inheritance is followed, in case of a cycle
+const MAX_ROLE_DEPTH: usize = 10;
+
+/// The ACL of a store of the registry
+#[derive(Deserialize)]
+struct StoreAcl {
+    #[serde(rename = "ACL")]
+    acl: Option<Acl>,
+}
+
+/// Returns true if the credentials of a client can modify the store of a serial.
+///
+/// The master key can modify anything, and a store without an ACL can be modified with the
+/// REST key. Otherwise the ACL of the store must give write access to the public, to the user
+/// of the session or to one of their roles, inherited ones included. Class-level permissions
+/// are not checked.
+pub async fn can_write(client: &ParseClient, serial: &str) -> Result<bool, ParseError> {
+    if client.uses_master_key() {
+        return Ok(true);
+    }
+    let stores: Vec<StoreAcl> = client
+        .fetch(STORE_CLASS.to_string(), json!({ "serial": serial }))
+        .await?;
+    let store = stores
+        .into_iter()
+        .next()
+        .ok_or_else(|| ParseError::Identity {
+            serial: serial.to_string(),
+            cause: "this serial is not in the store registry or cannot be read".to_string(),
+        })?;
+    let Some(acl) = store.acl else {
+        return Ok(true);
+    };
+    if acl.public_permission().write {
+        return Ok(true);
+    }
+    if client.session_token().is_none() {
+        return Ok(false);
+    }
+    let user = ParseUser::me(client).await?;
+    let Some(user_id) = user.object_id else {
+        return Ok(false);
+    };
+    if acl.user_permission(&user_id).write {
+        return Ok(true);
+    }
+    let mut seen = HashSet::new();
+    let mut roles = ParseRole::of_user(client, &user_id).await?;
+    for _ in 0..MAX_ROLE_DEPTH {
+        roles.retain(|role| seen.insert(role.object_id.clone()));
+        if roles.is_empty() {
+            break;
+        }
+        if roles
+            .iter()
+            .any(|role| acl.role_permission(&role.name).write)
+        {
+            return Ok(true);
+        }
+        // The users of a child role get the permissions of its parents
+        let children: Vec<Pointer<ParseRole>> = roles
+            .iter()
+            .filter_map(|role| Some(Pointer::new(role.object_id.as_ref()?)))
+            .collect();
+        roles = client
+            .fetch(
+                ROLE_PATH.to_string(),
+                json!({ "roles": { "$in": children } }),
+            )
+            .await?;
+    }
+    Ok(false)
+}
+
+/// Fails with [`ParseError::Forbidden`] when the credentials of a client cannot modify the
+/// store of a serial, see [`can_write`]
+pub async fn ensure_write(client: &ParseClient, serial: &str) -> Result<(), ParseError> {
+    if can_write(client, serial).await? {
+        Ok(())
+    } else {
+        Err(ParseError::Forbidden {
+            serial: serial.to_string(),
+            cause: "the ACL of the store does not grant write access".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{BatchFailure, BatchOperation, BATCH_SIZE};
+    use crate::mock;
+
+    fn store(acl: &str) -> String {
+        mock::response(
+            "200 OK",
+            &[],
+            &format!(
+                r#"{{"results":[{{"objectId":"st1","serial":"s1","name":"Rungis","ACL":{acl}}}]}}"#
+            ),
+        )
+    }
+
+    const ME: &str = r#"{"objectId":"u1","username":"marie"}"#;
+
+    #[tokio::test]
+    async fn inherited_role() {
+        let (url, server) = mock::serve(vec![
+            store(r#"{"*":{"read":true},"role:manager":{"read":true,"write":true}}"#),
+            mock::response("200 OK", &[], ME),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"r1","name":"fishmonger","ACL":{}}]}"#,
+            ),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"r2","name":"manager","ACL":{}}]}"#,
+            ),
+        ]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_session_token("t".to_string());
+        assert!(can_write(&client, "s1").await.unwrap());
+        let requests = server.join().unwrap();
+        assert!(requests[3].contains("%22roles%22%3A%7B%22%24in%22"));
+        assert!(requests[3].contains("%22objectId%22%3A%22r1%22"));
+
+        let master = client.with_master_key("master".to_string()).as_master();
+        assert!(can_write(&master, "s1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn enforcement() {
+        let (url, server) = mock::serve(vec![
+            store(r#"{"*":{"read":true}}"#),
+            mock::response("200 OK", &[], ME),
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_session_token("t".to_string())
+            .with_write_checks();
+        let operations =
+            vec![
+                BatchOperation::create("classes/Esl".to_string(), &json!({ "serial": "s1" }))
+                    .unwrap(),
+            ];
+        match client.batch(&operations).await {
+            Err(BatchFailure {
+                error: ParseError::Forbidden { serial, .. },
+                ..
+            }) => assert_eq!(serial, "s1"),
+            _ => panic!("expected a forbidden error"),
+        }
+        // The decision is cached, nothing is sent this time
+        assert!(matches!(
+            client
+                .save("classes/Esl".to_string(), json!({ "serial": "s1" }))
+                .await,
+            Err(ParseError::Forbidden { .. })
+        ));
+        // The master key is not bound by the decision made for the session
+        let master = client
+            .clone()
+            .with_master_key("master".to_string())
+            .as_master();
+        master
+            .update("classes/Esl/e1".to_string(), json!({ "serial": "s1" }))
+            .await
+            .unwrap();
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[3].starts_with("PUT /classes/Esl/e1"));
+    }
+
+    #[tokio::test]
+    async fn enforcement_without_serial() {
+        let stored = || {
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"e1","serial":"s1"}]}"#,
+            )
+        };
+        let (url, server) = mock::serve(vec![
+            stored(),
+            store(r#"{"*":{"read":true}}"#),
+            mock::response("200 OK", &[], ME),
+            mock::response("200 OK", &[], r#"{"results":[]}"#),
+            stored(),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_session_token("t".to_string())
+            .with_write_checks();
+        assert!(matches!(
+            client
+                .update("classes/Esl/e1".to_string(), json!({ "printed": true }))
+                .await,
+            Err(ParseError::Forbidden { serial, .. }) if serial == "s1"
+        ));
+        assert!(matches!(
+            client.delete("classes/Esl/e1".to_string()).await,
+            Err(ParseError::Forbidden { .. })
+        ));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /classes/Esl?"));
+        assert!(requests[0].contains("keys=serial"));
+        assert!(requests[0].contains("%22objectId%22%3A%7B%22%24in%22%3A%5B%22e1%22%5D"));
+        assert!(requests[4].starts_with("GET /classes/Esl?"));
+    }
+
+    #[tokio::test]
+    async fn enforcement_chunks() {
+        let none = || mock::response("200 OK", &[], r#"{"results":[]}"#);
+        let applied = |count| {
+            let results = vec![r#"{"success":{}}"#; count].join(",");
+            mock::response("200 OK", &[], &format!("[{results}]"))
+        };
+        let (url, server) = mock::serve(vec![none(), none(), applied(BATCH_SIZE), applied(1)]);
+        let client = ParseClient::new("app".to_string(), None, url)
+            .with_session_token("t".to_string())
+            .with_write_checks();
+        let operations: Vec<BatchOperation> = (0..=BATCH_SIZE)
+            .map(|i| BatchOperation::delete(format!("classes/Esl/e{i}")))
+            .collect();
+        assert_eq!(
+            client.batch(&operations).await.unwrap().len(),
+            BATCH_SIZE + 1
+        );
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains(&format!("limit={BATCH_SIZE}")));
+        assert!(requests[1].contains("limit=1"));
+    }
+}
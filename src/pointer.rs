@@ -0,0 +1,261 @@
+use crate::parse::{ParseClient, ParseError};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A type stored in a Parse class, so that pointers to it know their `className`
+pub trait ParseClass {
+    /// The name of the class, such as `Store` or `_User`
+    const CLASS_NAME: &'static str;
+
+    /// Returns the path of the objects of this class on the Parse API
+    fn class_path() -> String {
+        match Self::CLASS_NAME {
+            "_User" => "users".to_string(),
+            "_Role" => "roles".to_string(),
+            "_Installation" => "installations".to_string(),
+            class_name => format!("classes/{class_name}"),
+        }
+    }
+}
+
+/// A reference to an object of another class, such as the `store` of a gateway.
+///
+/// It serializes to `{"__type": "Pointer", "className": ..., "objectId": ...}`. A pointer
+/// expanded with [`FetchOptions::include`](crate::fetch::FetchOptions::include) deserializes
+/// too, keeping only its objectId: deserialize the column into `T` to keep the whole object.
+pub struct Pointer<T> {
+    pub object_id: String,
+    class: PhantomData<T>,
+}
+
+impl<T: ParseClass> Pointer<T> {
+    pub fn new(object_id: &str) -> Self {
+        Self {
+            object_id: object_id.to_string(),
+            class: PhantomData,
+        }
+    }
+
+    /// Reads the object this pointer references
+    pub async fn fetch(&self, client: &ParseClient) -> Result<T, ParseError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        client.get_object(T::class_path(), &self.object_id).await
+    }
+}
+
+// Not derived, they would require T to implement them
+impl<T> Clone for Pointer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            object_id: self.object_id.clone(),
+            class: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Pointer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.object_id == other.object_id
+    }
+}
+
+impl<T> Eq for Pointer<T> {}
+
+impl<T: ParseClass> fmt::Debug for Pointer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pointer<{}>({})", T::CLASS_NAME, self.object_id)
+    }
+}
+
+impl<T: ParseClass> Serialize for Pointer<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        json!({ "__type": "Pointer", "className": T::CLASS_NAME, "objectId": self.object_id })
+            .serialize(serializer)
+    }
+}
+
+/// The fields of a pointer, or of an included object
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPointer {
+    #[serde(rename = "__type")]
+    r#type: String,
+    class_name: String,
+    #[serde(default)]
+    object_id: Option<String>,
+}
+
+impl RawPointer {
+    fn check<E: de::Error>(&self, types: &[&str], class_name: &str) -> Result<(), E> {
+        if !types.contains(&self.r#type.as_str()) {
+            return Err(E::custom(format!(
+                "expected a {}, got a {}",
+                types[0], self.r#type
+            )));
+        }
+        if self.class_name != class_name {
+            return Err(E::custom(format!(
+                "expected a {} of {class_name}, got one of {}",
+                types[0], self.class_name
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<'de, T: ParseClass> Deserialize<'de> for Pointer<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawPointer::deserialize(deserializer)?;
+        raw.check(&["Pointer", "Object"], T::CLASS_NAME)?;
+        let object_id = raw
+            .object_id
+            .ok_or_else(|| de::Error::missing_field("objectId"))?;
+        Ok(Pointer::new(&object_id))
+    }
+}
+
+/// A many-to-many relation column, such as the users of a role.
+///
+/// Parse returns the column without its objects: they are fetched with
+/// [`WhereClause::related_to`](crate::query::WhereClause::related_to) and modified with
+/// [`Relation::add`] and [`Relation::remove`].
+pub struct Relation<T> {
+    class: PhantomData<T>,
+}
+
+impl<T: ParseClass> Relation<T> {
+    pub fn new() -> Self {
+        Self { class: PhantomData }
+    }
+
+    /// The update adding objects to a relation, such as
+    /// `client.update(path, json!({ "users": Relation::add(&users) }))`
+    pub fn add(objects: &[Pointer<T>]) -> Value {
+        json!({ "__op": "AddRelation", "objects": objects })
+    }
+
+    pub fn remove(objects: &[Pointer<T>]) -> Value {
+        json!({ "__op": "RemoveRelation", "objects": objects })
+    }
+}
+
+impl<T: ParseClass> Default for Relation<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for Relation<T> {
+    fn clone(&self) -> Self {
+        Self { class: PhantomData }
+    }
+}
+
+impl<T> PartialEq for Relation<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T: ParseClass> fmt::Debug for Relation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Relation<{}>", T::CLASS_NAME)
+    }
+}
+
+impl<T: ParseClass> Serialize for Relation<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        json!({ "__type": "Relation", "className": T::CLASS_NAME }).serialize(serializer)
+    }
+}
+
+impl<'de, T: ParseClass> Deserialize<'de> for Relation<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawPointer::deserialize(deserializer)?;
+        raw.check(&["Relation"], T::CLASS_NAME)?;
+        Ok(Relation::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::query::WhereClause;
+    use crate::role::ParseRole;
+    use crate::store::Store;
+    use crate::user::ParseUser;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct StoreStaff {
+        store: Pointer<Store>,
+        staff: Relation<ParseUser>,
+    }
+
+    #[test]
+    fn serde() {
+        let staff = StoreStaff {
+            store: Pointer::new("st1"),
+            staff: Relation::new(),
+        };
+        let value = serde_json::to_value(&staff).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "store": { "__type": "Pointer", "className": "Store", "objectId": "st1" },
+                "staff": { "__type": "Relation", "className": "_User" }
+            })
+        );
+        assert_eq!(serde_json::from_value::<StoreStaff>(value).unwrap(), staff);
+
+        let included = json!({ "__type": "Object", "className": "Store", "objectId": "st1", "name": "Rungis" });
+        assert_eq!(
+            serde_json::from_value::<Pointer<Store>>(included).unwrap(),
+            Pointer::new("st1")
+        );
+        let role = json!({ "__type": "Pointer", "className": "_Role", "objectId": "r1" });
+        assert!(serde_json::from_value::<Pointer<Store>>(role).is_err());
+        assert_eq!(
+            Relation::add(&[Pointer::<ParseRole>::new("r1")]),
+            json!({ "__op": "AddRelation", "objects": [{ "__type": "Pointer", "className": "_Role", "objectId": "r1" }] })
+        );
+    }
+
+    #[tokio::test]
+    async fn related_to() {
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"u1","username":"marie"}]}"#,
+            ),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"objectId":"st1","serial":"s1","name":"Rungis"}"#,
+            ),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let role = Pointer::<ParseRole>::new("r1");
+        let users: Vec<ParseUser> = client
+            .fetch_where(
+                ParseUser::class_path(),
+                &WhereClause::related_to(&role, "users"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(users[0].username, "marie");
+        let store = Pointer::<Store>::new("st1").fetch(&client).await.unwrap();
+        assert_eq!(store.name, "Rungis");
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /users?where=%7B%22%24relatedTo%22"));
+        assert!(requests[0].contains("%22key%22%3A%22users%22"));
+        assert!(requests[1].starts_with("GET /classes/Store/st1"));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::csv::{field, Csv};
+use crate::generic_esl::GenericEsl;
+use crate::parse::ParseError;
+use crate::price;
+#[cfg(feature = "postgres")]
+use bb8::Pool;
+#[cfg(feature = "postgres")]
+use bb8_postgres::PostgresConnectionManager;
+use std::collections::HashMap;
+#[cfg(feature = "postgres")]
+use tokio_postgres::NoTls;
+
+/// A label displaying a price different from the POS price
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceMismatch {
+    pub esl: GenericEsl,
+    /// The POS price in cents
+    pub pos_cents: i64,
+}
+
+impl PriceMismatch {
+    /// Returns the POS price formatted like the displayed price
+    pub fn pos_prix(&self) -> String {
+        price::format_cents(self.pos_cents, &self.esl.prix)
+    }
+}
+
+/// The result of a cross-check between the labels of a store and its POS prices
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PosReport {
+    pub mismatches: Vec<PriceMismatch>,
+    /// Labels whose PLU is not in the POS export
+    pub missing_in_pos: Vec<GenericEsl>,
+    /// Labels whose displayed price cannot be read
+    pub invalid_prices: Vec<GenericEsl>,
+    /// The number of labels displaying the POS price
+    pub matching: usize,
+}
+
+impl PosReport {
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty()
+            && self.missing_in_pos.is_empty()
+            && self.invalid_prices.is_empty()
+    }
+}
+
+/// Parses a POS price export with a `plu;prix` header, returning the prices in cents by PLU.
+///
+/// In a `,` separated export the prices with a decimal comma must be quoted, such as
+/// `1234,"18,90"`: a row with an extra field is refused.
+pub fn parse_csv(csv: &str) -> Result<HashMap<String, i64>, ParseError> {
+    let csv = Csv::parse(csv)?;
+    let (plu, prix) = (csv.column("plu")?, csv.column("prix")?);
+    csv.rows
+        .iter()
+        .map(|(line, row)| {
+            let error = |cause: String| ParseError::Import { line: *line, cause };
+            let plu = field(row, plu);
+            if plu.is_empty() {
+                return Err(error("the PLU is empty".to_string()));
+            }
+            let prix = field(row, prix);
+            let cents =
+                price::parse_cents(prix).ok_or_else(|| error(format!("invalid price {prix:?}")))?;
+            Ok((plu.to_string(), cents))
+        })
+        .collect()
+}
+
+/// Compares the price displayed by each label with the POS price of its PLU
+pub fn cross_check(esls: Vec<GenericEsl>, pos_prices: &HashMap<String, i64>) -> PosReport {
+    let mut report = PosReport::default();
+    for esl in esls {
+        match (pos_prices.get(&esl.plu), price::parse_cents(&esl.prix)) {
+            (None, _) => report.missing_in_pos.push(esl),
+            (Some(_), None) => report.invalid_prices.push(esl),
+            (Some(&pos_cents), Some(cents)) if pos_cents != cents => {
+                report.mismatches.push(PriceMismatch { esl, pos_cents })
+            }
+            (Some(_), Some(_)) => report.matching += 1,
+        }
+    }
+    report
+}
+
+/// Cross-checks every label of a serial against the POS prices
+#[cfg(feature = "postgres")]
+pub async fn verify(
+    serial: &str,
+    pos_prices: &HashMap<String, i64>,
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+) -> Result<PosReport, ParseError> {
+    let esls = GenericEsl::find_by_serial(serial, pool).await?;
+    Ok(cross_check(esls, pos_prices))
+}
+
+/// Displays the POS price on every mismatching label of a report.
+///
+/// Returns the corrected labels, they are marked as not printed so the POS price is pushed.
+#[cfg(feature = "postgres")]
+pub async fn correct(
+    report: &PosReport,
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+) -> Result<Vec<GenericEsl>, ParseError> {
+    let mut corrected = Vec::with_capacity(report.mismatches.len());
+    for mismatch in &report.mismatches {
+        let esl =
+            GenericEsl::set_prix(mismatch.esl.clone(), mismatch.pos_prix(), pool.clone()).await?;
+        corrected.push(esl);
+    }
+    Ok(corrected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn esl(plu: &str, prix: &str) -> GenericEsl {
+        GenericEsl {
+            plu: plu.to_string(),
+            prix: prix.to_string(),
+            ..mock::esl()
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let prices = parse_csv("PLU;Prix\n1234;18,90\n5678;7.5\n").unwrap();
+        assert_eq!(prices["1234"], 1890);
+        assert_eq!(prices["5678"], 750);
+        assert!(matches!(
+            parse_csv("plu;prix\n1;gratuit"),
+            Err(ParseError::Import { line: 2, .. })
+        ));
+
+        // An unquoted decimal comma would read 18,00
+        assert!(matches!(
+            parse_csv("plu,prix\n1234,18,90\n"),
+            Err(ParseError::Import { line: 2, .. })
+        ));
+        let prices = parse_csv("plu,prix\n1234,\"18,90\"\n").unwrap();
+        assert_eq!(prices["1234"], 1890);
+    }
+
+    #[test]
+    fn check() {
+        let prices = parse_csv("plu;prix\n1;18,90\n2;12,00\n3;5,00").unwrap();
+        let report = cross_check(
+            vec![
+                esl("1", "18,90"),
+                esl("2", "12,50"),
+                esl("3", "?"),
+                esl("4", "3,00"),
+            ],
+            &prices,
+        );
+        assert!(!report.is_clean());
+        assert_eq!(report.matching, 1);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].esl.plu, "2");
+        assert_eq!(report.mismatches[0].pos_prix(), "12,00");
+        assert_eq!(report.invalid_prices[0].plu, "3");
+        assert_eq!(report.missing_in_pos[0].plu, "4");
+        assert!(cross_check(vec![esl("1", "18.9")], &prices).is_clean());
+    }
+}
@@ -0,0 +1,24 @@
+pub use crate::acl::{Acl, WithAcl};
+pub use crate::campaign::{Campaign, CampaignStatus};
+pub use crate::endpoint::ServerEndpoint;
+pub use crate::error::EslError;
+pub use crate::fetch::{FetchOptions, Order};
+pub use crate::file::ParseFile;
+pub use crate::gateway::{Gateway, GatewayCredentials};
+pub use crate::generic_esl::{EslType, GenericEsl};
+pub use crate::geo::GeoPoint;
+pub use crate::installation::ParseInstallation;
+pub use crate::latency::LatencyBudget;
+pub use crate::location::Location;
+pub use crate::masking::MaskingProfile;
+pub use crate::parse::{
+    DeleteConfirmation, DeleteOptions, ParseClient, ParseCreated, ParseError, ParseObject,
+};
+pub use crate::pointer::{ParseClass, Pointer, Relation};
+pub use crate::price_zone::PriceZone;
+pub use crate::provenance::ProvenanceLinks;
+pub use crate::query::{Constraint, Query, WhereClause};
+pub use crate::role::ParseRole;
+pub use crate::store::{GatewayIdentity, Store};
+pub use crate::user::ParseUser;
+pub use crate::vendor::VendorError;
@@ -0,0 +1,76 @@
+/// Parses a displayed price (`"18,90"`, `"18.90 €"`) into cents.
+///
+/// Both `,` and `.` are accepted as decimal separators and a trailing `€` is ignored. Prices are
+/// converted to cents before any computation so no floating point rounding ends up on a label.
+pub fn parse_cents(prix: &str) -> Option<i64> {
+    let prix = prix.trim().trim_end_matches('€').trim();
+    let (units, decimals) = match prix.find([',', '.']) {
+        Some(index) => (&prix[..index], &prix[index + 1..]),
+        None => (prix, ""),
+    };
+    if units.is_empty()
+        || decimals.len() > 2
+        || !units.chars().all(|c| c.is_ascii_digit())
+        || !decimals.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let units: i64 = units.parse().ok()?;
+    let decimals: i64 = match decimals.len() {
+        0 => 0,
+        1 => decimals.parse::<i64>().ok()? * 10,
+        _ => decimals.parse().ok()?,
+    };
+    Some(units * 100 + decimals)
+}
+
+/// Formats cents as a displayed price using the decimal separator and the unit of `like`.
+///
+/// `like` is usually the previous price of the Esl so the new price keeps the same format: the
+/// text after its last digit, such as ` €`, is kept.
+pub fn format_cents(cents: i64, like: &str) -> String {
+    let separator = if like.contains('.') { '.' } else { ',' };
+    let unit = like
+        .rfind(|c: char| c.is_ascii_digit())
+        .map_or("", |index| &like[index + 1..]);
+    format!("{}{}{:02}{}", cents / 100, separator, cents % 100, unit)
+}
+
+/// Applies a percentage discount to a displayed price, rounding to the nearest cent.
+pub fn discount(prix: &str, percent: f64) -> Option<String> {
+    let cents = parse_cents(prix)?;
+    let discounted = (cents as f64 * (1. - percent / 100.)).round() as i64;
+    Some(format_cents(discounted.max(0), prix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_cents("18,90"), Some(1890));
+        assert_eq!(parse_cents("18.9"), Some(1890));
+        assert_eq!(parse_cents(" 7 €"), Some(700));
+        assert_eq!(parse_cents("0,05"), Some(5));
+        assert_eq!(parse_cents(""), None);
+        assert_eq!(parse_cents("18,905"), None);
+        assert_eq!(parse_cents("dix"), None);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_cents(1890, "16,90"), "18,90");
+        assert_eq!(format_cents(1805, "16.90"), "18.05");
+        assert_eq!(format_cents(1890, "16,90 €"), "18,90 €");
+        assert_eq!(format_cents(1890, "16.90€"), "18.90€");
+    }
+
+    #[test]
+    fn apply_discount() {
+        assert_eq!(discount("18,90", 10.).as_deref(), Some("17,01"));
+        assert_eq!(discount("10.00", 50.).as_deref(), Some("5.00"));
+        assert_eq!(discount("10,00", 150.).as_deref(), Some("0,00"));
+        assert_eq!(discount("18,90 €", 10.).as_deref(), Some("17,01 €"));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::generic_esl::GenericEsl;
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// The Parse class holding the price zones
+pub const PRICE_ZONE_CLASS: &str = "classes/PriceZone";
+
+/// A group of stores sharing regional prices.
+///
+/// The base price of a product is the price of its Esl, a zone overrides it for the PLUs of
+/// `prices`. A zone of a single serial overrides the prices of one store.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceZone {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub name: String,
+    /// The serials of the stores of this zone
+    #[serde(default)]
+    pub serials: Vec<String>,
+    /// The prices of this zone by PLU, formatted like Esl prices
+    #[serde(default)]
+    pub prices: HashMap<String, String>,
+}
+
+/// The price to display on an Esl and where it comes from
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectivePrice {
+    pub prix: String,
+    /// The name of the zone overriding the base price
+    pub zone: Option<String>,
+}
+
+impl PriceZone {
+    pub fn new(name: String) -> Self {
+        Self {
+            object_id: None,
+            name,
+            serials: vec![],
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Returns the path of this zone on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", PRICE_ZONE_CLASS, object_id))
+    }
+}
+
+/// Returns the price to display on an Esl.
+///
+/// When several zones of its store override the price, the zone with the fewest stores wins so
+/// a store override beats a regional one, ties are broken by name.
+pub fn effective_price(esl: &GenericEsl, zones: &[PriceZone]) -> EffectivePrice {
+    zones
+        .iter()
+        .filter(|zone| zone.serials.contains(&esl.serial))
+        .filter_map(|zone| zone.prices.get(&esl.plu).map(|prix| (zone, prix)))
+        .min_by(|(a, _), (b, _)| (a.serials.len(), &a.name).cmp(&(b.serials.len(), &b.name)))
+        .map(|(zone, prix)| EffectivePrice {
+            prix: prix.clone(),
+            zone: Some(zone.name.clone()),
+        })
+        .unwrap_or_else(|| EffectivePrice {
+            prix: esl.prix.clone(),
+            zone: None,
+        })
+}
+
+/// Applies the zone prices to Esls before they are pushed
+pub fn apply(esls: Vec<GenericEsl>, zones: &[PriceZone]) -> Vec<GenericEsl> {
+    esls.into_iter()
+        .map(|esl| GenericEsl {
+            prix: effective_price(&esl, zones).prix,
+            ..esl
+        })
+        .collect()
+}
+
+impl ParseClass for PriceZone {
+    const CLASS_NAME: &'static str = "PriceZone";
+}
+
+impl ParseObject for PriceZone {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(PRICE_ZONE_CLASS.to_string(), self).await
+    }
+
+    /// Finds every zone a serial belongs to
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(PRICE_ZONE_CLASS.to_string(), json!({ "serials": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn zone(name: &str, serials: &[&str], prix: &str) -> PriceZone {
+        PriceZone {
+            serials: serials.iter().map(|s| s.to_string()).collect(),
+            prices: HashMap::from([("1234".to_string(), prix.to_string())]),
+            ..PriceZone::new(name.to_string())
+        }
+    }
+
+    #[test]
+    fn resolution() {
+        let esl = mock::esl();
+        assert_eq!(
+            effective_price(&esl, &[]),
+            EffectivePrice {
+                prix: "18,90".to_string(),
+                zone: None
+            }
+        );
+        let bretagne = zone("bretagne", &["serial", "other"], "17,90");
+        let store = zone("serial", &["serial"], "16,90");
+        let elsewhere = zone("paris", &["paris"], "21,90");
+        let price = effective_price(&esl, &[bretagne.clone(), store, elsewhere.clone()]);
+        assert_eq!(price.prix, "16,90");
+        assert_eq!(price.zone.as_deref(), Some("serial"));
+        assert_eq!(effective_price(&esl, &[elsewhere, bretagne]).prix, "17,90");
+        let other_plu = GenericEsl {
+            plu: "5678".to_string(),
+            ..mock::esl()
+        };
+        let zones = [zone("serial", &["serial"], "16,90")];
+        let esls = apply(vec![esl, other_plu], &zones);
+        assert_eq!(esls[0].prix, "16,90");
+        assert_eq!(esls[1].prix, "18,90");
+    }
+}
@@ -0,0 +1,484 @@
+use crate::currency::Currency;
+use crate::date;
+use crate::fetch::{FetchOptions, Order};
+use crate::generic_esl::GenericEsl;
+use crate::mentions::{Mention, DECONGELE_MENTION};
+use crate::parse::{ParseClient, ParseCreated, ParseError};
+use crate::pointer::ParseClass;
+use crate::rules::{RuleSet, RuleSets};
+use crate::runtime;
+use crate::store_config::StoreConfig;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+/// The Parse class holding the paper price tags to print
+pub const PRINT_JOB_CLASS: &str = "classes/PrintJob";
+
+/// A network printer speaking ESC/POS, usually on the raw port 9100
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Printer {
+    pub name: String,
+    /// The address of the printer, such as `10.0.0.5:9100`
+    pub addr: String,
+}
+
+impl Printer {
+    /// Reads a printer list such as `comptoir=10.0.0.5:9100,reserve=10.0.0.6:9100`
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, ParseError> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|printer| !printer.is_empty())
+            .map(|printer| match printer.split_once('=') {
+                Some((name, addr)) if !name.trim().is_empty() && !addr.trim().is_empty() => {
+                    Ok(Self {
+                        name: name.trim().to_string(),
+                        addr: addr.trim().to_string(),
+                    })
+                }
+                _ => Err(ParseError::Query {
+                    cause: format!("invalid printer {printer:?}, expected name=host:port"),
+                }),
+            })
+            .collect()
+    }
+
+    /// Returns the printers of a list answering on their address within `timeout`.
+    ///
+    /// Printers are configured statically, this finds out which ones are switched on.
+    pub async fn discover(
+        printers: &[Printer],
+        timeout: Duration,
+    ) -> Result<Vec<Printer>, ParseError> {
+        let runtime = runtime::get()?;
+        let probes = printers.iter().map(|printer| async move {
+            let connected = runtime::timeout(timeout, runtime.connect(&printer.addr)).await?;
+            Ok(matches!(connected, Some(Ok(_))).then(|| printer.clone()))
+        });
+        let discovered: Vec<Option<Printer>> = futures::future::join_all(probes)
+            .await
+            .into_iter()
+            .collect::<Result<_, ParseError>>()?;
+        Ok(discovered.into_iter().flatten().collect())
+    }
+
+    /// Sends raw ESC/POS data to the printer
+    pub async fn print(&self, data: &[u8], timeout: Duration) -> Result<(), ParseError> {
+        use futures::io::AsyncWriteExt;
+        let runtime = runtime::get()?;
+        let print = async {
+            let mut stream = runtime.connect(&self.addr).await?;
+            stream.write_all(data).await?;
+            stream.close().await
+        };
+        match runtime::timeout(timeout, print).await? {
+            Some(result) => Ok(result?),
+            None => Err(ParseError::Io {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!(
+                        "the printer {} did not answer within {timeout:?}",
+                        self.name
+                    ),
+                ),
+            }),
+        }
+    }
+}
+
+/// The state of a print job
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrintJobStatus {
+    /// Waiting for a printer, failed attempts included
+    Queued,
+    Printed,
+    /// Given up after `max_attempts` failed attempts, see [`requeue`]
+    Failed,
+    Cancelled,
+}
+
+/// Paper price tags to print for a store.
+///
+/// Jobs are queued in Parse so the printing station of the store picks them up, see
+/// [`process`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintJob {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub serial: String,
+    /// The printer to use, any available one when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub printer: Option<String>,
+    pub esls: Vec<GenericEsl>,
+    pub status: PrintJobStatus,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// The cause of the last failed attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(
+        default,
+        with = "crate::date::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub printed_at: Option<DateTime<Utc>>,
+}
+
+impl PrintJob {
+    /// A job printing one tag per label, given up after 3 failed attempts
+    pub fn new(serial: &str, esls: Vec<GenericEsl>) -> Self {
+        Self {
+            object_id: None,
+            serial: serial.to_string(),
+            printer: None,
+            esls,
+            status: PrintJobStatus::Queued,
+            attempts: 0,
+            max_attempts: 3,
+            error: None,
+            printed_at: None,
+        }
+    }
+
+    /// Prints this job on a given printer only
+    pub fn on(mut self, printer: &str) -> Self {
+        self.printer = Some(printer.to_string());
+        self
+    }
+
+    /// Returns the path of this job on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", PRINT_JOB_CLASS, object_id))
+    }
+
+    /// Returns the ESC/POS data printing the tags of this job, with the mentions required by
+    /// `rules` and the prices in `currency`
+    pub fn escpos(&self, rules: &RuleSet, currency: Currency) -> Vec<u8> {
+        self.esls
+            .iter()
+            .flat_map(|esl| ticket(esl, rules, currency))
+            .collect()
+    }
+
+    /// Saves the current state of this job
+    async fn save_state(&self, client: &ParseClient) -> Result<(), ParseError> {
+        client
+            .update(
+                self.path()?,
+                json!({
+                    "status": self.status,
+                    "attempts": self.attempts,
+                    "error": self.error,
+                    "printedAt": self.printed_at.as_ref().map(date::date),
+                }),
+            )
+            .await
+    }
+}
+
+impl ParseClass for PrintJob {
+    const CLASS_NAME: &'static str = "PrintJob";
+}
+
+/// Resets the printer
+const INIT: &[u8] = b"\x1b@";
+/// The WPC1252 code page, for the accents and the euro sign
+const CODE_PAGE: &[u8] = b"\x1bt\x10";
+const CENTER: &[u8] = b"\x1ba\x01";
+const NORMAL: &[u8] = b"\x1d!\x00";
+const DOUBLE: &[u8] = b"\x1d!\x11";
+const TRIPLE: &[u8] = b"\x1d!\x22";
+/// Feeds the paper then cuts it
+const CUT: &[u8] = b"\x1dVB\x03";
+
+/// Encodes text in WPC1252, the characters it lacks are printed as `?`
+fn cp1252(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '€' => 0x80,
+            '’' => 0x92,
+            'Œ' => 0x8c,
+            'œ' => 0x9c,
+            'Ÿ' => 0x9f,
+            '\n' => b'\n',
+            c if (' '..='~').contains(&c) || ('\u{a0}'..='\u{ff}').contains(&c) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Returns the line of a mention on a paper tag, none for the mentions printed on their own
+/// (name, scientific name and price) or missing from the label
+fn mention_line(esl: &GenericEsl, mention: Mention) -> Option<String> {
+    let with_code = |name: &Option<String>, code: &Option<String>, prefix: &str| {
+        let name = name.as_deref()?;
+        Some(match code.as_deref() {
+            Some(code) => format!("{name} ({prefix}{code})"),
+            None => name.to_string(),
+        })
+    };
+    match mention {
+        Mention::Production => esl.production.clone(),
+        Mention::Zone => with_code(&esl.zone, &esl.zone_code, "FAO "),
+        Mention::SousZone => with_code(&esl.sous_zone, &esl.sous_zone_code, ""),
+        Mention::Origine => esl.origine.clone(),
+        Mention::Engin => esl.engin.clone(),
+        Mention::Decongele => Some(DECONGELE_MENTION.to_string()),
+        Mention::Denomination | Mention::NomScientifique | Mention::Prix => None,
+    }
+}
+
+/// Returns the ESC/POS data of the paper tag of a label
+fn ticket(esl: &GenericEsl, rules: &RuleSet, currency: Currency) -> Vec<u8> {
+    let mut data = [INIT, CODE_PAGE, CENTER, DOUBLE].concat();
+    data.extend(cp1252(&format!("{}\n", esl.nom)));
+    data.extend(NORMAL);
+    if !esl.nom_scientifique.is_empty() {
+        data.extend(cp1252(&format!("{}\n", esl.nom_scientifique)));
+    }
+    data.extend(TRIPLE);
+    data.extend(cp1252(&format!("{} {}\n", esl.prix, currency.symbol())));
+    data.extend(NORMAL);
+    if !esl.infos_prix.is_empty() {
+        data.extend(cp1252(&format!("{}\n", esl.infos_prix)));
+    }
+    let mut mentions = rules.required_mentions(esl);
+    // The country of production is printed when known, required or not
+    if !mentions.contains(&Mention::Origine) {
+        mentions.push(Mention::Origine);
+    }
+    for line in mentions
+        .into_iter()
+        .filter_map(|mention| mention_line(esl, mention))
+        .filter(|line| !line.trim().is_empty())
+    {
+        data.extend(cp1252(&format!("{line}\n")));
+    }
+    data.extend(cp1252(&format!("PLU {}\n", esl.plu)));
+    data.extend(CUT);
+    data
+}
+
+/// Queues a job for the printing station of its store
+pub async fn enqueue(client: &ParseClient, job: &PrintJob) -> Result<ParseCreated, ParseError> {
+    client.save(PRINT_JOB_CLASS.to_string(), job).await
+}
+
+/// Returns the jobs of a store with a given status, the oldest first
+pub async fn jobs(
+    client: &ParseClient,
+    serial: &str,
+    status: PrintJobStatus,
+) -> Result<Vec<PrintJob>, ParseError> {
+    client
+        .fetch_with(
+            PRINT_JOB_CLASS.to_string(),
+            json!({ "serial": serial, "status": status }),
+            &FetchOptions::default().order(Order::asc("created_at")),
+        )
+        .await
+}
+
+/// Cancels a job which is not printed yet, a printed job cannot be cancelled
+pub async fn cancel(client: &ParseClient, job: &mut PrintJob) -> Result<(), ParseError> {
+    if job.status == PrintJobStatus::Printed {
+        return Err(ParseError::Query {
+            cause: format!("the job {:?} is already printed", job.object_id),
+        });
+    }
+    job.status = PrintJobStatus::Cancelled;
+    job.save_state(client).await
+}
+
+/// Queues a failed job again, with its attempts reset
+pub async fn requeue(client: &ParseClient, job: &mut PrintJob) -> Result<(), ParseError> {
+    job.status = PrintJobStatus::Queued;
+    job.attempts = 0;
+    job.error = None;
+    job.save_state(client).await
+}
+
+/// Prints the queued jobs of a store and records their status.
+///
+/// The tags carry the mentions required by the rule set of the store and its currency. Each job
+/// is printed on its printer or on the first printer of the list. A failed attempt is counted
+/// and the job stays queued for the next call, until `max_attempts` is reached and it is marked
+/// as failed. Returns the jobs processed, with their new status.
+pub async fn process(
+    client: &ParseClient,
+    config: &StoreConfig,
+    rules: &RuleSets,
+    printers: &[Printer],
+    timeout: Duration,
+    now: DateTime<Utc>,
+) -> Result<Vec<PrintJob>, ParseError> {
+    let rules = rules.for_store(Some(config))?;
+    let mut processed = vec![];
+    for mut job in jobs(client, &config.serial, PrintJobStatus::Queued).await? {
+        let printer = match &job.printer {
+            Some(name) => printers.iter().find(|printer| &printer.name == name),
+            None => printers.first(),
+        };
+        let result = match printer {
+            Some(printer) => {
+                let data = job.escpos(rules, config.currency());
+                printer.print(&data, timeout).await
+            }
+            None => Err(ParseError::Query {
+                cause: format!(
+                    "no printer {}",
+                    job.printer.as_deref().unwrap_or("configured")
+                ),
+            }),
+        };
+        job.attempts += 1;
+        match result {
+            Ok(()) => {
+                job.status = PrintJobStatus::Printed;
+                job.error = None;
+                job.printed_at = Some(now);
+            }
+            Err(e) => {
+                warn!("Could not print the job {:?}: {e}", job.object_id);
+                job.error = Some(e.to_string());
+                if job.attempts >= job.max_attempts {
+                    job.status = PrintJobStatus::Failed;
+                }
+            }
+        }
+        job.save_state(client).await?;
+        processed.push(job);
+    }
+    Ok(processed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use chrono::TimeZone;
+    use std::io::Read;
+
+    #[test]
+    fn printers() {
+        let printers =
+            Printer::parse_list("comptoir=10.0.0.5:9100, reserve=10.0.0.6:9100").unwrap();
+        assert_eq!(printers.len(), 2);
+        assert_eq!(printers[1].name, "reserve");
+        assert_eq!(printers[1].addr, "10.0.0.6:9100");
+        assert!(Printer::parse_list("comptoir").is_err());
+    }
+
+    #[tokio::test]
+    async fn print_queue() {
+        mock::runtime();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let printer = Printer {
+            name: "comptoir".to_string(),
+            addr: listener.local_addr().unwrap().to_string(),
+        };
+        let printed = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = vec![];
+            stream.read_to_end(&mut data).unwrap();
+            data
+        });
+        let mut queued = serde_json::to_value(PrintJob::new("serial", vec![mock::esl()])).unwrap();
+        queued["objectId"] = json!("j1");
+        let mut elsewhere = queued.clone();
+        elsewhere["objectId"] = json!("j2");
+        elsewhere["printer"] = json!("reserve");
+        elsewhere["attempts"] = json!(2);
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "200 OK",
+                &[],
+                &json!({ "results": [queued, elsewhere] }).to_string(),
+            ),
+            mock::response("200 OK", &[], "{}"),
+            mock::response("200 OK", &[], "{}"),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let now = Utc.with_ymd_and_hms(2023, 6, 1, 8, 0, 0).unwrap();
+        let config = StoreConfig::new("serial".to_string());
+        let printers = [printer];
+        let jobs = process(
+            &client,
+            &config,
+            &RuleSets::default(),
+            &printers,
+            Duration::from_secs(1),
+            now,
+        )
+        .await
+        .unwrap();
+        assert_eq!(jobs[0].status, PrintJobStatus::Printed);
+        assert_eq!(jobs[1].status, PrintJobStatus::Failed);
+        assert_eq!(
+            jobs[1].error.as_deref(),
+            Some("Invalid query: no printer reserve")
+        );
+
+        let data = printed.join().unwrap();
+        assert!(data.starts_with(INIT));
+        assert!(data.windows(8).any(|w| w == b"18,90 \x80\n"));
+        for line in [
+            &b"P\xeach\xe9\n"[..],
+            b"Atlantique Nord-Est (FAO 27)\n",
+            b"Mer du Nord (IV)\n",
+            b"Chaluts\n",
+        ] {
+            assert!(data.windows(line.len()).any(|w| w == line));
+        }
+        assert!(data.ends_with(CUT));
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("order=createdAt"));
+        assert!(requests[1].starts_with("PUT /classes/PrintJob/j1"));
+        assert!(requests[1].contains(r#""status":"printed""#));
+        assert!(requests[1]
+            .contains(r#""printedAt":{"__type":"Date","iso":"2023-06-01T08:00:00.000Z"}"#));
+        assert!(requests[2].contains(r#""attempts":3"#));
+        assert!(requests[2].contains(r#""status":"failed""#));
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(cp1252("Daurade é 2€ ✓"), b"Daurade \xe9 2\x80 ?");
+        assert_eq!(cp1252("Œuf cœur"), b"\x8cuf c\x9cur");
+    }
+
+    #[test]
+    fn tickets() {
+        let esl = GenericEsl {
+            prix: "18.90".to_string(),
+            congel_infos: Some("décongelé".to_string()),
+            ..mock::esl()
+        };
+        let data = ticket(&esl, &RuleSet::fr(), Currency::Chf);
+        assert!(data.windows(10).any(|w| w == b"18.90 CHF\n"));
+        let mention = cp1252(DECONGELE_MENTION);
+        assert!(data.windows(mention.len()).any(|w| w == mention));
+        let fresh = ticket(&mock::esl(), &RuleSet::fr(), Currency::Eur);
+        assert!(!fresh.windows(mention.len()).any(|w| w == mention));
+    }
+
+    #[tokio::test]
+    async fn cancel_printed() {
+        let client = ParseClient::new("app".to_string(), None, "http://127.0.0.1:9".to_string());
+        let mut job = PrintJob {
+            object_id: Some("j1".to_string()),
+            status: PrintJobStatus::Printed,
+            ..PrintJob::new("serial", vec![])
+        };
+        assert!(matches!(
+            cancel(&client, &mut job).await,
+            Err(ParseError::Query { .. })
+        ));
+        assert_eq!(job.status, PrintJobStatus::Printed);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::endpoint::ServerEndpoint;
+use crate::parse::{ParseError, ParseErrorResponse};
+use crate::trace::TraceContext;
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// The requests of the Parse REST API and the interpretation of their responses.
+///
+/// This does no IO: requests are built as `http::Request` and responses are read from
+/// `http::Response`, [`ParseClient`](crate::parse::ParseClient) sends them with reqwest. The
+/// encoding of queries and the mapping of errors can be tested without any server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Protocol {
+    application_id: String,
+    api_key: Option<String>,
+    master_key: Option<String>,
+    session_token: Option<String>,
+    trace_context: Option<TraceContext>,
+    endpoint: ServerEndpoint,
+}
+
+impl Protocol {
+    pub fn new(application_id: String, api_key: Option<String>, endpoint: ServerEndpoint) -> Self {
+        Self {
+            application_id,
+            api_key,
+            master_key: None,
+            session_token: None,
+            trace_context: None,
+            endpoint,
+        }
+    }
+
+    /// Authenticates the requests with the master key
+    pub fn with_master_key(mut self, master_key: String) -> Self {
+        self.master_key = Some(master_key);
+        self
+    }
+
+    /// Authenticates the requests as the user of a session
+    pub fn with_session_token(mut self, session_token: String) -> Self {
+        self.session_token = Some(session_token);
+        self
+    }
+
+    /// Sends the `traceparent` and `tracestate` headers of a trace
+    pub fn with_trace_context(mut self, trace_context: TraceContext) -> Self {
+        self.trace_context = Some(trace_context);
+        self
+    }
+
+    /// Returns the Parse authentication headers, and the trace headers
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let application_id = HeaderValue::from_str(&self.application_id)
+            .expect("Cannot encode application ID into a request header");
+        if let Some(api_key) = &self.api_key {
+            let key = HeaderValue::from_str(api_key)
+                .expect("Cannot encode application key into a request header");
+            headers.append("X-Parse-REST-API-Key", key);
+        }
+        if let Some(master_key) = &self.master_key {
+            let mut key = HeaderValue::from_str(master_key)
+                .expect("Cannot encode master key into a request header");
+            key.set_sensitive(true);
+            headers.append("X-Parse-Master-Key", key);
+        }
+        if let Some(session_token) = &self.session_token {
+            let mut token = HeaderValue::from_str(session_token)
+                .expect("Cannot encode session token into a request header");
+            token.set_sensitive(true);
+            headers.append("X-Parse-Session-Token", token);
+        }
+        if let Some(trace_context) = &self.trace_context {
+            let traceparent = HeaderValue::from_str(&trace_context.traceparent())
+                .expect("a traceparent is a valid header");
+            headers.append("traceparent", traceparent);
+            if let Some(state) = trace_context
+                .state
+                .as_deref()
+                .and_then(|state| HeaderValue::from_str(state).ok())
+            {
+                headers.append("tracestate", state);
+            }
+        }
+        headers.append("X-Parse-Application-Id", application_id);
+        headers
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Request<Vec<u8>>, ParseError> {
+        let mut request = Request::builder().method(method).uri(url);
+        for (name, value) in self.headers().iter() {
+            request = request.header(name, value);
+        }
+        if body.is_some() {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        request
+            .body(body.unwrap_or_default())
+            .map_err(|_e| ParseError::Url)
+    }
+
+    /// A POST creating an object
+    pub fn save<T: Serialize>(&self, path: &str, data: &T) -> Result<Request<Vec<u8>>, ParseError> {
+        let body = serde_json::to_vec(data)?;
+        self.request(Method::POST, &self.endpoint.url(path), Some(body))
+    }
+
+    /// A GET querying objects, with extra parameters (`limit`, `keys`...) next to `where`
+    pub fn fetch<U: Serialize>(
+        &self,
+        path: &str,
+        query: &U,
+        params: &[(&str, String)],
+    ) -> Result<Request<Vec<u8>>, ParseError> {
+        let mut url = Url::parse(&self.endpoint.url(path)).map_err(|_e| ParseError::Url)?;
+        url.query_pairs_mut()
+            .append_pair("where", &serde_json::to_string(query)?);
+        for (name, value) in params {
+            url.query_pairs_mut().append_pair(name, value);
+        }
+        self.request(Method::GET, url.as_str(), None)
+    }
+
+    /// A GET reading a resource
+    pub fn get(&self, path: &str) -> Result<Request<Vec<u8>>, ParseError> {
+        self.request(Method::GET, &self.endpoint.url(path), None)
+    }
+
+    /// A PUT updating an object
+    pub fn update<T: Serialize>(
+        &self,
+        path: &str,
+        data: &T,
+    ) -> Result<Request<Vec<u8>>, ParseError> {
+        let body = serde_json::to_vec(data)?;
+        self.request(Method::PUT, &self.endpoint.url(path), Some(body))
+    }
+
+    /// A DELETE removing an object
+    pub fn delete(&self, path: &str) -> Result<Request<Vec<u8>>, ParseError> {
+        self.request(Method::DELETE, &self.endpoint.url(path), None)
+    }
+
+    /// A POST uploading a file, its name is percent-encoded
+    pub fn upload(
+        &self,
+        name: &str,
+        bytes: Vec<u8>,
+        mime: &str,
+    ) -> Result<Request<Vec<u8>>, ParseError> {
+        let mut url = Url::parse(&self.endpoint.url("files")).map_err(|_e| ParseError::Url)?;
+        url.path_segments_mut()
+            .map_err(|_e| ParseError::Url)?
+            .push(name);
+        let mut request = Request::builder().method(Method::POST).uri(url.as_str());
+        for (name, value) in self.headers().iter() {
+            request = request.header(name, value);
+        }
+        request
+            .header(CONTENT_TYPE, mime)
+            .body(bytes)
+            .map_err(|_e| ParseError::Url)
+    }
+
+    /// The first message of a LiveQuery connection, authenticating the client
+    pub fn live_query_connect(&self) -> serde_json::Value {
+        let mut message = serde_json::json!({
+            "op": "connect",
+            "applicationId": self.application_id,
+        });
+        if let Some(api_key) = &self.api_key {
+            message["restAPIKey"] = api_key.as_str().into();
+        }
+        if let Some(master_key) = &self.master_key {
+            message["masterKey"] = master_key.as_str().into();
+        }
+        if let Some(session_token) = &self.session_token {
+            message["sessionToken"] = session_token.as_str().into();
+        }
+        message
+    }
+
+    /// A POST to the batch endpoint
+    pub fn batch(&self, requests: &serde_json::Value) -> Result<Request<Vec<u8>>, ParseError> {
+        let body = serde_json::to_vec(&serde_json::json!({ "requests": requests }))?;
+        self.request(Method::POST, &self.endpoint.url("batch"), Some(body))
+    }
+}
+
+/// Returns the error of a response whose status is not the expected one.
+///
+/// The cause is the `error` of the Parse error body, or the raw body when a proxy answered
+/// with something else than a Parse error.
+pub fn error(response: &Response<Vec<u8>>) -> ParseError {
+    let body = response.body();
+    let cause = match serde_json::from_slice::<ParseErrorResponse>(body) {
+        Ok(error) => error.error,
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    };
+    ParseError::Platform {
+        code: response.status(),
+        cause,
+    }
+}
+
+/// Reads the JSON body of a response, or its error if the status is not `expected`
+pub fn interpret<T: for<'de> Deserialize<'de>>(
+    response: &Response<Vec<u8>>,
+    expected: StatusCode,
+) -> Result<T, ParseError> {
+    if response.status() != expected {
+        return Err(error(response));
+    }
+    Ok(serde_json::from_slice(response.body())?)
+}
+
+/// Checks the status of a response whose body is not needed
+pub fn interpret_empty(
+    response: &Response<Vec<u8>>,
+    expected: StatusCode,
+) -> Result<(), ParseError> {
+    if response.status() != expected {
+        return Err(error(response));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseCreated;
+    use crate::query::WhereClause;
+    use serde_json::json;
+
+    fn protocol() -> Protocol {
+        Protocol::new(
+            "app".to_string(),
+            Some("key".to_string()),
+            ServerEndpoint::new("https://example.com/parse"),
+        )
+    }
+
+    fn response(status: u16, body: &str) -> Response<Vec<u8>> {
+        Response::builder()
+            .status(status)
+            .body(body.as_bytes().to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn requests() {
+        let request = protocol()
+            .fetch(
+                "classes/Esl",
+                &WhereClause::eq("serial", "a b"),
+                &[("limit", "10".to_string())],
+            )
+            .unwrap();
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.uri().to_string(),
+            "https://example.com/parse/classes/Esl?where=%7B%22serial%22%3A%22a+b%22%7D&limit=10"
+        );
+        assert_eq!(request.headers()["X-Parse-Application-Id"], "app");
+        assert_eq!(request.headers()["X-Parse-REST-API-Key"], "key");
+        assert!(request.body().is_empty());
+
+        let request = protocol()
+            .save("classes/Esl", &json!({ "nom": "Bar" }))
+            .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(request.body(), br#"{"nom":"Bar"}"#);
+
+        let anonymous = Protocol::new("app".to_string(), None, ServerEndpoint::new("http://h"));
+        let request = anonymous.delete("classes/Esl/abc").unwrap();
+        assert_eq!(request.uri(), "http://h/classes/Esl/abc");
+        assert!(!request.headers().contains_key("X-Parse-REST-API-Key"));
+        assert!(!request.headers().contains_key("X-Parse-Master-Key"));
+
+        let request = protocol()
+            .upload("photo cabillaud.jpg", vec![1, 2], "image/jpeg")
+            .unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://example.com/parse/files/photo%20cabillaud.jpg"
+        );
+        assert_eq!(request.headers()[CONTENT_TYPE], "image/jpeg");
+        assert_eq!(request.body(), &vec![1, 2]);
+
+        let master = protocol().with_master_key("master".to_string());
+        let request = master.get("schemas/Esl").unwrap();
+        assert_eq!(request.headers()["X-Parse-Master-Key"], "master");
+
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(header, Some("congo=t61rcWkgMzE")).unwrap();
+        let headers = protocol().with_trace_context(context).headers();
+        assert_eq!(headers["traceparent"], header);
+        assert_eq!(headers["tracestate"], "congo=t61rcWkgMzE");
+    }
+
+    #[test]
+    fn responses() {
+        let created: ParseCreated = interpret(
+            &response(201, r#"{"createdAt":"now","objectId":"abc"}"#),
+            StatusCode::CREATED,
+        )
+        .unwrap();
+        assert_eq!(created.object_id, "abc");
+        match interpret::<ParseCreated>(
+            &response(404, r#"{"code":101,"error":"Object not found."}"#),
+            StatusCode::CREATED,
+        ) {
+            Err(ParseError::Platform { code, cause }) => {
+                assert_eq!(code, StatusCode::NOT_FOUND);
+                assert_eq!(cause, "Object not found.");
+            }
+            _ => panic!("expected a platform error"),
+        }
+        match interpret_empty(&response(502, "<html>Bad Gateway</html>"), StatusCode::OK) {
+            Err(ParseError::Platform { cause, .. }) => {
+                assert_eq!(cause, "<html>Bad Gateway</html>")
+            }
+            _ => panic!("expected a platform error"),
+        }
+        assert!(matches!(
+            interpret::<ParseCreated>(&response(201, "{}"), StatusCode::CREATED),
+            Err(ParseError::SerdeJson { .. })
+        ));
+    }
+}
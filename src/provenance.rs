@@ -0,0 +1,140 @@
+use crate::generic_esl::GenericEsl;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Percent-encodes a value so it can be used as a path segment or a query value
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Builds the signed links to the provenance page of a product, printed as QR codes on labels.
+///
+/// The link is built from a template such as `https://example.com/p/{serial}/{plu}` to which a
+/// `token` query parameter is appended. The token is an HMAC-SHA256 of the serial and the PLU,
+/// the provenance web page verifies it with [`ProvenanceLinks::verify`] to make sure the link
+/// was issued by us.
+#[derive(Clone)]
+pub struct ProvenanceLinks {
+    url_template: String,
+    secret: Vec<u8>,
+}
+
+impl ProvenanceLinks {
+    pub fn new(url_template: String, secret: Vec<u8>) -> Self {
+        Self {
+            url_template,
+            secret,
+        }
+    }
+
+    /// Returns a new ProvenanceLinks by reading properties from the environment.
+    ///
+    /// * PROVENANCE_URL_TEMPLATE
+    /// * PROVENANCE_SECRET
+    pub fn from_env() -> Self {
+        let url_template =
+            env::var("PROVENANCE_URL_TEMPLATE").expect("env.PROVENANCE_URL_TEMPLATE is undefined");
+        let secret = env::var("PROVENANCE_SECRET").expect("env.PROVENANCE_SECRET is undefined");
+        ProvenanceLinks::new(url_template, secret.into_bytes())
+    }
+
+    fn mac(&self, serial: &str, plu: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(serial.as_bytes());
+        // Separates the fields so that ("ab", "c") and ("a", "bc") are signed differently
+        mac.update(&[0]);
+        mac.update(plu.as_bytes());
+        mac
+    }
+
+    /// Returns the token signing a serial and a PLU
+    pub fn token(&self, serial: &str, plu: &str) -> String {
+        let signature = self.mac(serial, plu).finalize().into_bytes();
+        signature
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Checks a token presented along with a serial and a PLU
+    pub fn verify(&self, serial: &str, plu: &str, token: &str) -> bool {
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return false;
+        }
+        let bytes: Option<Vec<u8>> = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16).ok())
+            .collect();
+        match bytes {
+            Some(bytes) => self.mac(serial, plu).verify_slice(&bytes).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Returns the signed provenance link of a serial and a PLU
+    pub fn link(&self, serial: &str, plu: &str) -> String {
+        let url = self
+            .url_template
+            .replace("{serial}", &encode(serial))
+            .replace("{plu}", &encode(plu));
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!("{url}{separator}token={}", self.token(serial, plu))
+    }
+
+    /// Returns the signed provenance link of an Esl
+    pub fn esl_link(&self, esl: &GenericEsl) -> String {
+        self.link(&esl.serial, &esl.plu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn links() -> ProvenanceLinks {
+        ProvenanceLinks::new(
+            "https://example.com/p/{serial}/{plu}".to_string(),
+            b"secret".to_vec(),
+        )
+    }
+
+    #[test]
+    fn link() {
+        let links = links();
+        let token = links.token("serial", "1234");
+        assert_eq!(token.len(), 64);
+        assert_eq!(
+            links.esl_link(&mock::esl()),
+            format!("https://example.com/p/serial/1234?token={token}")
+        );
+        assert!(links
+            .link("a b/", "1")
+            .starts_with("https://example.com/p/a%20b%2F/1?token="));
+    }
+
+    #[test]
+    fn verify() {
+        let links = links();
+        let token = links.token("serial", "1234");
+        assert!(links.verify("serial", "1234", &token));
+        assert!(!links.verify("serial", "1235", &token));
+        assert!(!links.verify("seria", "l1234", &links.token("serial", "1234")));
+        assert!(!links.verify("serial", "1234", "not a token"));
+        assert!(!links.verify("serial", "1234", "é"));
+        let other = ProvenanceLinks::new(String::new(), b"other".to_vec());
+        assert!(!other.verify("serial", "1234", &token));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::generic_esl::GenericEsl;
+use crate::parse::{ParseClient, ParseError};
+use crate::protocol;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// The devices a push notification is sent to
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PushTarget {
+    /// The installations subscribed to one of these channels
+    Channels(Vec<String>),
+    /// The installations matching a query on the `_Installation` class
+    Where(Value),
+}
+
+/// A push notification, sent with [`ParseClient::push`]
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Push {
+    #[serde(flatten)]
+    pub target: PushTarget,
+    /// The payload received by the app, `alert` is the text displayed
+    pub data: Map<String, Value>,
+    /// Delays the push, it is sent right away when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_time: Option<DateTime<Utc>>,
+    /// The push is not delivered to devices offline until then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+/// Returns the channel of the devices of a store, such as its printing stations.
+///
+/// Parse channel names only accept letters, digits, `_` and `-`, the other characters of the
+/// serial are replaced with `_`.
+pub fn store_channel(serial: &str) -> String {
+    let serial: String = serial
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("store-{serial}")
+}
+
+impl Push {
+    fn new(target: PushTarget, alert: &str) -> Self {
+        let mut data = Map::new();
+        data.insert("alert".to_string(), alert.into());
+        Self {
+            target,
+            data,
+            push_time: None,
+            expiration_time: None,
+        }
+    }
+
+    pub fn to_channels(channels: &[&str], alert: &str) -> Self {
+        let channels = channels.iter().map(|channel| channel.to_string()).collect();
+        Self::new(PushTarget::Channels(channels), alert)
+    }
+
+    /// Sends to the installations matching a query, such as `{"deviceType": "android"}`
+    pub fn to_installations<U: Serialize>(query: U, alert: &str) -> Result<Self, ParseError> {
+        Ok(Self::new(
+            PushTarget::Where(serde_json::to_value(query)?),
+            alert,
+        ))
+    }
+
+    /// Tells the devices of a store that labels are ready to be printed
+    pub fn labels_ready(serial: &str, esls: &[GenericEsl]) -> Self {
+        let alert = match esls.len() {
+            1 => "1 label ready to print".to_string(),
+            count => format!("{count} labels ready to print"),
+        };
+        let esl_ids: Vec<&str> = esls.iter().map(|esl| esl.id.as_str()).collect();
+        Self::to_channels(&[&store_channel(serial)], &alert)
+            .with_data("type", json!("labelsReady"))
+            .with_data("serial", json!(serial))
+            .with_data("eslIds", json!(esl_ids))
+    }
+
+    pub fn title(self, title: &str) -> Self {
+        self.with_data("title", json!(title))
+    }
+
+    /// Adds a key to the payload received by the app
+    pub fn with_data(mut self, key: &str, value: Value) -> Self {
+        self.data.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn at(mut self, push_time: DateTime<Utc>) -> Self {
+        self.push_time = Some(push_time);
+        self
+    }
+
+    pub fn expires_at(mut self, expiration_time: DateTime<Utc>) -> Self {
+        self.expiration_time = Some(expiration_time);
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct PushResult {
+    result: bool,
+}
+
+impl ParseClient {
+    /// Sends a push notification, with the master key Parse requires.
+    ///
+    /// Returns the id of its `_PushStatus` when the server gives it. The server must have a
+    /// push adapter configured, for FCM or APNs.
+    pub async fn push(&self, push: &Push) -> Result<Option<String>, ParseError> {
+        let master = self.as_master();
+        let (response, _) = master.send(master.protocol().save("push", push)?).await?;
+        let result: PushResult = protocol::interpret(&response, StatusCode::OK)?;
+        if !result.result {
+            return Err(ParseError::Platform {
+                code: response.status(),
+                cause: "the push was not scheduled".to_string(),
+            });
+        }
+        Ok(response
+            .headers()
+            .get("X-Parse-Push-Status-Id")
+            .and_then(|id| id.to_str().ok())
+            .map(str::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use chrono::TimeZone;
+
+    #[test]
+    fn serialize() {
+        let at = Utc.with_ymd_and_hms(2023, 6, 1, 8, 0, 0).unwrap();
+        let push = Push::to_installations(json!({ "deviceType": "android" }), "Hello")
+            .unwrap()
+            .title("Rungis")
+            .expires_at(at);
+        assert_eq!(
+            serde_json::to_value(&push).unwrap(),
+            json!({
+                "where": { "deviceType": "android" },
+                "data": { "alert": "Hello", "title": "Rungis" },
+                "expiration_time": "2023-06-01T08:00:00Z"
+            })
+        );
+        assert_eq!(store_channel("FR 27/01"), "store-FR_27_01");
+    }
+
+    #[tokio::test]
+    async fn push() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &["X-Parse-Push-Status-Id: p1"],
+            r#"{"result":true}"#,
+        )]);
+        let client =
+            ParseClient::new("app".to_string(), None, url).with_master_key("master".to_string());
+        let push = Push::labels_ready("serial", &[mock::esl()]);
+        assert_eq!(client.push(&push).await.unwrap().as_deref(), Some("p1"));
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("POST /push"));
+        assert!(request.contains("x-parse-master-key: master"));
+        assert!(request.contains(r#""channels":["store-serial"]"#));
+        assert!(request.contains(r#""alert":"1 label ready to print""#));
+        assert!(request.contains(r#""eslIds":["esl"]"#));
+    }
+}
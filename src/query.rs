@@ -0,0 +1,537 @@
+use crate::geo::{GeoPoint, EARTH_RADIUS_KM};
+use crate::parse::{parse_field_name, ParseError};
+use crate::pointer::{ParseClass, Pointer};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+/// A constraint on the value of a field in a where clause
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    Eq(Value),
+    Ne(Value),
+    Lt(Value),
+    Lte(Value),
+    Gt(Value),
+    Gte(Value),
+    /// The field value is one of the listed values
+    In(Vec<Value>),
+    /// The field value is none of the listed values
+    Nin(Vec<Value>),
+    /// The field is an array containing all the listed values
+    All(Vec<Value>),
+    Exists(bool),
+    Regex(String),
+    /// The GeoPoint field is near a point, results are sorted from the nearest
+    NearSphere(GeoPoint),
+    /// Limits a [`Constraint::NearSphere`] to a distance
+    MaxDistanceInKilometers(f64),
+    /// The GeoPoint field is within a box, given by its south-west and north-east corners
+    WithinBox(GeoPoint, GeoPoint),
+    /// The GeoPoint field is within a polygon of at least 3 points
+    GeoWithinPolygon(Vec<GeoPoint>),
+    /// The GeoPoint field is within a distance in kilometers of a point, unsorted
+    GeoWithinSphere(GeoPoint, f64),
+}
+
+impl Constraint {
+    /// Returns the Parse operator of this constraint
+    pub fn operator(&self) -> &'static str {
+        match self {
+            Constraint::Eq(_) => "$eq",
+            Constraint::Ne(_) => "$ne",
+            Constraint::Lt(_) => "$lt",
+            Constraint::Lte(_) => "$lte",
+            Constraint::Gt(_) => "$gt",
+            Constraint::Gte(_) => "$gte",
+            Constraint::In(_) => "$in",
+            Constraint::Nin(_) => "$nin",
+            Constraint::All(_) => "$all",
+            Constraint::Exists(_) => "$exists",
+            Constraint::Regex(_) => "$regex",
+            Constraint::NearSphere(_) => "$nearSphere",
+            Constraint::MaxDistanceInKilometers(_) => "$maxDistanceInKilometers",
+            Constraint::WithinBox(..) => "$within",
+            Constraint::GeoWithinPolygon(_) | Constraint::GeoWithinSphere(..) => "$geoWithin",
+        }
+    }
+
+    fn value(&self) -> Value {
+        match self {
+            Constraint::Eq(value)
+            | Constraint::Ne(value)
+            | Constraint::Lt(value)
+            | Constraint::Lte(value)
+            | Constraint::Gt(value)
+            | Constraint::Gte(value) => value.clone(),
+            Constraint::In(values) | Constraint::Nin(values) | Constraint::All(values) => {
+                Value::Array(values.clone())
+            }
+            Constraint::Exists(exists) => Value::Bool(*exists),
+            Constraint::Regex(regex) => Value::String(regex.clone()),
+            Constraint::NearSphere(point) => json!(point),
+            Constraint::MaxDistanceInKilometers(distance) => json!(distance),
+            Constraint::WithinBox(south_west, north_east) => {
+                json!({ "$box": [south_west, north_east] })
+            }
+            Constraint::GeoWithinPolygon(points) => json!({ "$polygon": points }),
+            // Parse expects the radius in radians
+            Constraint::GeoWithinSphere(center, distance) => {
+                json!({ "$centerSphere": [center, distance / EARTH_RADIUS_KM] })
+            }
+        }
+    }
+
+    fn validate(&self, field: &str) -> Result<(), ParseError> {
+        match self {
+            Constraint::Lt(value)
+            | Constraint::Lte(value)
+            | Constraint::Gt(value)
+            | Constraint::Gte(value)
+                if !is_comparable(value) =>
+            {
+                return Err(ParseError::Query {
+                    cause: format!(
+                        "{} on {field} expects a number, a string or a date, got {value}",
+                        self.operator()
+                    ),
+                });
+            }
+            Constraint::Regex(regex) if regex.is_empty() => {
+                return Err(ParseError::Query {
+                    cause: format!("$regex on {field} is empty"),
+                });
+            }
+            Constraint::GeoWithinPolygon(points) if points.len() < 3 => {
+                return Err(ParseError::Query {
+                    cause: format!("$polygon on {field} needs at least 3 points"),
+                });
+            }
+            Constraint::MaxDistanceInKilometers(distance)
+            | Constraint::GeoWithinSphere(_, distance)
+                if !(distance.is_finite() && *distance >= 0.) =>
+            {
+                return Err(ParseError::Query {
+                    cause: format!("invalid distance {distance} on {field}"),
+                });
+            }
+            _ => {}
+        }
+        let points = match self {
+            Constraint::NearSphere(point) | Constraint::GeoWithinSphere(point, _) => {
+                std::slice::from_ref(point)
+            }
+            Constraint::WithinBox(south_west, north_east) => &[*south_west, *north_east][..],
+            Constraint::GeoWithinPolygon(points) => points.as_slice(),
+            _ => &[],
+        };
+        if let Some(point) = points.iter().find(|point| !point.is_valid()) {
+            return Err(ParseError::Query {
+                cause: format!(
+                    "{} on {field} has an invalid point {}, {}",
+                    self.operator(),
+                    point.latitude,
+                    point.longitude
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if a value can be used with ordering operators
+fn is_comparable(value: &Value) -> bool {
+    match value {
+        Value::Number(_) | Value::String(_) => true,
+        Value::Object(object) => object.get("__type") == Some(&Value::from("Date")),
+        _ => false,
+    }
+}
+
+/// Checks that a field name can be used in a query.
+///
+/// Dots are allowed to reach the keys of object fields.
+fn validate_field(field: &str) -> Result<(), ParseError> {
+    let valid = !field.is_empty()
+        && !field.starts_with(['$', '.'])
+        && !field.ends_with('.')
+        && field
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(ParseError::Query {
+            cause: format!("invalid field name {field:?}"),
+        })
+    }
+}
+
+/// The `where` parameter of a Parse query.
+///
+/// Clauses are validated before being sent by [`ParseClient::fetch_where`], so that a malformed
+/// query fails with a precise error instead of an opaque server response. The
+/// [`WhereClause::Raw`] variant is sent as is and is not validated.
+///
+/// [`ParseClient::fetch_where`]: crate::parse::ParseClient::fetch_where
+#[derive(Clone, Debug, PartialEq)]
+pub enum WhereClause {
+    /// Constraints on one field, all of them must match
+    Field {
+        field: String,
+        constraints: Vec<Constraint>,
+    },
+    /// All the clauses must match
+    And(Vec<WhereClause>),
+    /// At least one of the clauses must match
+    Or(Vec<WhereClause>),
+    /// A where clause written by hand
+    Raw(Value),
+}
+
+impl WhereClause {
+    pub fn field(field: &str, constraint: Constraint) -> Self {
+        WhereClause::Field {
+            field: field.to_string(),
+            constraints: vec![constraint],
+        }
+    }
+
+    pub fn eq<V: Into<Value>>(field: &str, value: V) -> Self {
+        WhereClause::field(field, Constraint::Eq(value.into()))
+    }
+
+    pub fn raw(value: Value) -> Self {
+        WhereClause::Raw(value)
+    }
+
+    /// The objects of a relation column of another object, such as
+    /// `WhereClause::related_to(&role, "users")` for the users of a role
+    pub fn related_to<T: ParseClass>(object: &Pointer<T>, key: &str) -> Self {
+        WhereClause::Raw(json!({ "$relatedTo": { "object": object, "key": key } }))
+    }
+
+    /// Objects near a point, the nearest first, optionally within a distance in kilometers
+    pub fn near(field: &str, point: GeoPoint, max_distance: Option<f64>) -> Self {
+        let mut constraints = vec![Constraint::NearSphere(point)];
+        constraints.extend(max_distance.map(Constraint::MaxDistanceInKilometers));
+        WhereClause::Field {
+            field: field.to_string(),
+            constraints,
+        }
+    }
+
+    /// Objects within a box, given by its south-west and north-east corners
+    pub fn within_box(field: &str, south_west: GeoPoint, north_east: GeoPoint) -> Self {
+        WhereClause::field(field, Constraint::WithinBox(south_west, north_east))
+    }
+
+    /// Objects within a polygon, such as the boundaries of a region
+    pub fn within_polygon(field: &str, points: &[GeoPoint]) -> Self {
+        WhereClause::field(field, Constraint::GeoWithinPolygon(points.to_vec()))
+    }
+
+    /// Objects within a distance in kilometers of a point
+    pub fn within_radius(field: &str, center: GeoPoint, distance: f64) -> Self {
+        WhereClause::field(field, Constraint::GeoWithinSphere(center, distance))
+    }
+
+    /// Checks the field names, the operators and the type of their values
+    pub fn validate(&self) -> Result<(), ParseError> {
+        match self {
+            WhereClause::Field { field, constraints } => {
+                validate_field(field)?;
+                if constraints.is_empty() {
+                    return Err(ParseError::Query {
+                        cause: format!("no constraint on {field}"),
+                    });
+                }
+                constraints
+                    .iter()
+                    .try_for_each(|constraint| constraint.validate(field))
+            }
+            WhereClause::And(clauses) | WhereClause::Or(clauses) => {
+                if clauses.is_empty() {
+                    return Err(ParseError::Query {
+                        cause: "empty $and/$or clause".to_string(),
+                    });
+                }
+                clauses.iter().try_for_each(WhereClause::validate)
+            }
+            WhereClause::Raw(Value::Object(_)) => Ok(()),
+            WhereClause::Raw(value) => Err(ParseError::Query {
+                cause: format!("a where clause must be an object, got {value}"),
+            }),
+        }
+    }
+
+    /// Returns the JSON representation of this clause expected by Parse
+    pub fn to_json(&self) -> Value {
+        match self {
+            WhereClause::Field { field, constraints } => {
+                let value = match constraints.as_slice() {
+                    [Constraint::Eq(value)] => value.clone(),
+                    constraints => Value::Object(
+                        constraints
+                            .iter()
+                            .map(|c| (c.operator().to_string(), c.value()))
+                            .collect(),
+                    ),
+                };
+                let mut object = Map::new();
+                object.insert(field.clone(), value);
+                Value::Object(object)
+            }
+            WhereClause::And(clauses) => {
+                let mut object = Map::new();
+                for clause in clauses {
+                    match clause.to_json() {
+                        Value::Object(fields)
+                            if fields.keys().all(|key| !object.contains_key(key)) =>
+                        {
+                            object.extend(fields)
+                        }
+                        // The same field is constrained twice, Parse needs an explicit $and
+                        _ => {
+                            let clauses: Vec<Value> = clauses.iter().map(Self::to_json).collect();
+                            let mut object = Map::new();
+                            object.insert("$and".to_string(), Value::Array(clauses));
+                            return Value::Object(object);
+                        }
+                    }
+                }
+                Value::Object(object)
+            }
+            WhereClause::Or(clauses) => {
+                let mut object = Map::new();
+                object.insert(
+                    "$or".to_string(),
+                    Value::Array(clauses.iter().map(Self::to_json).collect()),
+                );
+                Value::Object(object)
+            }
+            WhereClause::Raw(value) => value.clone(),
+        }
+    }
+}
+
+impl Serialize for WhereClause {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+/// A fluent builder of where clauses, such as
+/// `Query::new().eq("serial", serial).eq("printed", false)`.
+///
+/// Field names are converted with [`parse_field_name`], `sous_zone_code` can be written for
+/// `sousZoneCode`. The constraints on the same field are merged, all of them must match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    fields: Vec<(String, Vec<Constraint>)>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn constraint(mut self, field: &str, constraint: Constraint) -> Self {
+        let field = parse_field_name(field);
+        match self.fields.iter_mut().find(|(name, _)| *name == field) {
+            Some((_, constraints)) => constraints.push(constraint),
+            None => self.fields.push((field, vec![constraint])),
+        }
+        self
+    }
+
+    pub fn eq<V: Into<Value>>(self, field: &str, value: V) -> Self {
+        self.constraint(field, Constraint::Eq(value.into()))
+    }
+
+    pub fn ne<V: Into<Value>>(self, field: &str, value: V) -> Self {
+        self.constraint(field, Constraint::Ne(value.into()))
+    }
+
+    pub fn gt<V: Into<Value>>(self, field: &str, value: V) -> Self {
+        self.constraint(field, Constraint::Gt(value.into()))
+    }
+
+    pub fn lt<V: Into<Value>>(self, field: &str, value: V) -> Self {
+        self.constraint(field, Constraint::Lt(value.into()))
+    }
+
+    /// The field value is one of `values`
+    pub fn in_<V: Into<Value>>(self, field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        self.constraint(field, Constraint::In(values))
+    }
+
+    pub fn exists(self, field: &str, exists: bool) -> Self {
+        self.constraint(field, Constraint::Exists(exists))
+    }
+
+    /// The string field contains `text`, matched literally
+    pub fn contains(self, field: &str, text: &str) -> Self {
+        let quoted = format!("\\Q{}\\E", text.replace("\\E", "\\E\\\\E\\Q"));
+        self.constraint(field, Constraint::Regex(quoted))
+    }
+
+    /// Returns the where clause of this query, see [`WhereClause::validate`]
+    pub fn build(&self) -> WhereClause {
+        WhereClause::And(
+            self.fields
+                .iter()
+                .map(|(field, constraints)| WhereClause::Field {
+                    field: field.clone(),
+                    constraints: constraints.clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl From<Query> for WhereClause {
+    fn from(query: Query) -> Self {
+        query.build()
+    }
+}
+
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.build().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serialize() {
+        let clause = WhereClause::And(vec![
+            WhereClause::eq("serial", "abc"),
+            WhereClause::eq("printed", false),
+            WhereClause::Field {
+                field: "categorie".to_string(),
+                constraints: vec![Constraint::Gte(json!(1)), Constraint::Lt(json!(3))],
+            },
+        ]);
+        assert_eq!(
+            serde_json::to_value(&clause).unwrap(),
+            json!({ "serial": "abc", "printed": false, "categorie": { "$gte": 1, "$lt": 3 } })
+        );
+
+        let clause = WhereClause::Or(vec![
+            WhereClause::field("zone", Constraint::In(vec![json!("27"), json!("37")])),
+            WhereClause::field("zone", Constraint::Exists(false)),
+        ]);
+        assert_eq!(
+            clause.to_json(),
+            json!({ "$or": [{ "zone": { "$in": ["27", "37"] } }, { "zone": { "$exists": false } }] })
+        );
+    }
+
+    #[test]
+    fn query() {
+        let query = Query::new()
+            .eq("serial", "abc")
+            .eq("printed", false)
+            .gt("categorie", 1)
+            .lt("categorie", 3)
+            .ne("sous_zone_code", "IV")
+            .in_("zone_code", ["27", "37"])
+            .exists("engin", true)
+            .contains("nom", "cab.");
+        assert!(query.build().validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({
+                "serial": "abc",
+                "printed": false,
+                "categorie": { "$gt": 1, "$lt": 3 },
+                "sousZoneCode": { "$ne": "IV" },
+                "zoneCode": { "$in": ["27", "37"] },
+                "engin": { "$exists": true },
+                "nom": { "$regex": "\\Qcab.\\E" },
+            })
+        );
+        assert_eq!(
+            Query::new().contains("nom", "a\\Eb").build().to_json()["nom"]["$regex"],
+            "\\Qa\\E\\\\E\\Qb\\E"
+        );
+        assert!(Query::new().gt("prix", true).build().validate().is_err());
+    }
+
+    #[test]
+    fn serialize_duplicate_fields() {
+        let clause = WhereClause::And(vec![
+            WhereClause::field("nom", Constraint::Regex("^Cab".to_string())),
+            WhereClause::field("nom", Constraint::Ne(json!("Cabillaud"))),
+        ]);
+        assert_eq!(
+            clause.to_json(),
+            json!({ "$and": [{ "nom": { "$regex": "^Cab" } }, { "nom": { "$ne": "Cabillaud" } }] })
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert!(WhereClause::eq("sousZoneCode", "IV").validate().is_ok());
+        assert!(WhereClause::eq("origine.pays", "FR").validate().is_ok());
+        assert!(WhereClause::eq("", "IV").validate().is_err());
+        assert!(WhereClause::eq("$where", "IV").validate().is_err());
+        assert!(WhereClause::eq("nom scientifique", "IV")
+            .validate()
+            .is_err());
+        assert!(WhereClause::field("prix", Constraint::Gt(json!(true)))
+            .validate()
+            .is_err());
+        assert!(WhereClause::field(
+            "createdAt",
+            Constraint::Gt(json!({ "__type": "Date", "iso": "2023-05-26T08:00:00.000Z" }))
+        )
+        .validate()
+        .is_ok());
+        assert!(WhereClause::field("nom", Constraint::Regex(String::new()))
+            .validate()
+            .is_err());
+        assert!(WhereClause::Or(vec![]).validate().is_err());
+        assert!(WhereClause::And(vec![WhereClause::eq("$bad", 1)])
+            .validate()
+            .is_err());
+        assert!(WhereClause::raw(json!({ "$anything": 1 }))
+            .validate()
+            .is_ok());
+        assert!(WhereClause::raw(json!([1])).validate().is_err());
+    }
+
+    #[test]
+    fn geo() {
+        let tours = GeoPoint::new(47.39, 0.69);
+        let point = json!({ "__type": "GeoPoint", "latitude": 47.39, "longitude": 0.69 });
+        let near = WhereClause::near("geoPoint", tours, Some(50.));
+        assert!(near.validate().is_ok());
+        assert_eq!(
+            near.to_json(),
+            json!({ "geoPoint": { "$nearSphere": point, "$maxDistanceInKilometers": 50. } })
+        );
+        let radius = WhereClause::within_radius("geoPoint", tours, EARTH_RADIUS_KM);
+        assert_eq!(
+            radius.to_json(),
+            json!({ "geoPoint": { "$geoWithin": { "$centerSphere": [point, 1.] } } })
+        );
+        let area = WhereClause::within_box("geoPoint", GeoPoint::new(47., 0.), tours);
+        assert_eq!(area.to_json()["geoPoint"]["$within"]["$box"][1], point);
+
+        assert!(WhereClause::within_polygon("geoPoint", &[tours, tours])
+            .validate()
+            .is_err());
+        assert!(WhereClause::near("geoPoint", GeoPoint::new(0., 200.), None)
+            .validate()
+            .is_err());
+        assert!(WhereClause::within_radius("geoPoint", tours, -1.)
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::parse::ParseError;
+use crate::runtime;
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many requests a client sends, see [`ParseClient::with_rate_limit`].
+///
+/// Hosted Parse servers answer `429 Too Many Requests` past a quota: bulk operations such as
+/// [`ParseClient::batch`] wait for their turn instead.
+///
+/// [`ParseClient::with_rate_limit`]: crate::parse::ParseClient::with_rate_limit
+/// [`ParseClient::batch`]: crate::parse::ParseClient::batch
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The requests waiting for a response at the same time, unlimited when unset
+    pub max_in_flight: Option<usize>,
+    /// The requests sent per second, evenly spaced, unlimited when unset
+    pub requests_per_second: Option<f64>,
+}
+
+impl RateLimit {
+    /// At most `max_in_flight` requests at the same time
+    pub fn in_flight(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight: Some(max_in_flight),
+            requests_per_second: None,
+        }
+    }
+
+    /// At most `requests_per_second` requests per second
+    pub fn per_second(requests_per_second: f64) -> Self {
+        Self {
+            max_in_flight: None,
+            requests_per_second: Some(requests_per_second),
+        }
+    }
+}
+
+/// The right to send a request, given back to its [`Limiter`] when dropped
+#[derive(Debug)]
+pub(crate) struct Permit(UnboundedSender<()>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.0.unbounded_send(());
+    }
+}
+
+/// The permits of the requests waiting for a response: one message per request that can be sent
+#[derive(Debug)]
+struct InFlight {
+    permits: futures::lock::Mutex<UnboundedReceiver<()>>,
+    release: UnboundedSender<()>,
+}
+
+/// Enforces a [`RateLimit`], shared by the clones of a client
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    in_flight: Option<InFlight>,
+    interval: Option<Duration>,
+    /// The earliest time the next request can be sent
+    next: Mutex<Option<Instant>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: &RateLimit) -> Self {
+        Self {
+            in_flight: limit.max_in_flight.map(|max| {
+                let (release, permits) = mpsc::unbounded();
+                for _ in 0..max.max(1) {
+                    let _ = release.unbounded_send(());
+                }
+                InFlight {
+                    permits: futures::lock::Mutex::new(permits),
+                    release,
+                }
+            }),
+            interval: limit
+                .requests_per_second
+                .filter(|rate| *rate > 0.)
+                .map(|rate| Duration::from_secs_f64(1. / rate)),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Waits until a request can be sent, the returned permit is held until its response is
+    /// read
+    pub(crate) async fn acquire(&self) -> Result<Option<Permit>, ParseError> {
+        let permit = match &self.in_flight {
+            Some(in_flight) => {
+                // The limiter holds a sender, the channel is never closed
+                in_flight.permits.lock().await.next().await;
+                Some(Permit(in_flight.release.clone()))
+            }
+            None => None,
+        };
+        if let Some(interval) = self.interval {
+            let slot = {
+                let mut next = self.next.lock().unwrap();
+                let now = Instant::now();
+                let slot = next.map_or(now, |next| next.max(now));
+                *next = Some(slot + interval);
+                slot
+            };
+            runtime::get()?
+                .sleep(slot.saturating_duration_since(Instant::now()))
+                .await;
+        }
+        Ok(permit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn limits() {
+        mock::runtime();
+        let limiter = Arc::new(Limiter::new(&RateLimit::in_flight(2)));
+        let first = limiter.acquire().await.unwrap();
+        let _second = limiter.acquire().await.unwrap();
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.unwrap().is_some() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        assert!(waiting.await.unwrap());
+
+        let limiter = Limiter::new(&RateLimit::per_second(100.));
+        let started = Instant::now();
+        for _ in 0..5 {
+            assert!(limiter.acquire().await.unwrap().is_none());
+        }
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::generic_esl::GenericEsl;
+use crate::parse::ParseError;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// The columns whose changes are always pushed: the price and the mandatory mentions
+pub const ESSENTIAL_FIELDS: &[&str] = &[
+    "prix",
+    "infosPrix",
+    "plu",
+    "nom",
+    "nomScientifique",
+    "origine",
+    "engin",
+    "zone",
+    "sousZone",
+    "production",
+    "taille",
+    "allergenes",
+    "congelInfos",
+];
+
+/// Columns that do not change what a label displays
+const IGNORED_FIELDS: &[&str] = &["objectId", "printed"];
+
+/// How often labels may be refreshed, e-paper refreshes draining their batteries
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshPolicy {
+    /// The refreshes allowed per label and per day (UTC) for cosmetic changes, changes of
+    /// essential fields are pushed even beyond
+    pub max_refreshes_per_day: u32,
+    /// The Parse columns whose changes are essential, see [`ESSENTIAL_FIELDS`]
+    pub essential_fields: Vec<String>,
+}
+
+impl Default for RefreshPolicy {
+    /// 6 refreshes a day, with [`ESSENTIAL_FIELDS`]
+    fn default() -> Self {
+        Self {
+            max_refreshes_per_day: 6,
+            essential_fields: ESSENTIAL_FIELDS.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+}
+
+/// What to do with a label waiting to be pushed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshDecision {
+    Push,
+    /// Only cosmetic changes and the daily budget is spent, push it another day
+    Defer,
+    /// The label would display the same thing, it does not need a refresh
+    Unchanged,
+}
+
+/// The refreshes of a label
+#[derive(Clone, Debug)]
+struct Refreshes {
+    displayed: Map<String, Value>,
+    last_refresh: DateTime<Utc>,
+    day: NaiveDate,
+    count: u32,
+}
+
+/// Applies a [`RefreshPolicy`] to the labels pushed by a gateway.
+///
+/// Call [`RefreshTracker::decide`] before pushing a label and [`RefreshTracker::pushed`] once
+/// the push succeeded. The labels are only tracked in memory, a restarted gateway pushes each
+/// label once before limiting it again.
+#[derive(Clone, Debug, Default)]
+pub struct RefreshTracker {
+    policy: RefreshPolicy,
+    labels: HashMap<String, Refreshes>,
+}
+
+fn displayed(esl: &GenericEsl) -> Result<Map<String, Value>, ParseError> {
+    let Value::Object(mut fields) = serde_json::to_value(esl)? else {
+        unreachable!("a GenericEsl serializes to an object")
+    };
+    fields.retain(|field, _| !IGNORED_FIELDS.contains(&field.as_str()));
+    Ok(fields)
+}
+
+impl RefreshTracker {
+    pub fn new(policy: RefreshPolicy) -> Self {
+        Self {
+            policy,
+            labels: HashMap::new(),
+        }
+    }
+
+    /// The refreshes of a label on the day of `now`
+    fn count(&self, esl_id: &str, now: DateTime<Utc>) -> u32 {
+        match self.labels.get(esl_id) {
+            Some(refreshes) if refreshes.day == now.date_naive() => refreshes.count,
+            _ => 0,
+        }
+    }
+
+    /// Decides whether a label should be pushed at `now`, given what it displays
+    pub fn decide(
+        &self,
+        esl: &GenericEsl,
+        now: DateTime<Utc>,
+    ) -> Result<RefreshDecision, ParseError> {
+        let Some(refreshes) = self.labels.get(&esl.id) else {
+            return Ok(RefreshDecision::Push);
+        };
+        let current = displayed(esl)?;
+        let changed: Vec<&String> = current
+            .keys()
+            .chain(refreshes.displayed.keys())
+            .filter(|field| current.get(*field) != refreshes.displayed.get(*field))
+            .collect();
+        if changed.is_empty() {
+            Ok(RefreshDecision::Unchanged)
+        } else if changed
+            .iter()
+            .any(|field| self.policy.essential_fields.contains(field))
+            || self.count(&esl.id, now) < self.policy.max_refreshes_per_day
+        {
+            Ok(RefreshDecision::Push)
+        } else {
+            Ok(RefreshDecision::Defer)
+        }
+    }
+
+    /// Records that a label has been pushed at `now`
+    pub fn pushed(&mut self, esl: &GenericEsl, now: DateTime<Utc>) -> Result<(), ParseError> {
+        let count = self.count(&esl.id, now) + 1;
+        let refreshes = Refreshes {
+            displayed: displayed(esl)?,
+            last_refresh: now,
+            day: now.date_naive(),
+            count,
+        };
+        self.labels.insert(esl.id.clone(), refreshes);
+        Ok(())
+    }
+
+    /// Returns the labels refreshed more than the daily budget on the day of `now`, with their
+    /// number of refreshes, the most refreshed first
+    pub fn over_budget(&self, now: DateTime<Utc>) -> Vec<(String, u32)> {
+        let mut labels: Vec<(String, u32)> = self
+            .labels
+            .keys()
+            .map(|esl_id| (esl_id.clone(), self.count(esl_id, now)))
+            .filter(|(_, count)| *count > self.policy.max_refreshes_per_day)
+            .collect();
+        labels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        labels
+    }
+
+    /// Returns the labels not refreshed since a date, the least recently refreshed first
+    pub fn idle(&self, since: DateTime<Utc>) -> Vec<String> {
+        let mut labels: Vec<(&String, DateTime<Utc>)> = self
+            .labels
+            .iter()
+            .filter(|(_, refreshes)| refreshes.last_refresh < since)
+            .map(|(esl_id, refreshes)| (esl_id, refreshes.last_refresh))
+            .collect();
+        labels.sort_by_key(|(esl_id, last_refresh)| (*last_refresh, esl_id.to_string()));
+        labels
+            .into_iter()
+            .map(|(esl_id, _)| esl_id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn policy() {
+        let mut tracker = RefreshTracker::new(RefreshPolicy {
+            max_refreshes_per_day: 1,
+            ..RefreshPolicy::default()
+        });
+        let morning = Utc.with_ymd_and_hms(2023, 6, 1, 8, 0, 0).unwrap();
+        let esl = mock::esl();
+        assert_eq!(
+            tracker.decide(&esl, morning).unwrap(),
+            RefreshDecision::Push
+        );
+        tracker.pushed(&esl, morning).unwrap();
+
+        let printed = GenericEsl {
+            printed: true,
+            ..esl.clone()
+        };
+        assert_eq!(
+            tracker.decide(&printed, morning).unwrap(),
+            RefreshDecision::Unchanged
+        );
+        let cosmetic = GenericEsl {
+            stock: Some(3),
+            ..esl.clone()
+        };
+        assert_eq!(
+            tracker.decide(&cosmetic, morning).unwrap(),
+            RefreshDecision::Defer
+        );
+        assert_eq!(
+            tracker
+                .decide(&cosmetic, morning + Duration::days(1))
+                .unwrap(),
+            RefreshDecision::Push
+        );
+        let repriced = GenericEsl {
+            prix: "12,90".to_string(),
+            ..cosmetic
+        };
+        assert_eq!(
+            tracker.decide(&repriced, morning).unwrap(),
+            RefreshDecision::Push
+        );
+        tracker.pushed(&repriced, morning).unwrap();
+        assert_eq!(tracker.over_budget(morning), vec![("esl".to_string(), 2)]);
+        assert!(tracker.over_budget(morning + Duration::days(1)).is_empty());
+
+        assert!(tracker.idle(morning).is_empty());
+        assert_eq!(tracker.idle(morning + Duration::days(7)), vec!["esl"]);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::parse::ParseError;
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, StatusCode};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The header Parse Server deduplicates the requests by, when its `idempotencyOptions` are
+/// set: the retries of a request carry the id of its first attempt
+pub const REQUEST_ID_HEADER: &str = "X-Parse-Request-Id";
+
+/// How failed requests are retried, see [`ParseClient::with_retry_policy`].
+///
+/// Only the failures that can succeed on a second try are retried: `5xx` and `429 Too Many
+/// Requests` responses, timeouts and connection errors. The delay doubles after each attempt,
+/// from `base_delay` up to `max_delay`, or is the one asked by a `Retry-After` header.
+///
+/// [`ParseClient::with_retry_policy`]: crate::parse::ParseClient::with_retry_policy
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The attempts made in total, the first one included
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Waits a random delay between half and all of the backoff, so the gateways of a store
+    /// cut off together do not retry at the same time
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// 4 attempts, waiting 200ms, 400ms and 800ms with jitter
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay to wait after a failed attempt, the first one being 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return backoff;
+        }
+        let random = (Uuid::new_v4().as_u128() % 1000) as u32;
+        backoff / 2 + (backoff / 2) * random / 1000
+    }
+
+    /// Returns the delay to wait after a failed attempt whose response asked to wait
+    /// `retry_after`, none when it asked for more than `max_delay`
+    pub fn delay_after(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        let delay = self.delay(attempt);
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_delay => None,
+            Some(retry_after) => Some(delay.max(retry_after)),
+            None => Some(delay),
+        }
+    }
+
+    /// Returns true if a response status is worth a retry
+    pub fn retries_status(&self, status: StatusCode) -> bool {
+        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Returns true if a failure to get a response is worth a retry
+    pub fn retries_error(&self, error: &ParseError) -> bool {
+        match error {
+            ParseError::Reqwest { source } => source.is_timeout() || source.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+/// Returns how long a response asks to wait before the next request, from its `Retry-After`
+/// header: a number of seconds or an HTTP date
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers
+        .get(http::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            max_delay: Duration::from_millis(500),
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+        let jittered = RetryPolicy::default().delay(2);
+        assert!(jittered >= Duration::from_millis(200) && jittered <= Duration::from_millis(400));
+        assert!(policy.retries_status(StatusCode::BAD_GATEWAY));
+        assert!(!policy.retries_status(StatusCode::NOT_FOUND));
+        assert!(policy.retries_status(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(
+            policy.delay_after(1, Some(Duration::from_millis(300))),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(policy.delay_after(1, Some(Duration::from_secs(60))), None);
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+        headers.insert(http::header::RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(120)));
+        headers.insert(
+            http::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:30 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(30)));
+        headers.insert(http::header::RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), None);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::acl::Acl;
+use crate::parse::{ParseClient, ParseError};
+use crate::pointer::{ParseClass, Pointer, Relation};
+use crate::user::ParseUser;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The path of the roles on the Parse API
+pub const ROLE_PATH: &str = "roles";
+
+fn user_pointers(user_ids: &[&str]) -> Vec<Pointer<ParseUser>> {
+    user_ids.iter().map(|id| Pointer::new(id)).collect()
+}
+
+fn role_pointers(roles: &[&ParseRole]) -> Result<Vec<Pointer<ParseRole>>, ParseError> {
+    roles
+        .iter()
+        .map(|role| {
+            let object_id = role.object_id.as_ref().ok_or(ParseError::ObectId)?;
+            Ok(Pointer::new(object_id))
+        })
+        .collect()
+}
+
+/// A Parse role, such as `fishmonger`, granting its permissions to its users and to the users of
+/// its child roles.
+///
+/// Managing roles usually requires the master key, see [`ParseClient::as_master`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseRole {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub name: String,
+    /// Who can read and modify the role itself, Parse requires one
+    #[serde(rename = "ACL")]
+    pub acl: Acl,
+}
+
+impl ParseRole {
+    /// Returns the path of this role on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", ROLE_PATH, object_id))
+    }
+
+    /// Creates a role without users
+    pub async fn create(client: &ParseClient, name: &str, acl: Acl) -> Result<Self, ParseError> {
+        let mut role = Self {
+            object_id: None,
+            name: name.to_string(),
+            acl,
+        };
+        let created = client.save(ROLE_PATH.to_string(), &role).await?;
+        role.object_id = Some(created.object_id);
+        Ok(role)
+    }
+
+    /// Finds a role by its name
+    pub async fn find_by_name(
+        client: &ParseClient,
+        name: &str,
+    ) -> Result<Option<Self>, ParseError> {
+        let roles: Vec<Self> = client
+            .fetch(ROLE_PATH.to_string(), json!({ "name": name }))
+            .await?;
+        Ok(roles.into_iter().next())
+    }
+
+    /// Returns the roles a user belongs to directly, not through a child role
+    pub async fn of_user(client: &ParseClient, user_id: &str) -> Result<Vec<Self>, ParseError> {
+        client
+            .fetch(
+                ROLE_PATH.to_string(),
+                json!({ "users": Pointer::<ParseUser>::new(user_id) }),
+            )
+            .await
+    }
+
+    async fn relation(
+        &self,
+        client: &ParseClient,
+        field: &str,
+        op: Value,
+    ) -> Result<(), ParseError> {
+        client.update(self.path()?, json!({ field: op })).await
+    }
+
+    /// Adds users to this role, by their objectId
+    pub async fn add_users(
+        &self,
+        client: &ParseClient,
+        user_ids: &[&str],
+    ) -> Result<(), ParseError> {
+        let users = Relation::add(&user_pointers(user_ids));
+        self.relation(client, "users", users).await
+    }
+
+    pub async fn remove_users(
+        &self,
+        client: &ParseClient,
+        user_ids: &[&str],
+    ) -> Result<(), ParseError> {
+        let users = Relation::remove(&user_pointers(user_ids));
+        self.relation(client, "users", users).await
+    }
+
+    /// Adds child roles: their users get the permissions of this role
+    pub async fn add_roles(
+        &self,
+        client: &ParseClient,
+        roles: &[&ParseRole],
+    ) -> Result<(), ParseError> {
+        let roles = Relation::add(&role_pointers(roles)?);
+        self.relation(client, "roles", roles).await
+    }
+
+    pub async fn remove_roles(
+        &self,
+        client: &ParseClient,
+        roles: &[&ParseRole],
+    ) -> Result<(), ParseError> {
+        let roles = Relation::remove(&role_pointers(roles)?);
+        self.relation(client, "roles", roles).await
+    }
+
+    pub async fn delete(self, client: &ParseClient) -> Result<(), ParseError> {
+        client.delete(self.path()?).await
+    }
+}
+
+impl ParseClass for ParseRole {
+    const CLASS_NAME: &'static str = "_Role";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn roles() {
+        let (url, server) = mock::serve(vec![
+            mock::response("201 Created", &[], r#"{"createdAt":"now","objectId":"r1"}"#),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+            mock::response(
+                "200 OK",
+                &[],
+                r#"{"results":[{"objectId":"r2","name":"manager","ACL":{"*":{"read":true}}}]}"#,
+            ),
+            mock::response("200 OK", &[], r#"{"updatedAt":"now"}"#),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let fishmonger = ParseRole::create(&client, "fishmonger", Acl::new().public(true, false))
+            .await
+            .unwrap();
+        assert_eq!(fishmonger.object_id.as_deref(), Some("r1"));
+        fishmonger.add_users(&client, &["u1", "u2"]).await.unwrap();
+        let managers = ParseRole::of_user(&client, "u3").await.unwrap();
+        assert_eq!(managers[0].name, "manager");
+        fishmonger
+            .add_roles(&client, &[&managers[0]])
+            .await
+            .unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /roles"));
+        assert!(requests[0].contains(r#""ACL":{"*":{"read":true}}"#));
+        assert!(requests[1].starts_with("PUT /roles/r1"));
+        assert!(requests[1].contains(r#""__op":"AddRelation""#));
+        assert!(requests[1].contains(r#""className":"_User","objectId":"u2""#));
+        assert!(requests[2].contains("%22className%22%3A%22_User%22"));
+        assert!(requests[3].contains(r#""roles":{"__op":"AddRelation""#));
+        assert!(requests[3].contains(r#""className":"_Role","objectId":"r2""#));
+    }
+}
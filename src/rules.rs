@@ -0,0 +1,329 @@
+use crate::generic_esl::GenericEsl;
+use crate::mentions::{CongelState, Mention, Production, Violation};
+use crate::parse::ParseError;
+use crate::price;
+use crate::store_config::StoreConfig;
+use serde::{Deserialize, Serialize};
+
+/// The EU consumer information rules on fishery and aquaculture products
+const EU_1379_2013: &str = "Règlement (UE) n° 1379/2013, art. 35";
+
+/// A mention required on the labels matching some conditions.
+///
+/// Empty conditions match every label: a rule without `production` applies whatever the
+/// production method.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    /// A unique id in its rule set, such as `sous-zone`
+    pub id: String,
+    pub mention: Mention,
+    /// The production methods the rule applies to
+    #[serde(default)]
+    pub production: Vec<Production>,
+    /// The FAO zone codes the rule applies to
+    #[serde(default)]
+    pub zone_codes: Vec<String>,
+    /// Applies to thawed products only
+    #[serde(default)]
+    pub thawed: bool,
+    /// The text the rule comes from, reported with its violations
+    pub source: String,
+}
+
+impl Rule {
+    fn new(id: &str, mention: Mention, source: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            mention,
+            production: vec![],
+            zone_codes: vec![],
+            thawed: false,
+            source: source.to_string(),
+        }
+    }
+
+    fn production(mut self, production: &[Production]) -> Self {
+        self.production = production.to_vec();
+        self
+    }
+
+    /// Returns true if the rule applies to a label
+    pub fn applies(&self, esl: &GenericEsl) -> bool {
+        let production = esl.production.as_deref().and_then(Production::parse);
+        let zone_code = esl.zone_code.as_deref().map(str::trim);
+        (self.production.is_empty() || production.is_some_and(|p| self.production.contains(&p)))
+            && (self.zone_codes.is_empty()
+                || zone_code.is_some_and(|code| self.zone_codes.iter().any(|c| c == code)))
+            && (!self.thawed || CongelState::of(esl) == CongelState::Decongele)
+    }
+}
+
+/// The labelling rules of a country, or a custom set extending them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RuleSet {
+    /// The name of the set, such as `fr`, prefixing the rule ids in the violations
+    pub name: String,
+    /// The name of the set whose rules are applied first, for custom sets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// The mentions of EU regulation 1379/2013, shared by every member state
+fn eu_rules() -> Vec<Rule> {
+    use Production::*;
+    vec![
+        Rule::new("denomination", Mention::Denomination, EU_1379_2013),
+        Rule::new("nom-scientifique", Mention::NomScientifique, EU_1379_2013),
+        Rule::new("production", Mention::Production, EU_1379_2013),
+        Rule::new("zone", Mention::Zone, EU_1379_2013).production(&[Peche]),
+        Rule {
+            zone_codes: vec!["27".to_string(), "37".to_string()],
+            ..Rule::new("sous-zone", Mention::SousZone, EU_1379_2013).production(&[Peche])
+        },
+        Rule::new("origine", Mention::Origine, EU_1379_2013).production(&[PecheEauDouce, Elevage]),
+        Rule::new("engin", Mention::Engin, EU_1379_2013).production(&[Peche, PecheEauDouce]),
+        Rule {
+            thawed: true,
+            ..Rule::new("decongele", Mention::Decongele, EU_1379_2013)
+        },
+    ]
+}
+
+impl RuleSet {
+    fn national(name: &str, price_rule: &str) -> Self {
+        let mut rules = eu_rules();
+        rules.push(Rule::new("prix", Mention::Prix, price_rule));
+        Self {
+            name: name.to_string(),
+            extends: None,
+            rules,
+        }
+    }
+
+    /// The French rules
+    pub fn fr() -> Self {
+        Self::national(
+            "fr",
+            "Arrêté du 3 décembre 1987 relatif à l'information du consommateur sur les prix",
+        )
+    }
+
+    /// The Belgian rules
+    pub fn be() -> Self {
+        Self::national("be", "Code de droit économique, livre VI, art. VI.4")
+    }
+
+    /// The Spanish rules
+    pub fn es() -> Self {
+        Self::national("es", "Real Decreto 3423/2000, de indicación de precios")
+    }
+
+    /// Reads a custom rule set, such as:
+    ///
+    /// ```toml
+    /// name = "be-bio"
+    /// extends = "be"
+    ///
+    /// [[rules]]
+    /// id = "origine-peche"
+    /// mention = "origine"
+    /// production = ["peche"]
+    /// source = "Cahier des charges bio, art. 4"
+    /// ```
+    pub fn from_toml(toml: &str) -> Result<Self, ParseError> {
+        toml::from_str(toml).map_err(|e| ParseError::Import {
+            line: e
+                .span()
+                .map_or(0, |span| toml[..span.start].lines().count().max(1)),
+            cause: e.message().to_string(),
+        })
+    }
+
+    /// Returns the mentions that must be rendered on the label of an Esl, in the order of the
+    /// rules
+    pub fn required_mentions(&self, esl: &GenericEsl) -> Vec<Mention> {
+        let mut mentions = vec![];
+        for rule in self.rules.iter().filter(|rule| rule.applies(esl)) {
+            if !mentions.contains(&rule.mention) {
+                mentions.push(rule.mention);
+            }
+        }
+        mentions
+    }
+
+    /// Checks that an Esl carries every mention required by the rules, each violation names the
+    /// rule it breaks
+    pub fn validate(&self, esl: &GenericEsl) -> Vec<Violation> {
+        let mut violations: Vec<Violation> = vec![];
+        for rule in self.rules.iter().filter(|rule| rule.applies(esl)) {
+            if violations.iter().any(|v| v.mention == rule.mention) {
+                continue;
+            }
+            if let Some(message) = check(esl, rule.mention) {
+                violations.push(Violation {
+                    mention: rule.mention,
+                    message: message.to_string(),
+                    rule: format!("{}/{}", self.name, rule.id),
+                    source: rule.source.clone(),
+                });
+            }
+        }
+        violations
+    }
+}
+
+fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().is_none_or(|value| value.trim().is_empty())
+}
+
+/// Returns why an Esl does not carry a mention, if it does not
+fn check(esl: &GenericEsl, mention: Mention) -> Option<&'static str> {
+    match mention {
+        Mention::Denomination if esl.nom.trim().is_empty() => Some("the name is empty"),
+        Mention::NomScientifique if esl.nom_scientifique.trim().is_empty() => {
+            Some("the scientific name is empty")
+        }
+        Mention::Production if is_blank(&esl.production) => {
+            Some("the production method is missing")
+        }
+        Mention::Production
+            if esl
+                .production
+                .as_deref()
+                .and_then(Production::parse)
+                .is_none() =>
+        {
+            Some("expected pêché, pêché en eaux douces or élevé")
+        }
+        Mention::Zone if is_blank(&esl.zone) || is_blank(&esl.zone_code) => {
+            Some("the FAO zone and its code are required")
+        }
+        Mention::SousZone if is_blank(&esl.sous_zone) => {
+            Some("the sub-area is required in FAO zones 27 and 37")
+        }
+        Mention::Origine if is_blank(&esl.origine) => Some("the country of production is missing"),
+        Mention::Engin if is_blank(&esl.engin) => Some("the fishing gear is missing"),
+        Mention::Prix if price::parse_cents(&esl.prix).is_none() => {
+            Some("the price is not a valid amount")
+        }
+        Mention::Prix if esl.infos_prix.trim().is_empty() => Some("the price unit is missing"),
+        _ => None,
+    }
+}
+
+/// The rule sets available to the stores: the built-in `fr`, `be` and `es` ones and the custom
+/// ones added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleSets {
+    sets: Vec<RuleSet>,
+}
+
+impl Default for RuleSets {
+    fn default() -> Self {
+        Self {
+            sets: vec![RuleSet::fr(), RuleSet::be(), RuleSet::es()],
+        }
+    }
+}
+
+impl RuleSets {
+    /// Adds a rule set, replacing the one with the same name.
+    ///
+    /// The rules of the set it extends come first, a rule with the id of one of them replaces
+    /// it.
+    pub fn with(mut self, set: RuleSet) -> Result<Self, ParseError> {
+        let set = match &set.extends {
+            Some(base) => {
+                let mut rules = self.get(base)?.rules.clone();
+                for rule in set.rules {
+                    match rules.iter_mut().find(|r| r.id == rule.id) {
+                        Some(existing) => *existing = rule,
+                        None => rules.push(rule),
+                    }
+                }
+                RuleSet { rules, ..set }
+            }
+            None => set,
+        };
+        self.sets.retain(|s| s.name != set.name);
+        self.sets.push(set);
+        Ok(self)
+    }
+
+    /// Returns a rule set by its name
+    pub fn get(&self, name: &str) -> Result<&RuleSet, ParseError> {
+        self.sets
+            .iter()
+            .find(|set| set.name == name)
+            .ok_or_else(|| ParseError::Query {
+                cause: format!("no rule set {name:?}"),
+            })
+    }
+
+    /// Returns the rule set of a store, the French one when its configuration has none
+    pub fn for_store(&self, config: Option<&StoreConfig>) -> Result<&RuleSet, ParseError> {
+        let name = config
+            .and_then(|config| config.rule_set.as_deref())
+            .unwrap_or("fr");
+        self.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn national() {
+        let esl = GenericEsl {
+            sous_zone: None,
+            ..mock::esl()
+        };
+        let violations = RuleSet::es().validate(&esl);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "es/sous-zone");
+        assert_eq!(violations[0].source, EU_1379_2013);
+        let esl = GenericEsl {
+            infos_prix: String::new(),
+            ..mock::esl()
+        };
+        assert!(RuleSet::be().validate(&esl)[0].source.contains("VI.4"));
+    }
+
+    #[test]
+    fn custom() {
+        let custom = RuleSet::from_toml(
+            r#"
+            name = "be-bio"
+            extends = "be"
+
+            [[rules]]
+            id = "origine-peche"
+            mention = "origine"
+            production = ["peche"]
+            source = "Cahier des charges"
+            "#,
+        )
+        .unwrap();
+        let sets = RuleSets::default().with(custom).unwrap();
+        let mut config = StoreConfig::new("serial".to_string());
+        assert_eq!(sets.for_store(Some(&config)).unwrap().name, "fr");
+        config.rule_set = Some("be-bio".to_string());
+        let set = sets.for_store(Some(&config)).unwrap();
+        let violations = set.validate(&mock::esl());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].mention, Mention::Origine);
+        assert_eq!(violations[0].rule, "be-bio/origine-peche");
+        assert_eq!(set.rules.len(), RuleSet::be().rules.len() + 1);
+
+        config.rule_set = Some("it".to_string());
+        assert!(sets.for_store(Some(&config)).is_err());
+        assert!(matches!(
+            RuleSet::from_toml("name = \"x\"\n[[rules]]\nid = 1"),
+            Err(ParseError::Import { line: 3, .. })
+        ));
+    }
+}
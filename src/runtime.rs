@@ -0,0 +1,165 @@
+use crate::parse::ParseError;
+use futures::future::{BoxFuture, Either};
+use futures::io::AsyncWrite;
+use std::future::Future;
+use std::io;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// A TCP connection opened by a [`Runtime`]
+pub type Connection = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// The timers, sockets, tasks and blocking threads of an async runtime.
+///
+/// The retries, the rate limits, the canary pushes, the printers and the supplier sources go
+/// through the runtime set by [`set`], tokio with the `tokio` feature when none is set. Implement
+/// it to run them on another runtime, such as async-std. The HTTP requests are sent by reqwest,
+/// which needs a tokio reactor: wrap them in a compatibility layer such as `async-compat`.
+pub trait Runtime: Send + Sync {
+    /// Waits for a duration
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+    /// Runs a task in the background, without waiting for it
+    fn spawn(&self, task: BoxFuture<'static, ()>);
+    /// Opens a TCP connection to an address such as `10.0.0.5:9100`
+    fn connect(&self, addr: &str) -> BoxFuture<'static, io::Result<Connection>>;
+    /// Runs a blocking call on a thread where blocking is allowed, the future completes when
+    /// the call returns
+    fn spawn_blocking(&self, call: Box<dyn FnOnce() + Send>) -> BoxFuture<'static, ()>;
+}
+
+static RUNTIME: OnceLock<Box<dyn Runtime>> = OnceLock::new();
+
+/// Sets the runtime used by the crate.
+///
+/// It can only be set once, before the first timer or connection: the runtime is given back
+/// when one is already set.
+pub fn set(runtime: Box<dyn Runtime>) -> Result<(), Box<dyn Runtime>> {
+    RUNTIME.set(runtime)
+}
+
+/// Returns the runtime set by [`set`], tokio when none is set.
+///
+/// Without the `tokio` feature, fails with [`ParseError::NoRuntime`] until [`set`] is called.
+pub fn get() -> Result<&'static dyn Runtime, ParseError> {
+    #[cfg(feature = "tokio")]
+    let runtime = Some(RUNTIME.get_or_init(|| Box::new(TokioRuntime)));
+    #[cfg(not(feature = "tokio"))]
+    let runtime = RUNTIME.get();
+    runtime.map(Box::as_ref).ok_or(ParseError::NoRuntime)
+}
+
+/// Waits for a future at most `duration`, none when it did not complete in time
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<Option<F::Output>, ParseError> {
+    let sleep = get()?.sleep(duration);
+    Ok(
+        match futures::future::select(std::pin::pin!(future), sleep).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        },
+    )
+}
+
+/// Runs a blocking call with [`Runtime::spawn_blocking`] and returns its result
+pub(crate) async fn blocking<T, F>(call: F) -> Result<T, ParseError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    get()?
+        .spawn_blocking(Box::new(move || {
+            let _ = sender.send(call());
+        }))
+        .await;
+    // The sender is dropped without a value when the call panicked
+    receiver.await.map_err(|e| ParseError::Io {
+        source: io::Error::other(e),
+    })
+}
+
+/// The tokio runtime, the one the crate is run on when no other is set
+#[cfg(any(feature = "tokio", test))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioRuntime;
+
+#[cfg(any(feature = "tokio", test))]
+impl Runtime for TokioRuntime {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn spawn(&self, task: BoxFuture<'static, ()>) {
+        tokio::spawn(task);
+    }
+
+    fn connect(&self, addr: &str) -> BoxFuture<'static, io::Result<Connection>> {
+        let addr = addr.to_string();
+        Box::pin(async move {
+            let stream = tokio::net::TcpStream::connect(addr).await?;
+            Ok(Box::new(TokioConnection(stream)) as Connection)
+        })
+    }
+
+    fn spawn_blocking(&self, call: Box<dyn FnOnce() + Send>) -> BoxFuture<'static, ()> {
+        let task = tokio::task::spawn_blocking(call);
+        Box::pin(async {
+            let _ = task.await;
+        })
+    }
+}
+
+/// A tokio stream seen as a `futures` one
+#[cfg(any(feature = "tokio", test))]
+struct TokioConnection(tokio::net::TcpStream);
+
+#[cfg(any(feature = "tokio", test))]
+impl AsyncWrite for TokioConnection {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(std::pin::Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(std::pin::Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(std::pin::Pin::new(&mut self.0), cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn tokio_runtime() {
+        mock::runtime();
+        let ran = timeout(Duration::from_secs(1), async { 1 }).await.unwrap();
+        assert_eq!(ran, Some(1));
+        let pending = futures::future::pending::<()>();
+        let ran = timeout(Duration::from_millis(5), pending).await.unwrap();
+        assert_eq!(ran, None);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        get().unwrap().spawn(Box::pin(async {
+            let _ = sender.send(3);
+        }));
+        assert_eq!(receiver.await, Ok(3));
+        assert_eq!(blocking(|| 2).await.unwrap(), 2);
+        let panicked: Result<(), _> = blocking(|| panic!("failed")).await;
+        assert!(panicked.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::date;
+use crate::gateway::{Gateway, GATEWAY_CLASS};
+use crate::parse::{ParseClient, ParseError};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// The number of points of each gateway on the ring
+pub const DEFAULT_VNODES: usize = 64;
+
+/// Returns a hash that is the same on every gateway, whatever its platform or version
+fn hash(key: &str) -> u64 {
+    let digest = Sha256::digest(key.as_bytes());
+    u64::from_be_bytes(
+        digest[..8]
+            .try_into()
+            .expect("a SHA-256 has more than 8 bytes"),
+    )
+}
+
+/// Assigns labels to the gateways of a store by consistent hashing on their `eslId`.
+///
+/// Every gateway computes the same assignment from the same members, so no coordination is
+/// needed besides agreeing on the members. When a gateway joins or leaves, only the labels it
+/// owns or takes over move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashRing {
+    vnodes: usize,
+    members: Vec<String>,
+    ring: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    pub fn new(members: &[String], vnodes: usize) -> Self {
+        let mut ring = Self {
+            vnodes: vnodes.max(1),
+            members: vec![],
+            ring: BTreeMap::new(),
+        };
+        ring.rebalance(members);
+        ring
+    }
+
+    pub fn members(&self) -> &[String] {
+        &self.members
+    }
+
+    /// Replaces the members of the ring, returns true if they changed
+    pub fn rebalance(&mut self, members: &[String]) -> bool {
+        let mut members = members.to_vec();
+        members.sort();
+        members.dedup();
+        if members == self.members {
+            return false;
+        }
+        self.ring = members
+            .iter()
+            .flat_map(|member| {
+                (0..self.vnodes)
+                    .map(move |vnode| (hash(&format!("{member}#{vnode}")), member.clone()))
+            })
+            .collect();
+        self.members = members;
+        true
+    }
+
+    /// Returns the gateway owning a label, `None` when the ring has no members
+    pub fn owner(&self, esl_id: &str) -> Option<&str> {
+        let point = hash(esl_id);
+        self.ring
+            .range(point..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, member)| member.as_str())
+    }
+
+    /// Returns true if a gateway has to push a label
+    pub fn owns(&self, gateway_id: &str, esl_id: &str) -> bool {
+        self.owner(esl_id) == Some(gateway_id)
+    }
+}
+
+/// Returns the ids of the gateways of a store seen since `since`.
+///
+/// Gateways announce themselves with [`Gateway::heartbeat`], the ones that stop doing so leave
+/// the ring and their labels are taken over by the others.
+pub async fn members(
+    client: &ParseClient,
+    serial: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<String>, ParseError> {
+    let gateways: Vec<Gateway> = client
+        .fetch(
+            GATEWAY_CLASS.to_string(),
+            json!({ "serial": serial, "lastSeen": { "$gte": date::date(&since) } }),
+        )
+        .await?;
+    Ok(gateways
+        .into_iter()
+        .map(|gateway| gateway.gateway_id)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn ids(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("esl-{i}")).collect()
+    }
+
+    #[test]
+    fn ring() {
+        let gateways = vec!["gw-a".to_string(), "gw-b".to_string(), "gw-c".to_string()];
+        let mut ring = HashRing::new(&gateways, DEFAULT_VNODES);
+        let esls = ids(3000);
+        let owners: Vec<String> = esls
+            .iter()
+            .map(|id| ring.owner(id).unwrap().to_string())
+            .collect();
+        for gateway in &gateways {
+            let owned = owners.iter().filter(|owner| *owner == gateway).count();
+            assert!(owned > 600, "{gateway} owns {owned} labels");
+        }
+        assert!(ring.owns(&owners[0], &esls[0]));
+
+        let reversed: Vec<String> = gateways.iter().rev().cloned().collect();
+        assert!(!ring.rebalance(&reversed));
+        assert!(ring.rebalance(&gateways[..2]));
+        for (id, owner) in esls.iter().zip(&owners) {
+            if owner != "gw-c" {
+                assert_eq!(ring.owner(id), Some(owner.as_str()));
+            } else {
+                assert_ne!(ring.owner(id), Some("gw-c"));
+            }
+        }
+        assert_eq!(HashRing::new(&[], DEFAULT_VNODES).owner("esl-0"), None);
+    }
+
+    #[tokio::test]
+    async fn members() {
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &[],
+            r#"{"results":[{"objectId":"g1","gatewayId":"gw-a","serial":"s1","secretHash":"h"}]}"#,
+        )]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let since = DateTime::parse_from_rfc3339("2023-06-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let members = super::members(&client, "s1", since).await.unwrap();
+        assert_eq!(members, vec!["gw-a"]);
+        let request = &server.join().unwrap()[0];
+        assert!(request.contains("%22%24gte%22%3A%7B%22__type%22%3A%22Date%22"));
+        assert!(request.contains("%222023-06-01T08%3A00%3A00.000Z%22"));
+    }
+}
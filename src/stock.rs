@@ -0,0 +1,230 @@
+use crate::csv::{field, Csv};
+use crate::generic_esl::GenericEsl;
+use crate::parse::ParseError;
+#[cfg(feature = "postgres")]
+use bb8::Pool;
+#[cfg(feature = "postgres")]
+use bb8_postgres::PostgresConnectionManager;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use std::collections::HashMap;
+#[cfg(feature = "postgres")]
+use tokio_postgres::NoTls;
+
+/// The stock of a product reported by the POS/stock system
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StockUpdate {
+    pub plu: String,
+    pub stock: i32,
+    /// The date of the next delivery, if one is planned
+    pub arrivage: Option<NaiveDate>,
+}
+
+/// A stock-driven message displayed on a label
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StockBanner {
+    /// "Arrivage demain": the product is (almost) sold out and delivered tomorrow
+    ArrivageDemain,
+    /// "Dernier lot": only a few units are left
+    DernierLot,
+}
+
+impl StockBanner {
+    pub fn text(&self) -> &'static str {
+        match self {
+            StockBanner::ArrivageDemain => "Arrivage demain",
+            StockBanner::DernierLot => "Dernier lot",
+        }
+    }
+}
+
+/// Returns the banner to display for a stock level.
+///
+/// `low_stock` is the quantity under which (inclusive) the stock is considered low.
+pub fn banner(
+    stock: Option<i32>,
+    arrivage: Option<NaiveDate>,
+    today: NaiveDate,
+    low_stock: i32,
+) -> Option<StockBanner> {
+    let stock = stock?;
+    if stock > low_stock {
+        return None;
+    }
+    if arrivage.is_some() && arrivage == today.succ_opt() {
+        Some(StockBanner::ArrivageDemain)
+    } else if stock > 0 {
+        Some(StockBanner::DernierLot)
+    } else {
+        None
+    }
+}
+
+/// Returns the banner to display on an Esl
+pub fn esl_banner(esl: &GenericEsl, today: NaiveDate, low_stock: i32) -> Option<StockBanner> {
+    banner(esl.stock, esl.arrivage, today, low_stock)
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%d/%m/%Y"))
+        .ok()
+}
+
+/// Parses a stock export with a `plu;stock;arrivage` header.
+///
+/// Columns may be separated by `;` or `,`, they can be in any order and `arrivage` is optional.
+/// Dates are either `2023-05-26` or `26/05/2023`.
+pub fn parse_csv(csv: &str) -> Result<Vec<StockUpdate>, ParseError> {
+    let csv = Csv::parse(csv)?;
+    let (plu, stock) = (csv.column("plu")?, csv.column("stock")?);
+    let arrivage = csv.optional("arrivage");
+    csv.rows
+        .iter()
+        .map(|(line, row)| {
+            let error = |cause: String| ParseError::Import { line: *line, cause };
+            let plu = field(row, plu);
+            if plu.is_empty() {
+                return Err(error("the PLU is empty".to_string()));
+            }
+            let stock = field(row, stock);
+            let stock = stock
+                .parse()
+                .map_err(|_| error(format!("invalid stock {stock:?}")))?;
+            let arrivage = match arrivage.map(|index| field(row, index)) {
+                None | Some("") => None,
+                Some(date) => {
+                    Some(parse_date(date).ok_or_else(|| error(format!("invalid date {date:?}")))?)
+                }
+            };
+            Ok(StockUpdate {
+                plu: plu.to_string(),
+                stock,
+                arrivage,
+            })
+        })
+        .collect()
+}
+
+/// Saves stock updates on the Esls of a serial.
+///
+/// Esls whose banner changes are marked as not printed so the banner is pushed, other Esls are
+/// left as they are. Returns the number of Esls to push again.
+#[cfg(feature = "postgres")]
+pub async fn apply_updates(
+    serial: &str,
+    updates: Vec<StockUpdate>,
+    today: NaiveDate,
+    low_stock: i32,
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+) -> Result<usize, ParseError> {
+    let plus: Vec<String> = updates.iter().map(|update| update.plu.clone()).collect();
+    let updates: HashMap<String, StockUpdate> = updates
+        .into_iter()
+        .map(|update| (update.plu.clone(), update))
+        .collect();
+    let mut reprinted = 0;
+    for esl in GenericEsl::find_by_plus(serial, &plus, pool.clone()).await? {
+        let update = &updates[&esl.plu];
+        if esl.stock == Some(update.stock) && esl.arrivage == update.arrivage {
+            continue;
+        }
+        let reprint = esl_banner(&esl, today, low_stock)
+            != banner(Some(update.stock), update.arrivage, today, low_stock);
+        if reprint {
+            reprinted += 1;
+        }
+        GenericEsl::set_stock(
+            esl,
+            Some(update.stock),
+            update.arrivage,
+            reprint,
+            pool.clone(),
+        )
+        .await?;
+    }
+    Ok(reprinted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    #[test]
+    fn banners() {
+        let today = date(26);
+        assert_eq!(banner(None, None, today, 3), None);
+        assert_eq!(banner(Some(10), Some(date(27)), today, 3), None);
+        assert_eq!(
+            banner(Some(2), None, today, 3),
+            Some(StockBanner::DernierLot)
+        );
+        assert_eq!(banner(Some(0), None, today, 3), None);
+        assert_eq!(
+            banner(Some(0), Some(date(27)), today, 3),
+            Some(StockBanner::ArrivageDemain)
+        );
+        assert_eq!(
+            banner(Some(1), Some(date(27)), today, 3),
+            Some(StockBanner::ArrivageDemain)
+        );
+        assert_eq!(
+            banner(Some(1), Some(date(29)), today, 3),
+            Some(StockBanner::DernierLot)
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let updates =
+            parse_csv("stock;PLU;arrivage\n12;1234;\n0;5678;27/05/2023\n\n3;9;2023-05-28\n")
+                .unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                StockUpdate {
+                    plu: "1234".to_string(),
+                    stock: 12,
+                    arrivage: None
+                },
+                StockUpdate {
+                    plu: "5678".to_string(),
+                    stock: 0,
+                    arrivage: Some(date(27))
+                },
+                StockUpdate {
+                    plu: "9".to_string(),
+                    stock: 3,
+                    arrivage: Some(date(28))
+                },
+            ]
+        );
+        assert_eq!(parse_csv("plu,stock\n1,2").unwrap()[0].stock, 2);
+    }
+
+    #[test]
+    fn csv_errors() {
+        assert!(matches!(
+            parse_csv(""),
+            Err(ParseError::Import { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("plu;arrivage\n1;"),
+            Err(ParseError::Import { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_csv("plu;stock\n1;2\n2;beaucoup"),
+            Err(ParseError::Import { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse_csv("plu;stock;arrivage\n1;2;demain"),
+            Err(ParseError::Import { line: 2, .. })
+        ));
+    }
+}
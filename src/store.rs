@@ -0,0 +1,392 @@
+use crate::generic_esl::GenericEsl;
+use crate::geo::GeoPoint;
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+#[cfg(feature = "postgres")]
+use bb8::Pool;
+#[cfg(feature = "postgres")]
+use bb8_postgres::PostgresConnectionManager;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+#[cfg(feature = "postgres")]
+use tokio_postgres::NoTls;
+use uuid::Uuid;
+
+/// The Parse class holding the store registry
+pub const STORE_CLASS: &str = "classes/Store";
+
+/// Mean radius of the earth, in meters
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// A store of the registry, identified by its serial.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Store {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub serial: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// The ids of the gateways allowed to push labels for this store
+    #[serde(default)]
+    pub gateways: Vec<String>,
+}
+
+/// The identity a gateway has been configured with
+#[derive(Clone, Debug, PartialEq)]
+pub struct GatewayIdentity {
+    pub gateway_id: String,
+    /// The serial of the store this gateway pushes labels to
+    pub serial: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl GatewayIdentity {
+    /// Returns the gateway identity by reading properties from the environment.
+    ///
+    /// * GATEWAY_ID
+    /// * GATEWAY_SERIAL
+    /// * GATEWAY_LATITUDE (optional)
+    /// * GATEWAY_LONGITUDE (optional)
+    pub fn from_env() -> Self {
+        let gateway_id = env::var("GATEWAY_ID").expect("env.GATEWAY_ID is undefined");
+        let serial = env::var("GATEWAY_SERIAL").expect("env.GATEWAY_SERIAL is undefined");
+        let coordinate = |name: &str| {
+            env::var(name).ok().map(|value| {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("env.{name} is not a number"))
+            })
+        };
+        Self {
+            gateway_id,
+            serial,
+            latitude: coordinate("GATEWAY_LATITUDE"),
+            longitude: coordinate("GATEWAY_LONGITUDE"),
+        }
+    }
+}
+
+impl Store {
+    pub fn new(serial: String, name: String) -> Self {
+        Self {
+            object_id: None,
+            serial,
+            name,
+            address: None,
+            latitude: None,
+            longitude: None,
+            gateways: vec![],
+        }
+    }
+
+    /// Returns the path of this store on the Parse API
+    pub(crate) fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", STORE_CLASS, object_id))
+    }
+
+    /// Returns the coordinates of the store as a GeoPoint, to attach them to its labels
+    pub fn location(&self) -> Option<GeoPoint> {
+        Some(GeoPoint::new(self.latitude?, self.longitude?))
+    }
+
+    /// Returns the distance in meters between the store and a position, if the store has
+    /// coordinates
+    pub fn distance(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        let (store_latitude, store_longitude) = (self.latitude?, self.longitude?);
+        let (lat1, lat2) = (store_latitude.to_radians(), latitude.to_radians());
+        let delta_lat = lat2 - lat1;
+        let delta_long = (longitude - store_longitude).to_radians();
+        let a = (delta_lat / 2.).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (delta_long / 2.).sin().powi(2);
+        Some(2. * EARTH_RADIUS * a.sqrt().asin())
+    }
+
+    /// Checks that a gateway is registered for this store.
+    ///
+    /// When both the store and the gateway have coordinates, the gateway must also be located
+    /// less than `max_distance` meters away from the store.
+    pub fn check_identity(
+        &self,
+        identity: &GatewayIdentity,
+        max_distance: f64,
+    ) -> Result<(), ParseError> {
+        let refuse = |cause: String| ParseError::Identity {
+            serial: self.serial.clone(),
+            cause,
+        };
+        if identity.serial != self.serial {
+            return Err(refuse(format!(
+                "the gateway is configured for {}",
+                identity.serial
+            )));
+        }
+        if !self.gateways.contains(&identity.gateway_id) {
+            return Err(refuse(format!(
+                "gateway {} is not registered for this store",
+                identity.gateway_id
+            )));
+        }
+        if let (Some(latitude), Some(longitude)) = (identity.latitude, identity.longitude) {
+            if let Some(distance) = self.distance(latitude, longitude) {
+                if distance > max_distance {
+                    return Err(refuse(format!(
+                        "the gateway is located {:.0}m away from the store",
+                        distance
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that an Esl belongs to this store before it is pushed
+    pub fn check_esl(&self, esl: &GenericEsl) -> Result<(), ParseError> {
+        if esl.serial != self.serial {
+            return Err(ParseError::Identity {
+                serial: esl.serial.clone(),
+                cause: format!("the gateway belongs to {}", self.serial),
+            });
+        }
+        Ok(())
+    }
+
+    /// Finds the registered store of a gateway and checks its identity.
+    ///
+    /// Pushes should be refused when this fails: it means the gateway is configured with the
+    /// serial of a store it does not belong to.
+    pub async fn verify(identity: &GatewayIdentity, max_distance: f64) -> Result<Self, ParseError> {
+        let store = Store::find(identity.serial.clone())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ParseError::Identity {
+                serial: identity.serial.clone(),
+                cause: "this serial is not in the store registry".to_string(),
+            })?;
+        store.check_identity(identity, max_distance)?;
+        Ok(store)
+    }
+}
+
+impl ParseClass for Store {
+    const CLASS_NAME: &'static str = "Store";
+}
+
+impl ParseObject for Store {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(STORE_CLASS.to_string(), self).await
+    }
+
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(STORE_CLASS.to_string(), json!({ "serial": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+/// The labels created by [`clone_store`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloneReport {
+    pub created: Vec<GenericEsl>,
+    /// The overridden PLUs the template store does not have
+    pub unknown_overrides: Vec<String>,
+}
+
+/// A [`clone_store`] that stopped on an error.
+///
+/// The copy is not atomic: the labels of the report were saved before the error and are not
+/// removed.
+#[derive(Debug)]
+pub struct CloneFailure {
+    pub report: CloneReport,
+    pub error: ParseError,
+}
+
+impl From<CloneFailure> for ParseError {
+    fn from(failure: CloneFailure) -> Self {
+        failure.error
+    }
+}
+
+/// Returns the copy of a template label for another store.
+///
+/// The copy is not saved nor bound to a physical label: it has no objectId nor `itemId`, and it
+/// is not printed. Its `eslId` is a new random id, to be replaced by the id of the physical
+/// label it is bound to. Its stock is left for the stock sync of the new store.
+pub fn template_esl(
+    esl: &GenericEsl,
+    to_serial: &str,
+    overrides: &HashMap<String, String>,
+) -> GenericEsl {
+    GenericEsl {
+        serial: to_serial.to_string(),
+        printed: false,
+        object_id: None,
+        item_id: None,
+        id: Uuid::new_v4().to_string(),
+        prix: overrides.get(&esl.plu).unwrap_or(&esl.prix).clone(),
+        stock: None,
+        arrivage: None,
+        geo_point: None,
+        ..esl.clone()
+    }
+}
+
+/// Copies the labels of a template store to a new store.
+///
+/// `overrides` holds the prices of the new store by PLU, the other labels keep the price of the
+/// template store. A failing save stops the copy with a [`CloneFailure`] reporting the labels
+/// already created.
+#[cfg(feature = "postgres")]
+pub async fn clone_store(
+    from_serial: &str,
+    to_serial: &str,
+    overrides: &HashMap<String, String>,
+    pool: Pool<PostgresConnectionManager<NoTls>>,
+) -> Result<CloneReport, CloneFailure> {
+    let mut report = CloneReport::default();
+    let template = match GenericEsl::find_by_serial(from_serial, pool.clone()).await {
+        Ok(template) => template,
+        Err(error) => return Err(CloneFailure { report, error }),
+    };
+    report.unknown_overrides = unknown_overrides(&template, overrides);
+    for esl in &template {
+        let esl = template_esl(esl, to_serial, overrides);
+        match GenericEsl::do_save(esl, pool.clone()).await {
+            Ok(esl) => report.created.push(esl),
+            Err(error) => return Err(CloneFailure { report, error }),
+        }
+    }
+    Ok(report)
+}
+
+/// Returns the overridden PLUs the labels of a template store do not have, sorted
+pub fn unknown_overrides(
+    template: &[GenericEsl],
+    overrides: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut unknown: Vec<String> = overrides
+        .keys()
+        .filter(|plu| !template.iter().any(|esl| &esl.plu == *plu))
+        .cloned()
+        .collect();
+    unknown.sort();
+    unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn store() -> Store {
+        let mut store = Store::new("serial".to_string(), "Marée de Lorient".to_string());
+        store.latitude = Some(47.7486);
+        store.longitude = Some(-3.3700);
+        store.gateways = vec!["gateway".to_string()];
+        store
+    }
+
+    fn identity() -> GatewayIdentity {
+        GatewayIdentity {
+            gateway_id: "gateway".to_string(),
+            serial: "serial".to_string(),
+            latitude: Some(47.7490),
+            longitude: Some(-3.3710),
+        }
+    }
+
+    #[test]
+    fn distance() {
+        let store = store();
+        // Lorient to Quimper is about 60km
+        let distance = store.distance(47.9960, -4.1025).unwrap();
+        assert!((distance - 60_000.).abs() < 5_000., "{distance}");
+        assert!(Store::new("serial".to_string(), "name".to_string())
+            .distance(0., 0.)
+            .is_none());
+    }
+
+    #[test]
+    fn check_identity() {
+        let store = store();
+        assert!(store.check_identity(&identity(), 500.).is_ok());
+
+        let other_serial = GatewayIdentity {
+            serial: "other".to_string(),
+            ..identity()
+        };
+        assert!(store.check_identity(&other_serial, 500.).is_err());
+
+        let unregistered = GatewayIdentity {
+            gateway_id: "other".to_string(),
+            ..identity()
+        };
+        assert!(store.check_identity(&unregistered, 500.).is_err());
+
+        let far_away = GatewayIdentity {
+            latitude: Some(47.9960),
+            longitude: Some(-4.1025),
+            ..identity()
+        };
+        assert!(store.check_identity(&far_away, 500.).is_err());
+
+        let no_coordinates = GatewayIdentity {
+            latitude: None,
+            longitude: None,
+            ..far_away
+        };
+        assert!(store.check_identity(&no_coordinates, 500.).is_ok());
+    }
+
+    #[test]
+    fn template() {
+        let esl = GenericEsl {
+            printed: true,
+            object_id: Some("abc".to_string()),
+            item_id: Some("item".to_string()),
+            stock: Some(3),
+            ..mock::esl()
+        };
+        let overrides = HashMap::from([
+            ("1234".to_string(), "19,90".to_string()),
+            ("9999".to_string(), "1,00".to_string()),
+        ]);
+        let copy = template_esl(&esl, "new", &overrides);
+        assert_eq!(copy.serial, "new");
+        assert_eq!(copy.prix, "19,90");
+        assert!(!copy.printed);
+        assert_eq!(
+            (copy.object_id, copy.item_id, copy.stock),
+            (None, None, None)
+        );
+        assert!(!copy.id.is_empty());
+        assert_ne!(copy.id, template_esl(&esl, "new", &overrides).id);
+        assert_eq!(copy.nom, esl.nom);
+        assert_eq!(template_esl(&esl, "new", &HashMap::new()).prix, "18,90");
+        assert_eq!(unknown_overrides(&[esl], &overrides), vec!["9999"]);
+    }
+}
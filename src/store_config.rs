@@ -0,0 +1,320 @@
+use crate::currency::{Currency, FixedRate};
+use crate::generic_esl::EslType;
+use crate::mentions::CongelState;
+use crate::parse::{ParseClient, ParseCreated, ParseError, ParseObject};
+use crate::pointer::ParseClass;
+use chrono::NaiveTime;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+/// The Parse class holding the per-store settings
+pub const STORE_CONFIG_CLASS: &str = "classes/StoreConfig";
+
+/// A period of the day, in the local time of the store, during which labels may be pushed. It
+/// may span midnight.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PushWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl PushWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// A discount applied to the labels of a category, or to every label without a category
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkdownRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categorie: Option<String>,
+    /// The discount in percent
+    pub discount: f64,
+}
+
+/// The settings of a store managed centrally in Parse.
+///
+/// Every setting is optional: a store without a StoreConfig object uses the defaults.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreConfig {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub serial: String,
+    /// When labels may be pushed, at any time when empty
+    #[serde(default)]
+    pub push_windows: Vec<PushWindow>,
+    #[serde(default)]
+    pub markdown_rules: Vec<MarkdownRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<String>,
+    /// The vendor the labels of the store are pushed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<EslType>,
+    /// The currency of the prices, euros when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// The rate of the second currency displayed next to the prices, CHF in Swiss border stores
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dual_currency: Option<FixedRate>,
+    /// The labelling rules of the store, such as `be`, see [`RuleSets::for_store`]
+    ///
+    /// [`RuleSets::for_store`]: crate::rules::RuleSets::for_store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_set: Option<String>,
+}
+
+impl StoreConfig {
+    /// The default settings of a store
+    pub fn new(serial: String) -> Self {
+        Self {
+            object_id: None,
+            serial,
+            push_windows: vec![],
+            markdown_rules: vec![],
+            template_version: None,
+            vendor: None,
+            currency: None,
+            dual_currency: None,
+            rule_set: None,
+        }
+    }
+
+    /// Returns the path of this configuration on the Parse API
+    fn path(&self) -> Result<String, ParseError> {
+        let object_id = self.object_id.as_ref().ok_or(ParseError::ObectId)?;
+        Ok(format!("{}/{}", STORE_CONFIG_CLASS, object_id))
+    }
+
+    /// Returns the currency of the prices of the store
+    pub fn currency(&self) -> Currency {
+        self.currency.unwrap_or(Currency::Eur)
+    }
+
+    /// Returns true if labels may be pushed at `time`
+    pub fn push_allowed(&self, time: NaiveTime) -> bool {
+        self.push_windows.is_empty() || self.push_windows.iter().any(|w| w.contains(time))
+    }
+
+    /// Returns the discount of the markdown rule of a category, falling back to the rule
+    /// without a category.
+    ///
+    /// Thawed products are never marked down, see [`CongelState::allows_markdown`].
+    pub fn markdown(&self, categorie: Option<&str>, congel: CongelState) -> Option<f64> {
+        if !congel.allows_markdown() {
+            return None;
+        }
+        let rule = |categorie: Option<&str>| {
+            self.markdown_rules
+                .iter()
+                .find(|rule| rule.categorie.as_deref() == categorie)
+        };
+        categorie
+            .and_then(|categorie| rule(Some(categorie)))
+            .or_else(|| rule(None))
+            .map(|rule| rule.discount)
+    }
+}
+
+impl ParseClass for StoreConfig {
+    const CLASS_NAME: &'static str = "StoreConfig";
+}
+
+impl ParseObject for StoreConfig {
+    async fn save(&self) -> Result<ParseCreated, ParseError> {
+        let client = ParseClient::global();
+        client.save(STORE_CONFIG_CLASS.to_string(), self).await
+    }
+
+    async fn find(serial: String) -> Result<Vec<Self>, ParseError> {
+        let client = ParseClient::global();
+        client
+            .fetch(STORE_CONFIG_CLASS.to_string(), json!({ "serial": serial }))
+            .await
+    }
+
+    async fn update(&mut self) -> Result<Self, ParseError> {
+        let client = ParseClient::global();
+        client.update(self.path()?, &*self).await?;
+        Ok(self.clone())
+    }
+
+    async fn delete(self) -> Result<(), ParseError> {
+        let client = ParseClient::global();
+        client.delete(self.path()?).await
+    }
+}
+
+/// The local file of a [`StoreConfigLoader`], which keeps the objectId Parse is not sent
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedConfig {
+    object_id: Option<String>,
+    #[serde(flatten)]
+    config: StoreConfig,
+}
+
+/// Keeps the configuration of a store up to date.
+///
+/// Gateways call [`StoreConfigLoader::reload`] periodically, for instance on every poll. Each
+/// configuration fetched is written to a local file, which is used instead when Parse cannot be
+/// reached so an offline gateway keeps its last known settings across restarts.
+#[derive(Clone, Debug)]
+pub struct StoreConfigLoader {
+    serial: String,
+    cache: PathBuf,
+    current: Option<StoreConfig>,
+}
+
+impl StoreConfigLoader {
+    pub fn new(serial: String, cache: PathBuf) -> Self {
+        Self {
+            serial,
+            cache,
+            current: None,
+        }
+    }
+
+    /// Returns the configuration loaded last, if any
+    pub fn current(&self) -> Option<&StoreConfig> {
+        self.current.as_ref()
+    }
+
+    async fn fetch(&self, client: &ParseClient) -> Result<StoreConfig, ParseError> {
+        let configs: Vec<StoreConfig> = client
+            .fetch(
+                STORE_CONFIG_CLASS.to_string(),
+                json!({ "serial": self.serial }),
+            )
+            .await?;
+        Ok(configs
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| StoreConfig::new(self.serial.clone())))
+    }
+
+    fn read_cache(&self) -> Result<StoreConfig, ParseError> {
+        let cached: CachedConfig = serde_json::from_slice(&fs::read(&self.cache)?)?;
+        Ok(StoreConfig {
+            object_id: cached.object_id,
+            ..cached.config
+        })
+    }
+
+    fn write_cache(&self, config: &StoreConfig) -> Result<(), ParseError> {
+        let cached = CachedConfig {
+            object_id: config.object_id.clone(),
+            config: config.clone(),
+        };
+        Ok(fs::write(&self.cache, serde_json::to_vec(&cached)?)?)
+    }
+
+    /// Fetches the configuration from Parse, or reads the local file if Parse cannot be reached.
+    ///
+    /// Returns true if the configuration changed since the last call. The error of Parse is
+    /// returned when there is no local file either.
+    pub async fn reload(&mut self, client: &ParseClient) -> Result<bool, ParseError> {
+        let config = match self.fetch(client).await {
+            Ok(config) => {
+                if let Err(error) = self.write_cache(&config) {
+                    warn!("Cannot write the configuration of {}: {error}", self.serial);
+                }
+                config
+            }
+            Err(error) if self.current.is_some() => {
+                warn!("Keeping the configuration of {}: {error}", self.serial);
+                return Ok(false);
+            }
+            Err(error) => {
+                warn!("Using the local configuration of {}: {error}", self.serial);
+                self.read_cache().map_err(|_| error)?
+            }
+        };
+        let changed = self.current.as_ref() != Some(&config);
+        self.current = Some(config);
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use std::env;
+    use uuid::Uuid;
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn settings() {
+        let mut config = StoreConfig::new("s1".to_string());
+        assert!(config.push_allowed(time(12)));
+        config.push_windows = vec![PushWindow {
+            start: time(22),
+            end: time(6),
+        }];
+        assert!(config.push_allowed(time(23)));
+        assert!(config.push_allowed(time(2)));
+        assert!(!config.push_allowed(time(12)));
+
+        config.markdown_rules = vec![
+            MarkdownRule {
+                categorie: None,
+                discount: 10.,
+            },
+            MarkdownRule {
+                categorie: Some("Crustacés".to_string()),
+                discount: 30.,
+            },
+        ];
+        let frais = CongelState::Frais;
+        assert_eq!(config.markdown(Some("Crustacés"), frais), Some(30.));
+        assert_eq!(config.markdown(Some("Poisson"), frais), Some(10.));
+        assert_eq!(config.markdown(None, frais), Some(10.));
+        let decongele = CongelState::Decongele;
+        assert_eq!(config.markdown(Some("Crustacés"), decongele), None);
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        let body = r#"{"results":[{"objectId":"c1","serial":"s1","pushWindows":[{"start":"06:00:00","end":"21:00:00"}],"vendor":"Hanshow"}]}"#;
+        let (url, server) = mock::serve(vec![
+            mock::response("200 OK", &[], body),
+            mock::response("200 OK", &[], body),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let cache = env::temp_dir().join(format!("store-config-{}.json", Uuid::new_v4()));
+        let mut loader = StoreConfigLoader::new("s1".to_string(), cache.clone());
+        assert!(loader.reload(&client).await.unwrap());
+        assert!(!loader.reload(&client).await.unwrap());
+        assert_eq!(loader.current().unwrap().vendor, Some(EslType::Hanshow));
+        server.join().unwrap();
+
+        let offline = ParseClient::new("app".to_string(), None, "http://127.0.0.1:9".to_string());
+        let mut restarted = StoreConfigLoader::new("s1".to_string(), cache.clone());
+        assert!(restarted.reload(&offline).await.unwrap());
+        assert_eq!(restarted.current(), loader.current());
+        assert_eq!(
+            restarted.current().unwrap().object_id.as_deref(),
+            Some("c1")
+        );
+        fs::remove_file(&cache).unwrap();
+
+        let mut unknown = StoreConfigLoader::new("s2".to_string(), cache);
+        assert!(matches!(
+            unknown.reload(&offline).await,
+            Err(ParseError::Reqwest { .. })
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+use crate::parse::ParseError;
+use crate::pos::parse_csv;
+use crate::runtime;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// A file waiting in the inbox of a [`PriceFileSource`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListedFile {
+    pub name: String,
+    /// The size in bytes, when the source reports it
+    pub size: Option<u64>,
+    /// The last modification time, when the source reports it
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// A location where a supplier drops its price files, such as an SFTP or FTP directory.
+///
+/// Files are read from an inbox and moved to an archive once processed, so every file listed is
+/// a new one. The calls block, the [`Poller`] runs them with [`Runtime::spawn_blocking`].
+///
+/// [`Runtime::spawn_blocking`]: crate::runtime::Runtime::spawn_blocking
+pub trait PriceFileSource {
+    /// Lists the files waiting in the inbox
+    fn list(&mut self) -> Result<Vec<ListedFile>, ParseError>;
+    /// Reads a file of the inbox
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, ParseError>;
+    /// Moves a file of the inbox to the archive under a new name
+    fn archive(&mut self, name: &str, archived_name: &str) -> Result<(), ParseError>;
+}
+
+/// What became of a price file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportStatus {
+    /// The prices were parsed and imported, `updated` is the count returned by the import
+    Imported { prices: usize, updated: usize },
+    /// The file could not be read, parsed or imported
+    Failed { cause: String },
+}
+
+impl ImportStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            ImportStatus::Imported { .. } => "ok",
+            ImportStatus::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// A price file processed by [`Poller::poll`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportedFile {
+    pub name: String,
+    /// The name of the file in the archive, such as `20230601T020000-ok-prices.csv`
+    pub archived_name: String,
+    pub status: ImportStatus,
+}
+
+/// Polls a supplier location, remembering the files listed from one poll to the next.
+pub struct Poller<S> {
+    source: Arc<Mutex<S>>,
+    /// The files listed by the previous poll and not processed yet
+    listed: HashMap<String, ListedFile>,
+}
+
+impl<S: PriceFileSource + Send + 'static> Poller<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source: Arc::new(Mutex::new(source)),
+            listed: HashMap::new(),
+        }
+    }
+
+    /// Processes the files of the inbox that are complete.
+    ///
+    /// A file is processed once it was listed with the same size and modification time by the
+    /// previous poll, so a file still being uploaded waits for the next one: poll at an interval
+    /// longer than the pauses of an upload.
+    ///
+    /// Each file is parsed with [`parse_csv`] and its prices `plu -> cents` passed to `import`,
+    /// which returns how many labels it updated. The file is then archived with its status and
+    /// processing time in its name, whether the import succeeded or not, so a failed file is
+    /// not retried on the next poll: fix it and drop it again. Only listing or archiving errors
+    /// stop the poll.
+    pub async fn poll<F, Fut>(
+        &mut self,
+        now: DateTime<Utc>,
+        mut import: F,
+    ) -> Result<Vec<ImportedFile>, ParseError>
+    where
+        F: FnMut(String, HashMap<String, i64>) -> Fut,
+        Fut: Future<Output = Result<usize, ParseError>>,
+    {
+        let mut files = self.blocking(|source| source.list()).await?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        let previous = std::mem::take(&mut self.listed);
+        let mut processed = vec![];
+        for file in files {
+            if previous.get(&file.name) != Some(&file) {
+                debug!("Waiting for {} to be complete", file.name);
+                self.listed.insert(file.name.clone(), file);
+                continue;
+            }
+            let name = file.name;
+            let status = match self.read_prices(&name).await {
+                Ok(prices) => {
+                    let count = prices.len();
+                    match import(name.clone(), prices).await {
+                        Ok(updated) => ImportStatus::Imported {
+                            prices: count,
+                            updated,
+                        },
+                        Err(e) => ImportStatus::Failed {
+                            cause: e.to_string(),
+                        },
+                    }
+                }
+                Err(e) => ImportStatus::Failed {
+                    cause: e.to_string(),
+                },
+            };
+            match &status {
+                ImportStatus::Imported { prices, updated } => {
+                    info!("Imported {name}: {prices} prices, {updated} labels updated")
+                }
+                ImportStatus::Failed { cause } => warn!("Could not import {name}: {cause}"),
+            }
+            let archived_name =
+                format!("{}-{}-{name}", now.format("%Y%m%dT%H%M%S"), status.label());
+            self.blocking({
+                let (name, archived_name) = (name.clone(), archived_name.clone());
+                move |source| source.archive(&name, &archived_name)
+            })
+            .await?;
+            processed.push(ImportedFile {
+                name,
+                archived_name,
+                status,
+            });
+        }
+        Ok(processed)
+    }
+
+    async fn read_prices(&self, name: &str) -> Result<HashMap<String, i64>, ParseError> {
+        let bytes = self
+            .blocking({
+                let name = name.to_string();
+                move |source| source.read(&name)
+            })
+            .await?;
+        let csv = String::from_utf8(bytes).map_err(|e| ParseError::Import {
+            line: 0,
+            cause: e.to_string(),
+        })?;
+        parse_csv(&csv)
+    }
+
+    /// Runs a blocking call of the source on a blocking thread of the runtime
+    async fn blocking<T, F>(&self, call: F) -> Result<T, ParseError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut S) -> Result<T, ParseError> + Send + 'static,
+    {
+        let source = self.source.clone();
+        runtime::blocking(move || call(&mut source.lock().unwrap())).await?
+    }
+}
+
+#[cfg(any(feature = "sftp", feature = "ftp"))]
+fn io_error(e: impl std::error::Error + Send + Sync + 'static) -> ParseError {
+    ParseError::Io {
+        source: std::io::Error::other(e),
+    }
+}
+
+/// A directory of the local filesystem, such as a mounted share
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalDirectory {
+    pub inbox: PathBuf,
+    pub archive: PathBuf,
+}
+
+impl LocalDirectory {
+    pub fn new(inbox: impl Into<PathBuf>, archive: impl Into<PathBuf>) -> Self {
+        Self {
+            inbox: inbox.into(),
+            archive: archive.into(),
+        }
+    }
+}
+
+impl PriceFileSource for LocalDirectory {
+    fn list(&mut self) -> Result<Vec<ListedFile>, ParseError> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(&self.inbox)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                files.push(ListedFile {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    size: Some(metadata.len()),
+                    modified: metadata.modified().ok().map(DateTime::from),
+                });
+            }
+        }
+        Ok(files)
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, ParseError> {
+        Ok(std::fs::read(self.inbox.join(name))?)
+    }
+
+    fn archive(&mut self, name: &str, archived_name: &str) -> Result<(), ParseError> {
+        std::fs::create_dir_all(&self.archive)?;
+        Ok(std::fs::rename(
+            self.inbox.join(name),
+            self.archive.join(archived_name),
+        )?)
+    }
+}
+
+/// How to log in to an SFTP server
+#[cfg(feature = "sftp")]
+#[derive(Clone, Debug)]
+pub enum SftpAuth {
+    Password(String),
+    /// A private key file, with its passphrase if any
+    Key(PathBuf, Option<String>),
+}
+
+/// A directory of an SFTP server
+#[cfg(feature = "sftp")]
+pub struct SftpSource {
+    sftp: ssh2::Sftp,
+    inbox: PathBuf,
+    archive: PathBuf,
+    // Keeps the connection open for as long as the SFTP channel is used
+    _session: ssh2::Session,
+}
+
+/// Returns the OpenSSH fingerprint of a SHA-256 host key hash, such as
+/// `SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU`
+#[cfg(feature = "sftp")]
+fn fingerprint(hash: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::from("SHA256:");
+    for chunk in hash.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        // OpenSSH leaves out the padding
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(feature = "sftp")]
+impl SftpSource {
+    /// Connects to `addr`, such as `sftp.supplier.com:22`, and creates the archive directory if
+    /// needed.
+    ///
+    /// `host_key` is the SHA-256 fingerprint of the server key, as printed by
+    /// `ssh-keyscan sftp.supplier.com | ssh-keygen -lf -`: the connection fails before
+    /// logging in when the server presents another key.
+    pub fn connect(
+        addr: &str,
+        host_key: &str,
+        user: &str,
+        auth: &SftpAuth,
+        inbox: impl Into<PathBuf>,
+        archive: impl Into<PathBuf>,
+    ) -> Result<Self, ParseError> {
+        let mut session = ssh2::Session::new().map_err(io_error)?;
+        session.set_tcp_stream(std::net::TcpStream::connect(addr)?);
+        session.handshake().map_err(io_error)?;
+        let presented = session
+            .host_key_hash(ssh2::HashType::Sha256)
+            .map(fingerprint);
+        if presented.as_deref() != Some(host_key.trim()) {
+            return Err(ParseError::Io {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!("the host key of {addr} is {presented:?}, expected {host_key}"),
+                ),
+            });
+        }
+        match auth {
+            SftpAuth::Password(password) => session.userauth_password(user, password),
+            SftpAuth::Key(key, passphrase) => {
+                session.userauth_pubkey_file(user, None, key, passphrase.as_deref())
+            }
+        }
+        .map_err(io_error)?;
+        let sftp = session.sftp().map_err(io_error)?;
+        let archive = archive.into();
+        if sftp.stat(&archive).is_err() {
+            sftp.mkdir(&archive, 0o755).map_err(io_error)?;
+        }
+        Ok(Self {
+            sftp,
+            inbox: inbox.into(),
+            archive,
+            _session: session,
+        })
+    }
+}
+
+#[cfg(feature = "sftp")]
+impl PriceFileSource for SftpSource {
+    fn list(&mut self) -> Result<Vec<ListedFile>, ParseError> {
+        Ok(self
+            .sftp
+            .readdir(&self.inbox)
+            .map_err(io_error)?
+            .into_iter()
+            .filter(|(_, stat)| stat.is_file())
+            .filter_map(|(path, stat)| {
+                Some(ListedFile {
+                    name: path.file_name()?.to_string_lossy().into_owned(),
+                    size: stat.size,
+                    modified: stat
+                        .mtime
+                        .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0)),
+                })
+            })
+            .collect())
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, ParseError> {
+        use std::io::Read;
+        let mut file = self.sftp.open(self.inbox.join(name)).map_err(io_error)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn archive(&mut self, name: &str, archived_name: &str) -> Result<(), ParseError> {
+        self.sftp
+            .rename(
+                &self.inbox.join(name),
+                &self.archive.join(archived_name),
+                None,
+            )
+            .map_err(io_error)
+    }
+}
+
+/// A directory of an FTP server.
+///
+/// FTP sends the password and the files in clear text: prefer an SFTP source when the supplier
+/// offers it, or keep the FTP server on a private network.
+#[cfg(feature = "ftp")]
+pub struct FtpSource {
+    ftp: suppaftp::FtpStream,
+    inbox: String,
+    archive: String,
+}
+
+#[cfg(feature = "ftp")]
+impl FtpSource {
+    /// Connects to `addr`, such as `ftp.supplier.com:21`, and creates the archive directory if
+    /// needed
+    pub fn connect(
+        addr: &str,
+        user: &str,
+        password: &str,
+        inbox: &str,
+        archive: &str,
+    ) -> Result<Self, ParseError> {
+        let mut ftp = suppaftp::FtpStream::connect(addr).map_err(io_error)?;
+        ftp.login(user, password).map_err(io_error)?;
+        // Fails when the directory exists, a missing archive fails the first rename instead
+        let _ = ftp.mkdir(archive);
+        Ok(Self {
+            ftp,
+            inbox: inbox.trim_end_matches('/').to_string(),
+            archive: archive.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "ftp")]
+impl PriceFileSource for FtpSource {
+    fn list(&mut self) -> Result<Vec<ListedFile>, ParseError> {
+        let names: Vec<String> = self
+            .ftp
+            .nlst(Some(&self.inbox))
+            .map_err(io_error)?
+            .into_iter()
+            // Some servers list full paths
+            .map(|path| path.rsplit('/').next().unwrap_or_default().to_string())
+            .filter(|name| !name.is_empty() && name != "." && name != "..")
+            .collect();
+        // SIZE and MDTM are extensions, a server without them leaves the fields unset
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                let path = format!("{}/{name}", self.inbox);
+                ListedFile {
+                    size: self.ftp.size(&path).ok().map(|size| size as u64),
+                    modified: self.ftp.mdtm(&path).ok().map(|at| at.and_utc()),
+                    name,
+                }
+            })
+            .collect())
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, ParseError> {
+        Ok(self
+            .ftp
+            .retr_as_buffer(&format!("{}/{name}", self.inbox))
+            .map_err(io_error)?
+            .into_inner())
+    }
+
+    fn archive(&mut self, name: &str, archived_name: &str) -> Result<(), ParseError> {
+        self.ftp
+            .rename(
+                &format!("{}/{name}", self.inbox),
+                &format!("{}/{archived_name}", self.archive),
+            )
+            .map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn poll_directory() {
+        mock::runtime();
+        let root = std::env::temp_dir().join(format!("supplier-{}", uuid::Uuid::new_v4()));
+        let source = LocalDirectory::new(root.join("inbox"), root.join("archive"));
+        let (inbox, archive) = (source.inbox.clone(), source.archive.clone());
+        std::fs::create_dir_all(&inbox).unwrap();
+        std::fs::write(inbox.join("a.csv"), "plu;prix\n123;18,90\n").unwrap();
+        std::fs::write(inbox.join("b.csv"), "plu;prix\n123;abc\n").unwrap();
+        let now = Utc.with_ymd_and_hms(2023, 6, 1, 2, 0, 0).unwrap();
+        let mut poller = Poller::new(source);
+
+        // Listed for the first time, or still growing
+        let files = poller.poll(now, |_, _| async { Ok(0) }).await.unwrap();
+        assert!(files.is_empty());
+        std::fs::write(inbox.join("a.csv"), "plu;prix\n123;18,90\n456;2,50\n").unwrap();
+        let files = poller.poll(now, |_, _| async { Ok(0) }).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "b.csv");
+
+        // Dropped again, it waits for the next poll
+        std::fs::write(inbox.join("b.csv"), "plu;prix\n123;abc\n").unwrap();
+        let files = poller
+            .poll(now, |name, prices| async move {
+                assert_eq!(name, "a.csv");
+                assert_eq!(prices["123"], 1890);
+                Ok(1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].status,
+            ImportStatus::Imported {
+                prices: 2,
+                updated: 1
+            }
+        );
+        assert_eq!(files[0].archived_name, "20230601T020000-ok-a.csv");
+        assert!(archive.join("20230601T020000-failed-b.csv").exists());
+        assert_eq!(std::fs::read_dir(&inbox).unwrap().count(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "sftp")]
+    #[test]
+    fn host_key_fingerprint() {
+        use sha2::{Digest, Sha256};
+        assert_eq!(
+            fingerprint(&Sha256::digest(b"")),
+            "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+        assert_eq!(fingerprint(b"ab"), "SHA256:YWI");
+        assert_eq!(fingerprint(b"abc"), "SHA256:YWJj");
+    }
+}
@@ -0,0 +1,111 @@
+use uuid::Uuid;
+
+/// The W3C trace context of the operation a request is part of.
+///
+/// It is sent in the `traceparent` and `tracestate` headers of the Parse requests, see
+/// [`ParseClient::with_trace_context`], so the server spans of a slow label update are
+/// attached to the trace of the caller. OpenTelemetry propagators read and write the same
+/// headers.
+///
+/// [`ParseClient::with_trace_context`]: crate::parse::ParseClient::with_trace_context
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits
+    pub trace_id: String,
+    /// The id of the caller span, 16 lowercase hex digits
+    pub parent_id: String,
+    pub sampled: bool,
+    /// The vendor-specific `tracestate`, passed along unchanged
+    pub state: Option<String>,
+}
+
+fn is_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && id.bytes().any(|b| b != b'0')
+}
+
+fn random_id(len: usize) -> String {
+    Uuid::new_v4().to_simple().to_string()[..len].to_string()
+}
+
+impl TraceContext {
+    /// Starts a new sampled trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_id(32),
+            parent_id: random_id(16),
+            sampled: true,
+            state: None,
+        }
+    }
+
+    /// Reads the headers of an incoming request, returns `None` when `traceparent` is invalid
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if version != "00" || parts.next().is_some() {
+            return None;
+        }
+        if !is_id(trace_id, 32) || !is_id(parent_id, 16) || flags.len() != 2 {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            sampled: flags & 1 == 1,
+            state: tracestate.map(str::to_string),
+        })
+    }
+
+    /// The context of a new span of this trace, such as one stage of a pipeline
+    pub fn child(&self) -> Self {
+        Self {
+            parent_id: random_id(16),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the `traceparent` header value
+    pub fn traceparent(&self) -> String {
+        let flags = if self.sampled { "01" } else { "00" };
+        format!("00-{}-{}-{flags}", self.trace_id, self.parent_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(header, Some("congo=t61rcWkgMzE")).unwrap();
+        assert!(context.sampled);
+        assert_eq!(context.traceparent(), header);
+        let child = context.child();
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_ne!(child.parent_id, context.parent_id);
+        assert!(TraceContext::parse(&TraceContext::new_root().traceparent(), None).is_some());
+
+        assert!(TraceContext::parse(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            None
+        )
+        .is_none());
+        assert!(TraceContext::parse(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            None
+        )
+        .is_none());
+        assert!(TraceContext::parse(
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            None
+        )
+        .is_none());
+    }
+}
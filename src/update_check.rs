@@ -0,0 +1,169 @@
+use crate::parse::{ParseClient, ParseError};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cmp::Ordering;
+
+/// The Parse class holding the release manifest
+pub const RELEASE_CLASS: &str = "classes/Release";
+
+/// The version of this crate
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A release published on a channel (`stable`, `beta`...)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub channel: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_notes: Option<String>,
+}
+
+/// The releases more recent than the running version
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateAvailable {
+    pub current: String,
+    /// The newer releases, from the oldest to the latest
+    pub releases: Vec<Release>,
+}
+
+impl UpdateAvailable {
+    pub fn latest(&self) -> &Release {
+        self.releases
+            .last()
+            .expect("an update always has at least one release")
+    }
+}
+
+/// Parses a `major.minor.patch` version, a leading `v` and a pre-release suffix are ignored
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Compares two versions, `None` if one of them cannot be parsed
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    Some(parse_version(a)?.cmp(&parse_version(b)?))
+}
+
+fn is_newer(release: &Release, current: &str) -> bool {
+    match compare_versions(&release.version, current) {
+        Some(ordering) => ordering == Ordering::Greater,
+        None => {
+            warn!("Ignoring release with invalid version {}", release.version);
+            false
+        }
+    }
+}
+
+/// Returns the releases of a manifest that are more recent than `current`, if any
+pub fn check(current: &str, releases: Vec<Release>) -> Option<UpdateAvailable> {
+    let mut newer: Vec<Release> = releases
+        .into_iter()
+        .filter(|release| is_newer(release, current))
+        .collect();
+    if newer.is_empty() {
+        return None;
+    }
+    newer.sort_by(|a, b| {
+        compare_versions(&a.version, &b.version).expect("versions have been checked")
+    });
+    Some(UpdateAvailable {
+        current: current.to_string(),
+        releases: newer,
+    })
+}
+
+/// Checks for updates of `current` against the releases of a channel stored in Parse
+pub async fn check_parse(
+    client: &ParseClient,
+    current: &str,
+    channel: &str,
+) -> Result<Option<UpdateAvailable>, ParseError> {
+    let releases: Vec<Release> = client
+        .fetch(RELEASE_CLASS.to_string(), json!({ "channel": channel }))
+        .await?;
+    Ok(check(current, releases))
+}
+
+/// Checks for updates of `current` against a JSON manifest (an array of releases) served at
+/// `url`, only the releases of `channel` are considered.
+///
+/// The manifest is fetched with the HTTP client of `client`, without the Parse credentials.
+pub async fn check_url(
+    client: &ParseClient,
+    url: &str,
+    current: &str,
+    channel: &str,
+) -> Result<Option<UpdateAvailable>, ParseError> {
+    let releases: Vec<Release> = client
+        .get_client()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let releases = releases
+        .into_iter()
+        .filter(|release| release.channel == channel)
+        .collect();
+    Ok(check(current, releases))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str) -> Release {
+        Release {
+            channel: "stable".to_string(),
+            version: version.to_string(),
+            release_notes: Some(format!("Notes for {version}")),
+        }
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("v1.2"), Some((1, 2, 0)));
+        assert_eq!(parse_version("1.2.3-beta.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("latest"), None);
+        assert_eq!(compare_versions("0.10.0", "0.9.1"), Some(Ordering::Greater));
+        assert!(parse_version(CRATE_VERSION).is_some());
+    }
+
+    #[test]
+    fn updates() {
+        let manifest = vec![
+            release("0.3.0"),
+            release("0.1.0"),
+            release("invalid"),
+            release("0.2.0"),
+        ];
+        let update = check("0.1.0", manifest.clone()).unwrap();
+        assert_eq!(update.releases, vec![release("0.2.0"), release("0.3.0")]);
+        assert_eq!(update.latest().version, "0.3.0");
+        assert!(check("0.3.0", manifest).is_none());
+    }
+
+    #[tokio::test]
+    async fn manifest() {
+        let body = serde_json::to_string(&[release("0.2.0")]).unwrap();
+        let (url, server) = crate::mock::serve(vec![crate::mock::response("200 OK", &[], &body)]);
+        let client = ParseClient::new("app".to_string(), Some("key".to_string()), url.clone());
+        let update = check_url(&client, &format!("{url}/releases.json"), "0.1.0", "stable")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.latest().version, "0.2.0");
+        let requests = server.join().unwrap();
+        assert!(!requests[0].contains("x-parse-rest-api-key"));
+    }
+}
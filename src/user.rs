@@ -0,0 +1,141 @@
+use crate::parse::{ParseClient, ParseError};
+use crate::pointer::ParseClass;
+use crate::protocol;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// A user of the Parse application, such as a shop tablet operator.
+///
+/// A logged in user carries a session token, the requests sent with
+/// [`ParseUser::client`] are made on its behalf and are subject to its ACLs and roles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseUser {
+    #[serde(skip_serializing)]
+    pub object_id: Option<String>,
+    pub username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing)]
+    pub session_token: Option<String>,
+}
+
+/// The response of a sign up
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignUpResponse {
+    object_id: String,
+    session_token: String,
+}
+
+impl ParseUser {
+    /// Creates a user, it is logged in
+    pub async fn sign_up(
+        client: &ParseClient,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+    ) -> Result<Self, ParseError> {
+        let mut body = json!({ "username": username, "password": password });
+        if let Some(email) = email {
+            body["email"] = json!(email);
+        }
+        let (response, _) = client.send(client.protocol().save("users", &body)?).await?;
+        let created: SignUpResponse = protocol::interpret(&response, StatusCode::CREATED)?;
+        Ok(Self {
+            object_id: Some(created.object_id),
+            username: username.to_string(),
+            email: email.map(str::to_string),
+            session_token: Some(created.session_token),
+        })
+    }
+
+    /// Logs a user in and opens a session
+    pub async fn log_in(
+        client: &ParseClient,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, ParseError> {
+        let body = json!({ "username": username, "password": password });
+        let (response, _) = client.send(client.protocol().save("login", &body)?).await?;
+        protocol::interpret(&response, StatusCode::OK)
+    }
+
+    /// Returns the user of the session of a client, see [`ParseClient::with_session_token`]
+    pub async fn me(client: &ParseClient) -> Result<Self, ParseError> {
+        let token = client.session_token().ok_or(ParseError::Session)?;
+        let mut user: Self = client.get_resource("users/me".to_string()).await?;
+        user.session_token.get_or_insert_with(|| token.to_string());
+        Ok(user)
+    }
+
+    /// Returns a client sending its requests in the session of this user
+    pub fn client(&self, client: &ParseClient) -> Result<ParseClient, ParseError> {
+        let token = self.session_token.clone().ok_or(ParseError::Session)?;
+        Ok(client.clone().with_session_token(token))
+    }
+
+    /// Closes the session of this user, its session token stops working
+    pub async fn log_out(&mut self, client: &ParseClient) -> Result<(), ParseError> {
+        let session = self.client(client)?;
+        let (response, _) = session
+            .send(session.protocol().save("logout", &json!({}))?)
+            .await?;
+        protocol::interpret_empty(&response, StatusCode::OK)?;
+        self.session_token = None;
+        Ok(())
+    }
+}
+
+impl ParseClass for ParseUser {
+    const CLASS_NAME: &'static str = "_User";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[tokio::test]
+    async fn session() {
+        let user = r#"{"objectId":"u1","username":"marie","email":"marie@example.com","sessionToken":"r:abc"}"#;
+        let (url, server) = mock::serve(vec![
+            mock::response(
+                "201 Created",
+                &[],
+                r#"{"objectId":"u1","createdAt":"now","sessionToken":"r:new"}"#,
+            ),
+            mock::response("200 OK", &[], user),
+            mock::response("200 OK", &[], user),
+            mock::response("200 OK", &[], "{}"),
+        ]);
+        let client = ParseClient::new("app".to_string(), None, url);
+        let created = ParseUser::sign_up(&client, "marie", "secret", Some("marie@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(created.session_token.as_deref(), Some("r:new"));
+
+        let mut user = ParseUser::log_in(&client, "marie", "secret").await.unwrap();
+        assert_eq!(user.session_token.as_deref(), Some("r:abc"));
+        let session = user.client(&client).unwrap();
+        assert_eq!(ParseUser::me(&session).await.unwrap(), user);
+        user.log_out(&client).await.unwrap();
+        assert_eq!(user.session_token, None);
+        assert!(matches!(user.client(&client), Err(ParseError::Session)));
+        assert!(matches!(
+            ParseUser::me(&client).await,
+            Err(ParseError::Session)
+        ));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /users"));
+        assert!(requests[0].contains(r#""email":"marie@example.com""#));
+        assert!(requests[1].starts_with("POST /login"));
+        assert!(!requests[1].contains("x-parse-session-token"));
+        assert!(requests[2].starts_with("GET /users/me"));
+        assert!(requests[2].contains("x-parse-session-token: r:abc"));
+        assert!(requests[3].starts_with("POST /logout"));
+        assert!(requests[3].contains("x-parse-session-token: r:abc"));
+    }
+}
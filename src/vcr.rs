@@ -0,0 +1,318 @@
+//! Recording and replay of the Parse HTTP interactions, for deterministic offline tests.
+//!
+//! Record a cassette once against a real server, then replay it in tests:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), esl_utils::parse::ParseError> {
+//! use esl_utils::parse::ParseClient;
+//! use esl_utils::vcr::Cassette;
+//! use std::sync::Arc;
+//!
+//! let cassette = Arc::new(Cassette::record("tests/cassettes/sync.json"));
+//! let client = ParseClient::from_env().with_cassette(cassette.clone());
+//! // run the flow with the client...
+//! cassette.save()?;
+//!
+//! let cassette = Arc::new(Cassette::replay("tests/cassettes/sync.json")?);
+//! let client = ParseClient::new("app".to_string(), None, "http://offline".to_string())
+//!     .with_cassette(cassette);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::parse::ParseError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The body fields replaced by [`REDACTED`] in the cassettes
+const SECRET_FIELDS: &[&str] = &["password", "sessionToken"];
+
+/// The response headers left out of the cassettes
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "set-cookie",
+    "x-parse-session-token",
+    "x-parse-master-key",
+    "x-parse-rest-api-key",
+];
+
+/// What the secrets of the bodies are replaced with
+pub const REDACTED: &str = "[redacted]";
+
+/// Replaces the values of the [`SECRET_FIELDS`] of a JSON body, at any depth.
+///
+/// Bodies without any secret, or that are not JSON, are kept as is.
+fn redact(body: String) -> String {
+    fn redact_value(value: &mut Value) -> bool {
+        match value {
+            Value::Object(fields) => {
+                let mut redacted = false;
+                for (name, value) in fields.iter_mut() {
+                    if SECRET_FIELDS.contains(&name.as_str()) && value.is_string() {
+                        *value = Value::from(REDACTED);
+                        redacted = true;
+                    } else {
+                        redacted |= redact_value(value);
+                    }
+                }
+                redacted
+            }
+            Value::Array(values) => {
+                let mut redacted = false;
+                for value in values {
+                    redacted |= redact_value(value);
+                }
+                redacted
+            }
+            _ => false,
+        }
+    }
+    match serde_json::from_str::<Value>(&body) {
+        Ok(mut value) => {
+            if redact_value(&mut value) {
+                value.to_string()
+            } else {
+                body
+            }
+        }
+        Err(_) => body,
+    }
+}
+
+/// A request as matched on replay.
+///
+/// Headers are left out of cassettes so keys and session tokens are not written to disk, and
+/// the url is only its path and query so a cassette replays against any server url. Passwords
+/// and session tokens in bodies are [`REDACTED`], on record and on replay alike.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// The interactions of a cassette file, see [`ParseClient::with_cassette`]
+///
+/// [`ParseClient::with_cassette`]: crate::parse::ParseClient::with_cassette
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    /// The interactions and whether they have been replayed
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl RecordedRequest {
+    pub(crate) fn of(request: &http::Request<Vec<u8>>) -> Self {
+        let url = request
+            .uri()
+            .path_and_query()
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        Self {
+            method: request.method().to_string(),
+            url,
+            body: redact(String::from_utf8_lossy(request.body()).into_owned()),
+        }
+    }
+}
+
+impl Cassette {
+    /// An empty cassette recording the interactions sent through it, see [`Cassette::save`]
+    pub fn record<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record,
+            interactions: Mutex::new(vec![]),
+        }
+    }
+
+    /// Reads a recorded cassette, its requests are answered without any network access
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let interactions: Vec<Interaction> = serde_json::from_slice(&fs::read(&path)?)?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(interactions.into_iter().map(|i| (i, false)).collect()),
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mode == Mode::Record
+    }
+
+    /// Writes the recorded interactions to the cassette file
+    pub fn save(&self) -> Result<(), ParseError> {
+        let interactions: Vec<Interaction> = self
+            .interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(interaction, _)| interaction.clone())
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&interactions)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn push(&self, request: RecordedRequest, response: &http::Response<Vec<u8>>) {
+        let headers = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| !SECRET_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let response = RecordedResponse {
+            status: response.status().as_u16(),
+            headers,
+            body: redact(String::from_utf8_lossy(response.body()).into_owned()),
+        };
+        let interaction = Interaction { request, response };
+        self.interactions.lock().unwrap().push((interaction, false));
+    }
+
+    /// Answers a request with the first identical recorded request not replayed yet, so a
+    /// request sent twice gets the two responses in the order they were recorded
+    pub(crate) fn answer(
+        &self,
+        request: &RecordedRequest,
+    ) -> Result<http::Response<Vec<u8>>, ParseError> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let (interaction, replayed) = interactions
+            .iter_mut()
+            .find(|(interaction, replayed)| !*replayed && interaction.request == *request)
+            .ok_or_else(|| ParseError::Cassette {
+                cause: format!(
+                    "no recorded interaction for {} {} in {}",
+                    request.method,
+                    request.url,
+                    self.path.display()
+                ),
+            })?;
+        *replayed = true;
+        let mut response = http::Response::builder().status(interaction.response.status);
+        for (name, value) in &interaction.response.headers {
+            response = response.header(name, value);
+        }
+        response
+            .body(interaction.response.body.clone().into_bytes())
+            .map_err(|error| ParseError::Cassette {
+                cause: error.to_string(),
+            })
+    }
+
+    /// Returns the recorded requests that have not been replayed
+    pub fn unused(&self) -> Vec<RecordedRequest> {
+        self.interactions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, replayed)| !replayed)
+            .map(|(interaction, _)| interaction.request.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use crate::parse::ParseClient;
+    use crate::user::ParseUser;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn record_replay() {
+        let path = std::env::temp_dir().join(format!("esl-utils-{}.json", uuid::Uuid::new_v4()));
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &["ETag: \"v1\""],
+            r#"{"results":[{"objectId":"a1"}]}"#,
+        )]);
+        let cassette = Arc::new(Cassette::record(&path));
+        let client = ParseClient::new("app".to_string(), Some("key".to_string()), url)
+            .with_cassette(cassette.clone());
+        let query = json!({ "serial": "s1" });
+        let recorded: Vec<Value> = client
+            .fetch("classes/Esl".to_string(), &query)
+            .await
+            .unwrap();
+        server.join().unwrap();
+        cassette.save().unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("key"));
+
+        let cassette = Arc::new(Cassette::replay(&path).unwrap());
+        let offline = ParseClient::new("app".to_string(), None, "http://127.0.0.1:9".to_string())
+            .with_cassette(cassette.clone());
+        let replayed: Vec<Value> = offline
+            .fetch("classes/Esl".to_string(), &query)
+            .await
+            .unwrap();
+        assert_eq!(replayed, recorded);
+        assert!(cassette.unused().is_empty());
+        assert!(matches!(
+            offline
+                .fetch::<Value, _>("classes/Esl".to_string(), &query)
+                .await,
+            Err(ParseError::Cassette { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn redacts_secrets() {
+        let path = std::env::temp_dir().join(format!("esl-utils-{}.json", uuid::Uuid::new_v4()));
+        let (url, server) = mock::serve(vec![mock::response(
+            "200 OK",
+            &["Set-Cookie: session=r:cookie"],
+            r#"{"objectId":"u1","username":"marie","sessionToken":"r:token"}"#,
+        )]);
+        let cassette = Arc::new(Cassette::record(&path));
+        let client = ParseClient::new("app".to_string(), None, url).with_cassette(cassette.clone());
+        ParseUser::log_in(&client, "marie", "s3cret").await.unwrap();
+        server.join().unwrap();
+        cassette.save().unwrap();
+        let recorded = fs::read_to_string(&path).unwrap();
+        assert!(!recorded.contains("s3cret"));
+        assert!(!recorded.contains("r:token"));
+        assert!(!recorded.contains("r:cookie"));
+        assert!(recorded.contains(REDACTED));
+
+        let cassette = Arc::new(Cassette::replay(&path).unwrap());
+        let offline = ParseClient::new("app".to_string(), None, "http://127.0.0.1:9".to_string())
+            .with_cassette(cassette.clone());
+        let user = ParseUser::log_in(&offline, "marie", "s3cret")
+            .await
+            .unwrap();
+        assert_eq!(user.username, "marie");
+        assert!(cassette.unused().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
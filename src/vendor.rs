@@ -0,0 +1,116 @@
+use crate::generic_esl::EslType;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// An error returned by an ESL vendor (Hanshow, Pricer, EasyVCO) when pushing labels.
+///
+/// Vendor clients map their responses to these causes so pushes can be retried per cause,
+/// see [`VendorError::retry_after`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum VendorError {
+    Auth {
+        vendor: EslType,
+        cause: String,
+    },
+    Quota {
+        vendor: EslType,
+        retry_after: Option<Duration>,
+    },
+    LabelNotFound {
+        vendor: EslType,
+        esl_id: String,
+    },
+    ImageTooLarge {
+        vendor: EslType,
+        size: usize,
+        max: usize,
+    },
+    OfflineGateway {
+        vendor: EslType,
+        gateway_id: String,
+    },
+    Other {
+        vendor: EslType,
+        cause: String,
+    },
+}
+
+impl fmt::Display for VendorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VendorError::Auth { vendor, cause } => {
+                write!(f, "{vendor:?} refused our credentials: {cause}")
+            }
+            VendorError::Quota { vendor, .. } => write!(f, "{vendor:?} quota exceeded"),
+            VendorError::LabelNotFound { vendor, esl_id } => {
+                write!(f, "{vendor:?} does not know the label {esl_id}")
+            }
+            VendorError::ImageTooLarge { vendor, size, max } => write!(
+                f,
+                "The image is too large for {vendor:?}: {size} bytes, at most {max}"
+            ),
+            VendorError::OfflineGateway { vendor, gateway_id } => {
+                write!(f, "The {vendor:?} gateway {gateway_id} is offline")
+            }
+            VendorError::Other { vendor, cause } => write!(f, "{vendor:?} error: {cause}"),
+        }
+    }
+}
+
+impl Error for VendorError {}
+
+/// The delay before retrying a push refused by a quota, when the vendor does not give one
+const DEFAULT_QUOTA_DELAY: Duration = Duration::from_secs(60);
+/// The delay before retrying a push through an offline gateway
+const OFFLINE_DELAY: Duration = Duration::from_secs(300);
+
+impl VendorError {
+    pub fn vendor(&self) -> &EslType {
+        match self {
+            VendorError::Auth { vendor, .. }
+            | VendorError::Quota { vendor, .. }
+            | VendorError::LabelNotFound { vendor, .. }
+            | VendorError::ImageTooLarge { vendor, .. }
+            | VendorError::OfflineGateway { vendor, .. }
+            | VendorError::Other { vendor, .. } => vendor,
+        }
+    }
+
+    /// Returns the delay after which the push can be retried, `None` when retrying the same
+    /// push cannot succeed (bad credentials, unknown label, image too large)
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            VendorError::Quota { retry_after, .. } => {
+                Some(retry_after.unwrap_or(DEFAULT_QUOTA_DELAY))
+            }
+            VendorError::OfflineGateway { .. } => Some(OFFLINE_DELAY),
+            VendorError::Other { .. } => Some(DEFAULT_QUOTA_DELAY),
+            VendorError::Auth { .. }
+            | VendorError::LabelNotFound { .. }
+            | VendorError::ImageTooLarge { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries() {
+        let quota = VendorError::Quota {
+            vendor: EslType::Pricer,
+            retry_after: Some(Duration::from_secs(5)),
+        };
+        assert_eq!(quota.retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(quota.vendor(), &EslType::Pricer);
+        assert_eq!(quota.to_string(), "Pricer quota exceeded");
+        let not_found = VendorError::LabelNotFound {
+            vendor: EslType::Hanshow,
+            esl_id: "abc".to_string(),
+        };
+        assert_eq!(not_found.retry_after(), None);
+        assert_eq!(not_found.to_string(), "Hanshow does not know the label abc");
+    }
+}
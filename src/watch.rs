@@ -0,0 +1,129 @@
+use crate::parse::ParseError;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A change of a watched field of an object
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChanged {
+    pub object_id: String,
+    /// The Parse column name of the field
+    pub field: String,
+    /// The previous value, `None` the first time the object is seen or when the field was unset
+    pub old: Option<Value>,
+    /// The new value, `None` when the field is unset
+    pub new: Option<Value>,
+}
+
+/// Watches some fields of objects and reports their changes.
+///
+/// Objects are passed to [`FieldWatcher::observe`] as they are received, from a poll or a
+/// subscription. Only the watched fields are kept and compared, so a change of `prix` is
+/// reported but an unrelated column change is not.
+#[derive(Clone, Debug, Default)]
+pub struct FieldWatcher {
+    fields: Vec<String>,
+    /// The watched fields of each object seen so far, by objectId
+    last: HashMap<String, HashMap<String, Value>>,
+}
+
+impl FieldWatcher {
+    /// Watches Parse columns, such as `prix` or `printed`
+    pub fn new(fields: &[&str]) -> Self {
+        Self {
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            last: HashMap::new(),
+        }
+    }
+
+    /// Returns the changes of the watched fields of an object since it was last observed.
+    ///
+    /// Objects without an objectId are ignored. The Parse models do not serialize theirs, watch
+    /// them as the `serde_json::Value`s fetched from Parse instead.
+    pub fn observe<T: Serialize>(&mut self, object: &T) -> Result<Vec<FieldChanged>, ParseError> {
+        let value = serde_json::to_value(object)?;
+        let object_id = match value.get("objectId").and_then(Value::as_str) {
+            Some(object_id) => object_id.to_string(),
+            None => return Ok(vec![]),
+        };
+        let current: HashMap<String, Value> = self
+            .fields
+            .iter()
+            .filter_map(|field| Some((field.clone(), value.get(field)?.clone())))
+            .filter(|(_, value)| !value.is_null())
+            .collect();
+        let previous = self.last.insert(object_id.clone(), current.clone());
+        let previous = previous.unwrap_or_default();
+        Ok(self
+            .fields
+            .iter()
+            .filter(|field| previous.get(*field) != current.get(*field))
+            .map(|field| FieldChanged {
+                object_id: object_id.clone(),
+                field: field.clone(),
+                old: previous.get(field).cloned(),
+                new: current.get(field).cloned(),
+            })
+            .collect())
+    }
+
+    /// Observes a batch of objects, such as the results of a poll
+    pub fn observe_all<T: Serialize>(
+        &mut self,
+        objects: &[T],
+    ) -> Result<Vec<FieldChanged>, ParseError> {
+        let mut changes = vec![];
+        for object in objects {
+            changes.extend(self.observe(object)?);
+        }
+        Ok(changes)
+    }
+
+    /// Forgets an object, after it was deleted
+    pub fn forget(&mut self, object_id: &str) {
+        self.last.remove(object_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic_esl::GenericEsl;
+    use crate::mock;
+
+    #[test]
+    fn changes() {
+        let mut watcher = FieldWatcher::new(&["prix", "printed"]);
+        let esl = GenericEsl {
+            object_id: Some("abc".to_string()),
+            ..mock::esl()
+        };
+        let first = watcher.observe(&esl).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].old, None);
+        assert_eq!(first[0].new, Some(Value::from("18,90")));
+
+        let renamed = GenericEsl {
+            nom: "Lieu".to_string(),
+            ..esl.clone()
+        };
+        assert!(watcher.observe(&renamed).unwrap().is_empty());
+
+        let repriced = GenericEsl {
+            prix: "16,90".to_string(),
+            ..esl.clone()
+        };
+        assert_eq!(
+            watcher.observe_all(&[repriced]).unwrap(),
+            vec![FieldChanged {
+                object_id: "abc".to_string(),
+                field: "prix".to_string(),
+                old: Some(Value::from("18,90")),
+                new: Some(Value::from("16,90")),
+            }]
+        );
+        assert!(watcher.observe(&mock::esl()).unwrap().is_empty());
+        watcher.forget("abc");
+        assert_eq!(watcher.observe(&esl).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::campaign::Campaign;
+use crate::gateway::Gateway;
+use crate::generic_esl::GenericEsl;
+use crate::location::Location;
+use crate::price_zone::PriceZone;
+use crate::stock::StockUpdate;
+use crate::store::Store;
+use crate::update_check::Release;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A recorded wire format of a struct, in `fixtures/wire`.
+///
+/// The latest version of each struct must match its current serialization. When a format
+/// changes on purpose, record the new format as a new version and keep the previous one with
+/// a `migration` describing how existing data and consumers are migrated: previous versions
+/// must still be readable.
+struct Fixture {
+    file: &'static str,
+    json: &'static str,
+    migration: Option<&'static str>,
+}
+
+macro_rules! fixture {
+    ($file:literal) => {
+        fixture!($file, None)
+    };
+    ($file:literal, $migration:expr) => {
+        Fixture {
+            file: $file,
+            json: include_str!(concat!("../fixtures/wire/", $file)),
+            migration: $migration,
+        }
+    };
+}
+
+/// Checks the versions of a struct, from the oldest to the latest
+fn check<T: Serialize + DeserializeOwned>(versions: &[Fixture]) {
+    let (latest, previous) = versions.split_last().expect("no recorded version");
+    for fixture in previous {
+        assert!(
+            fixture.migration.is_some_and(|m| !m.is_empty()),
+            "{} is a previous version without a documented migration",
+            fixture.file
+        );
+        if let Err(error) = serde_json::from_str::<T>(fixture.json) {
+            panic!("{} can no longer be read: {error}", fixture.file);
+        }
+    }
+    let recorded: Value = serde_json::from_str(latest.json).unwrap();
+    let object: T = serde_json::from_value(recorded.clone())
+        .unwrap_or_else(|error| panic!("{} can no longer be read: {error}", latest.file));
+    assert_eq!(
+        serde_json::to_value(&object).unwrap(),
+        recorded,
+        "the serialization differs from {}, record a new version",
+        latest.file
+    );
+}
+
+#[test]
+fn generic_esl() {
+    check::<GenericEsl>(&[fixture!("generic_esl.v1.json")]);
+}
+
+#[test]
+fn models() {
+    check::<Store>(&[fixture!("store.v1.json")]);
+    check::<Location>(&[fixture!("location.v1.json")]);
+    check::<Gateway>(&[
+        fixture!(
+            "gateway.v1.json",
+            Some(concat!(
+                "lastSeen and lastSync become Parse Dates: their String columns are recreated ",
+                "as Date columns, the strings are still read"
+            ))
+        ),
+        fixture!("gateway.v2.json"),
+    ]);
+    check::<Campaign>(&[
+        fixture!(
+            "campaign.v1.json",
+            Some(concat!(
+                "startDate and endDate become Parse Dates: their String columns are recreated ",
+                "as Date columns, the strings are still read"
+            ))
+        ),
+        fixture!("campaign.v2.json"),
+    ]);
+    check::<PriceZone>(&[fixture!("price_zone.v1.json")]);
+    check::<StockUpdate>(&[fixture!("stock_update.v1.json")]);
+    check::<Release>(&[fixture!("release.v1.json")]);
+}